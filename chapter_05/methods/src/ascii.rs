@@ -0,0 +1,286 @@
+/* Terminal rendering.
+ *
+ * Draws rectangles with box-drawing characters, for a quick look without
+ * leaving the terminal:
+ *
+ *   ┌────────────────┬───────┐
+ *   │                │       │
+ *   │           40x20│       │
+ *   ├────────────────┘       │
+ *   │                   50x30│
+ *   └────────────────────────┘
+ *
+ * The drawing is scaled to fit the terminal. A character cell is about twice
+ * as tall as it is wide, so rows are squeezed by 'cell_aspect' to keep the
+ * proportions of the rectangles. Where edges meet, the matching junction
+ * character is used. Each rectangle is labelled with its dimensions in its
+ * bottom-right corner when there is room. */
+
+use std::env;
+
+use crate::geometry::{self, Point, Rect};
+use crate::packing::Packing;
+use crate::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsciiOptions {
+    /* Widest drawing allowed, in characters. */
+    pub max_width: usize,
+    /* Tallest drawing allowed, in lines. */
+    pub max_height: usize,
+    /* Height of a character cell divided by its width. */
+    pub cell_aspect: f64,
+}
+
+impl Default for AsciiOptions {
+    fn default() -> AsciiOptions {
+        AsciiOptions {
+            max_width: 80,
+            max_height: 40,
+            cell_aspect: 2.0,
+        }
+    }
+}
+
+impl AsciiOptions {
+    /* Uses the width of the terminal when the shell exports $COLUMNS. */
+    pub fn for_terminal() -> AsciiOptions {
+        let mut options = AsciiOptions::default();
+        if let Some(columns) = env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+            options.max_width = columns;
+        }
+        options
+    }
+}
+
+pub fn render(rect: &Rectangle, options: &AsciiOptions) -> String {
+    render_nested(rect, &[], options)
+}
+
+/* Draws the rectangles the way 'can_hold' compares them: all anchored at
+ * the top-left corner of the container. */
+pub fn render_nested(
+    container: &Rectangle,
+    contents: &[Rectangle],
+    options: &AsciiOptions,
+) -> String {
+    let mut rects = vec![Rect::at(Point::origin(), *container)];
+    rects.extend(contents.iter().map(|r| Rect::at(Point::origin(), *r)));
+    render_rects(&rects, options)
+}
+
+pub fn render_packing(packing: &Packing, options: &AsciiOptions) -> String {
+    let mut rects = vec![Rect::at(Point::origin(), packing.container)];
+    rects.extend(packing.placements.iter().map(|p| p.rect));
+    render_rects(&rects, options)
+}
+
+/* Draws positioned rects. The drawing starts at the top-left corner of
+ * the area they cover. */
+pub fn render_rects(rects: &[Rect], options: &AsciiOptions) -> String {
+    let (left, top, right, bottom) = match geometry::extent(rects) {
+        Some(edges) => edges,
+        None => return String::new(),
+    };
+
+    /* Columns per unit of width, leaving one column for the right edge. */
+    let by_width = (options.max_width.max(2) - 1) as f64 / ((right - left) as f64).max(1.0);
+    let by_height = (options.max_height.max(2) - 1) as f64 * options.cell_aspect
+        / ((bottom - top) as f64).max(1.0);
+    let scale_x = by_width.min(by_height);
+    let scale_y = scale_x / options.cell_aspect;
+
+    let col = |x: i64| ((x - left) as f64 * scale_x).round() as usize;
+    let row = |y: i64| ((y - top) as f64 * scale_y).round() as usize;
+
+    let mut grid = Grid::new(col(right) + 1, row(bottom) + 1);
+    let mut boxes = Vec::with_capacity(rects.len());
+    for rect in rects {
+        let (c0, r0) = (col(rect.left()), row(rect.top()));
+        let c1 = col(rect.right()).max(c0 + 1).min(grid.width - 1);
+        let r1 = row(rect.bottom()).max(r0 + 1).min(grid.height - 1);
+        grid.draw_box(c0, r0, c1, r1);
+        boxes.push((rect, c0, r0, c1, r1));
+    }
+
+    let mut chars = grid.to_chars();
+    for (rect, c0, r0, c1, r1) in boxes {
+        let label: Vec<char> = rect.size.to_string().chars().collect();
+        if r1 - r0 < 2 || c1 - c0 <= label.len() {
+            continue;
+        }
+        let line = &mut chars[r1 - 1];
+        let start = c1 - label.len();
+        if line[start..c1].iter().all(|&c| c == ' ') {
+            line[start..c1].copy_from_slice(&label);
+        }
+    }
+
+    chars
+        .iter()
+        .map(|line| {
+            let mut line: String = line.iter().collect();
+            line.truncate(line.trim_end().len());
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/* Each cell records which directions lines leave it in, so that crossing
+ * and touching edges turn into the right junction characters. */
+struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    fn connect(&mut self, col: usize, row: usize, directions: u8) {
+        self.cells[row * self.width + col] |= directions;
+    }
+
+    fn draw_box(&mut self, c0: usize, r0: usize, c1: usize, r1: usize) {
+        for c in c0..c1 {
+            for &r in &[r0, r1] {
+                self.connect(c, r, RIGHT);
+                self.connect(c + 1, r, LEFT);
+            }
+        }
+        for r in r0..r1 {
+            for &c in &[c0, c1] {
+                self.connect(c, r, DOWN);
+                self.connect(c, r + 1, UP);
+            }
+        }
+    }
+
+    fn to_chars(&self) -> Vec<Vec<char>> {
+        self.cells
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&cell| box_char(cell)).collect())
+            .collect()
+    }
+}
+
+fn box_char(directions: u8) -> char {
+    match directions {
+        0 => ' ',
+        d if d == LEFT || d == RIGHT || d == LEFT | RIGHT => '─',
+        d if d == UP || d == DOWN || d == UP | DOWN => '│',
+        d if d == DOWN | RIGHT => '┌',
+        d if d == DOWN | LEFT => '┐',
+        d if d == UP | RIGHT => '└',
+        d if d == UP | LEFT => '┘',
+        d if d == UP | DOWN | RIGHT => '├',
+        d if d == UP | DOWN | LEFT => '┤',
+        d if d == LEFT | RIGHT | DOWN => '┬',
+        d if d == LEFT | RIGHT | UP => '┴',
+        _ => '┼',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(width: u32, height: u32) -> Rectangle {
+        Rectangle { width, height }
+    }
+
+    fn options() -> AsciiOptions {
+        AsciiOptions {
+            max_width: 25,
+            max_height: 40,
+            cell_aspect: 2.0,
+        }
+    }
+
+    /* Joins the expected lines, each ending with a newline. */
+    fn lines(lines: &[&str]) -> String {
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn single_box() {
+        assert_eq!(
+            render(&rectangle(24, 10), &options()),
+            lines(&[
+                "┌───────────────────────┐",
+                "│                       │",
+                "│                       │",
+                "│                       │",
+                "│                  24x10│",
+                "└───────────────────────┘",
+            ])
+        );
+    }
+
+    #[test]
+    fn nested_boxes_share_the_top_left_corner() {
+        assert_eq!(
+            render_nested(&rectangle(24, 10), &[rectangle(16, 6)], &options()),
+            lines(&[
+                "┌───────────────┬───────┐",
+                "│               │       │",
+                "│           16x6│       │",
+                "├───────────────┘       │",
+                "│                  24x10│",
+                "└───────────────────────┘",
+            ])
+        );
+        /* One that does not fit spills out below the container. */
+        assert_eq!(
+            render_nested(
+                &rectangle(24, 10),
+                &[rectangle(16, 6), rectangle(10, 14)],
+                &options()
+            ),
+            lines(&[
+                "┌─────────┬─────┬───────┐",
+                "│         │     │       │",
+                "│         │ 16x6│       │",
+                "├─────────┼─────┘       │",
+                "│         │        24x10│",
+                "├─────────┼─────────────┘",
+                "│    10x14│",
+                "└─────────┘",
+            ])
+        );
+    }
+
+    #[test]
+    fn overlapping_edges_cross() {
+        let rects = [Rect::new(0, 0, 12, 8), Rect::new(8, 4, 16, 8)];
+        assert_eq!(
+            render_rects(&rects, &options()),
+            lines(&[
+                "┌───────────┐",
+                "│           │",
+                "│       ┌───┼───────────┐",
+                "│       │   │           │",
+                "└───────┼───┘           │",
+                "        │           16x8│",
+                "        └───────────────┘",
+            ])
+        );
+    }
+
+    #[test]
+    fn nothing_to_draw() {
+        assert_eq!(render_rects(&[], &options()), "");
+    }
+}
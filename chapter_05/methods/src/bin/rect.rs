@@ -0,0 +1,527 @@
+/* rect: answers questions about rectangles from the command line.
+ *
+ *   rect area [RECT...]               area and perimeter of each rectangle
+ *   rect fits OUTER [RECT...]         how each rectangle fits inside OUTER
+ *   rect pack CONTAINER [RECT...]     lays the rectangles out in CONTAINER
+ *   rect bbox [RECT@X,Y...]           bounding box of positioned rectangles
+ *
+ * Rectangles are written 50x30 and positioned ones 50x30@10,20. When no
+ * rectangles are given as arguments they are read from standard input, one
+ * per line, or as CSV or JSON (see the 'parse' module).
+ *
+ * 'fits' and 'pack' can also draw their result to an SVG file with --svg,
+ * or in the terminal with --ascii. The other subcommands refuse them.
+ *
+ * Exit status is 0 on success, 1 for invalid rectangles and 2 for invalid
+ * usage. */
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+use methods::ascii::{self, AsciiOptions};
+use methods::fit::{Fit, FitMode};
+use methods::geometry::{self, Rect};
+use methods::packing::{self, Algorithm, PackOptions};
+use methods::parse::{self, Format, ParseError};
+use methods::svg::{self, SvgOptions};
+use methods::Rectangle;
+
+const USAGE: &str = "usage: rect <area|fits|pack|bbox> [options] [RECT...]
+
+subcommands:
+  area [RECT...]              area and perimeter of each rectangle
+  fits OUTER [RECT...]        how each rectangle fits inside OUTER
+  pack CONTAINER [RECT...]    pack the rectangles into CONTAINER
+  bbox [RECT@X,Y...]          bounding box of positioned rectangles
+
+options:
+  --json                      print JSON instead of text
+  --format dims|csv|json      format of standard input (default: detect)
+  --strict                    fits: require room on every side, like can_hold
+  --algorithm shelf|guillotine|maxrects
+                              pack: packing algorithm (default: maxrects)
+  --rotate                    pack: allow turning rectangles by 90 degrees
+  --svg FILE                  fits, pack: also draw the result to FILE
+  --ascii                     fits, pack: also draw the result in the terminal
+  -h, --help                  show this help";
+
+enum CliError {
+    Usage(String),
+    Input(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Input(_) => 1,
+        }
+    }
+}
+
+impl From<ParseError> for CliError {
+    fn from(error: ParseError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Input(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+struct Options {
+    command: String,
+    specs: Vec<String>,
+    json: bool,
+    format: Option<Format>,
+    mode: FitMode,
+    pack: PackOptions,
+    svg: Option<String>,
+    ascii: bool,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    match parse_args(args).and_then(|options| run(&options)) {
+        Ok(output) => print!("{}", output),
+        Err(error) => {
+            eprintln!("rect: {}", error);
+            process::exit(error.exit_code());
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, CliError> {
+    let mut args = args.into_iter();
+    let command = args
+        .next()
+        .ok_or_else(|| CliError::Usage(String::from("missing subcommand")))?;
+    let mut options = Options {
+        command,
+        specs: Vec::new(),
+        json: false,
+        format: None,
+        mode: FitMode::Inclusive,
+        pack: PackOptions::default(),
+        svg: None,
+        ascii: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--strict" => options.mode = FitMode::Strict,
+            "--rotate" => options.pack.allow_rotation = true,
+            "--ascii" => options.ascii = true,
+            "--svg" => options.svg = Some(value_of(&arg, args.next())?),
+            "--format" => {
+                options.format = Some(match value_of(&arg, args.next())?.as_str() {
+                    "dims" => Format::Dimensions,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    other => return Err(usage(format!("unknown format {:?}", other))),
+                })
+            }
+            "--algorithm" => {
+                options.pack.algorithm = match value_of(&arg, args.next())?.as_str() {
+                    "shelf" => Algorithm::Shelf,
+                    "guillotine" => Algorithm::Guillotine,
+                    "maxrects" => Algorithm::MaxRects,
+                    other => return Err(usage(format!("unknown algorithm {:?}", other))),
+                }
+            }
+            flag if flag.starts_with("--") => {
+                return Err(usage(format!("unknown option {:?}", flag)))
+            }
+            _ => options.specs.push(arg),
+        }
+    }
+    /* Only 'fits' and 'pack' have a result to draw. */
+    if (options.svg.is_some() || options.ascii)
+        && (options.command == "area" || options.command == "bbox")
+    {
+        return Err(usage(format!(
+            "{} has nothing to draw; --svg and --ascii only work with fits and pack",
+            options.command
+        )));
+    }
+    if options.ascii && options.json {
+        return Err(usage(String::from(
+            "--ascii cannot be combined with --json",
+        )));
+    }
+    Ok(options)
+}
+
+fn write_svg(options: &Options, render: impl FnOnce() -> String) -> Result<(), CliError> {
+    match &options.svg {
+        Some(path) => fs::write(path, render())
+            .map_err(|error| CliError::Input(format!("cannot write {}: {}", path, error))),
+        None => Ok(()),
+    }
+}
+
+fn value_of(flag: &str, value: Option<String>) -> Result<String, CliError> {
+    value.ok_or_else(|| usage(format!("{} needs a value", flag)))
+}
+
+fn usage(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+fn run(options: &Options) -> Result<String, CliError> {
+    match options.command.as_str() {
+        "area" => area(options),
+        "fits" => fits(options),
+        "pack" => pack(options),
+        "bbox" => bbox(options),
+        other => Err(usage(format!("unknown subcommand {:?}", other))),
+    }
+}
+
+/* The rectangles from the arguments, or from stdin if there are none. */
+fn rectangles(options: &Options, specs: &[String]) -> Result<Vec<Rectangle>, CliError> {
+    if !specs.is_empty() {
+        return specs
+            .iter()
+            .map(|spec| spec.parse().map_err(CliError::from))
+            .collect();
+    }
+    let input = read_stdin()?;
+    let format = options.format.unwrap_or_else(|| Format::detect(&input));
+    Ok(parse::parse(&input, format)?)
+}
+
+fn read_stdin() -> Result<String, CliError> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|error| CliError::Input(format!("cannot read standard input: {}", error)))?;
+    Ok(input)
+}
+
+/* Splits off the first argument, which some subcommands require. */
+fn first_rectangle<'a>(
+    options: &'a Options,
+    name: &str,
+) -> Result<(Rectangle, &'a [String]), CliError> {
+    match options.specs.split_first() {
+        Some((first, rest)) => Ok((first.parse()?, rest)),
+        None => Err(usage(format!("missing {}", name))),
+    }
+}
+
+/* Each area is computed on a u64 rectangle, which holds the product of two
+ * u32 sides. Adding many of them up could still overflow a u64, so the total
+ * is a u128: it would take more than 2^64 rectangles of the largest size to
+ * overflow that. */
+fn area(options: &Options) -> Result<String, CliError> {
+    let rects: Vec<Rectangle<u64>> = rectangles(options, &options.specs)?
+        .into_iter()
+        .map(Rectangle::from)
+        .collect();
+    let total: u128 = rects.iter().map(|r| u128::from(r.area())).sum();
+
+    if options.json {
+        let items: Vec<String> = rects
+            .iter()
+            .map(|r| {
+                format!(
+                    "{{\"width\":{},\"height\":{},\"area\":{},\"perimeter\":{}}}",
+                    r.width,
+                    r.height,
+                    r.area(),
+                    r.perimeter()
+                )
+            })
+            .collect();
+        return Ok(format!(
+            "{{\"rectangles\":[{}],\"total_area\":{}}}\n",
+            items.join(","),
+            total
+        ));
+    }
+    let mut out = String::new();
+    for r in &rects {
+        out.push_str(&format!(
+            "{}\tarea {}\tperimeter {}\n",
+            r,
+            r.area(),
+            r.perimeter()
+        ));
+    }
+    if rects.len() > 1 {
+        out.push_str(&format!("total area {}\n", total));
+    }
+    Ok(out)
+}
+
+fn fits(options: &Options) -> Result<String, CliError> {
+    let (outer, rest) = first_rectangle(options, "OUTER rectangle")?;
+    let rects = rectangles(options, rest)?;
+    let results: Vec<(Rectangle, Fit)> = rects
+        .iter()
+        .map(|inner| (*inner, outer.fit(inner, options.mode)))
+        .collect();
+    write_svg(options, || {
+        svg::render_containment(&outer, &rects, &SvgOptions::default())
+    })?;
+
+    if options.json {
+        let items: Vec<String> = results
+            .iter()
+            .map(|(inner, fit)| {
+                format!(
+                    "{{\"rectangle\":{},\"fits\":{},\"rotated\":{},\"exact\":{}}}",
+                    parse::rectangle_to_json(inner),
+                    fit.fits(),
+                    fit.is_rotated(),
+                    matches!(fit, Fit::FitsExactly { .. })
+                )
+            })
+            .collect();
+        return Ok(format!(
+            "{{\"outer\":{},\"results\":[{}]}}\n",
+            parse::rectangle_to_json(&outer),
+            items.join(",")
+        ));
+    }
+    let mut out = String::new();
+    for (inner, fit) in &results {
+        let verdict = match fit {
+            Fit::Fits => "fits",
+            Fit::FitsRotated => "fits rotated",
+            Fit::FitsExactly { rotated: false } => "fits exactly",
+            Fit::FitsExactly { rotated: true } => "fits exactly rotated",
+            Fit::DoesNotFit => "does not fit",
+        };
+        out.push_str(&format!("{} in {}: {}\n", inner, outer, verdict));
+    }
+    if options.ascii {
+        out.push_str(&ascii::render_nested(
+            &outer,
+            &rects,
+            &AsciiOptions::for_terminal(),
+        ));
+    }
+    Ok(out)
+}
+
+fn pack(options: &Options) -> Result<String, CliError> {
+    let (container, rest) = first_rectangle(options, "CONTAINER rectangle")?;
+    let items = rectangles(options, rest)?;
+    let layout = packing::pack(container, &items, options.pack);
+    write_svg(options, || {
+        svg::render_packing(&layout, &SvgOptions::default())
+    })?;
+
+    if !options.json {
+        let mut out = layout.to_string();
+        if options.ascii {
+            out.push_str(&ascii::render_packing(
+                &layout,
+                &AsciiOptions::for_terminal(),
+            ));
+        }
+        return Ok(out);
+    }
+    let placements: Vec<String> = layout
+        .placements
+        .iter()
+        .map(|p| {
+            format!(
+                "{{\"index\":{},\"rect\":{},\"rotated\":{}}}",
+                p.index,
+                parse::rect_to_json(&p.rect),
+                p.rotated
+            )
+        })
+        .collect();
+    let unplaced: Vec<String> = layout.unplaced.iter().map(|i| i.to_string()).collect();
+    Ok(format!(
+        "{{\"container\":{},\"placements\":[{}],\"unplaced\":[{}],\"utilization\":{:.4}}}\n",
+        parse::rectangle_to_json(&container),
+        placements.join(","),
+        unplaced.join(","),
+        layout.utilization()
+    ))
+}
+
+fn bbox(options: &Options) -> Result<String, CliError> {
+    let rects: Vec<Rect> = if options.specs.is_empty() {
+        let input = read_stdin()?;
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                line.parse().map_err(|error| ParseError::AtLine {
+                    line: i + 1,
+                    error: Box::new(error),
+                })
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        options
+            .specs
+            .iter()
+            .map(|spec| spec.parse())
+            .collect::<Result<_, _>>()?
+    };
+
+    if rects.is_empty() {
+        return Err(CliError::Input(String::from("no rectangles given")));
+    }
+    match geometry::bounding_box(&rects) {
+        Some(bbox) if options.json => Ok(format!("{}\n", parse::rect_to_json(&bbox))),
+        Some(bbox) => Ok(format!("{}\n", bbox)),
+        None => Err(CliError::Input(String::from(
+            "bounding box is wider or taller than 4294967295",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Runs with the rectangles given as arguments; standard input is not
+     * read then. */
+    fn rect(args: &[&str]) -> Result<String, CliError> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect())
+            .and_then(|options| run(&options))
+    }
+
+    fn output(result: Result<String, CliError>) -> String {
+        match result {
+            Ok(output) => output,
+            Err(error) => panic!("rect failed: {}", error),
+        }
+    }
+
+    fn exit_code(result: Result<String, CliError>) -> i32 {
+        result.err().map_or(0, |error| error.exit_code())
+    }
+
+    #[test]
+    fn area() {
+        assert_eq!(
+            output(rect(&["area", "50x30", "20x10"])),
+            "50x30\tarea 1500\tperimeter 160\n\
+             20x10\tarea 200\tperimeter 60\n\
+             total area 1700\n"
+        );
+        assert_eq!(
+            output(rect(&["area", "--json", "50x30"])),
+            "{\"rectangles\":[{\"width\":50,\"height\":30,\"area\":1500,\"perimeter\":160}],\"total_area\":1500}\n"
+        );
+        /* Products and sums that overflow a u32. */
+        assert_eq!(
+            output(rect(&[
+                "area",
+                "4294967295x4294967295",
+                "4294967295x4294967295"
+            ])),
+            "4294967295x4294967295\tarea 18446744065119617025\tperimeter 17179869180\n\
+             4294967295x4294967295\tarea 18446744065119617025\tperimeter 17179869180\n\
+             total area 36893488130239234050\n"
+        );
+    }
+
+    #[test]
+    fn fits() {
+        assert_eq!(
+            output(rect(&["fits", "50x30", "40x20", "30x50", "50x30", "60x10"])),
+            "40x20 in 50x30: fits\n\
+             30x50 in 50x30: fits exactly rotated\n\
+             50x30 in 50x30: fits exactly\n\
+             60x10 in 50x30: does not fit\n"
+        );
+        assert_eq!(
+            output(rect(&["fits", "--strict", "--json", "50x30", "50x30", "40x20"])),
+            "{\"outer\":{\"width\":50,\"height\":30},\"results\":[\
+             {\"rectangle\":{\"width\":50,\"height\":30},\"fits\":false,\"rotated\":false,\"exact\":false},\
+             {\"rectangle\":{\"width\":40,\"height\":20},\"fits\":true,\"rotated\":false,\"exact\":false}]}\n"
+        );
+    }
+
+    #[test]
+    fn pack() {
+        assert_eq!(
+            output(rect(&["pack", "40x20", "10x10", "20x5", "100x100"])),
+            "container 40x20, 2 placed, 1 unplaced, 25.0% used\n  \
+             #0 10x10 at (0, 5)\n  \
+             #1 20x5 at (0, 0)\n  \
+             unplaced: [2]\n"
+        );
+        assert_eq!(
+            output(rect(&["pack", "--json", "20x10", "10x10", "10x10"])),
+            "{\"container\":{\"width\":20,\"height\":10},\"placements\":[\
+             {\"index\":0,\"rect\":{\"x\":0,\"y\":0,\"width\":10,\"height\":10},\"rotated\":false},\
+             {\"index\":1,\"rect\":{\"x\":10,\"y\":0,\"width\":10,\"height\":10},\"rotated\":false}],\
+             \"unplaced\":[],\"utilization\":1.0000}\n"
+        );
+    }
+
+    #[test]
+    fn bbox() {
+        assert_eq!(
+            output(rect(&["bbox", "10x10@0,0", "5x5@20,20"])),
+            "25x25@0,0\n"
+        );
+        assert_eq!(
+            output(rect(&["bbox", "--json", "1x1@-5,3"])),
+            "{\"x\":-5,\"y\":3,\"width\":1,\"height\":1}\n"
+        );
+        assert_eq!(exit_code(rect(&["bbox", "1x1@0,0", "1x1@5000000000,0"])), 1);
+    }
+
+    #[test]
+    fn drawing() {
+        let path = env::temp_dir().join(format!("methods-rect-{}.svg", process::id()));
+        let path = path.to_str().unwrap();
+        let out = output(rect(&["fits", "--svg", path, "--ascii", "4x2", "2x1"]));
+        assert!(out.starts_with("2x1 in 4x2: fits\n┌"));
+        assert!(fs::read_to_string(path).unwrap().starts_with("<svg "));
+        output(rect(&["pack", "--svg", path, "4x2", "2x1"]));
+        assert_eq!(
+            fs::read_to_string(path).unwrap().matches("<rect ").count(),
+            2
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn errors_set_the_exit_code() {
+        let cases: &[(&[&str], i32)] = &[
+            (&[], 2),
+            (&["frob"], 2),
+            (&["area", "--frob"], 2),
+            (&["area", "--ascii", "5x5"], 2),
+            (&["bbox", "--svg", "out.svg", "1x1@0,0"], 2),
+            (&["fits", "--ascii", "--json", "5x5"], 2),
+            (&["fits"], 2),
+            (&["pack"], 2),
+            (&["pack", "--algorithm", "best", "5x5"], 2),
+            (&["pack", "--algorithm"], 2),
+            (&["area", "5xx"], 1),
+            (&["fits", "5x5", "1x-1"], 1),
+            (&["bbox", "1x1@a,0"], 1),
+        ];
+        for (args, code) in cases {
+            assert_eq!(exit_code(rect(args)), *code, "rect {:?}", args);
+        }
+    }
+}
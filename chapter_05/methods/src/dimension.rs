@@ -0,0 +1,162 @@
+/* Numeric types that can be used for the width and height of a Rectangle.
+ *
+ * Integer dimensions can overflow when multiplied, so besides the plain
+ * operators every Dimension offers checked and saturating arithmetic. For
+ * f64 "overflow" means the result is no longer finite. */
+
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+pub trait Dimension:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + fmt::Debug
+    + fmt::Display
+{
+    const ZERO: Self;
+    const MAX: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn saturating_add(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_integer_dimension {
+    ($($t:ty),*) => {
+        $(
+            impl Dimension for $t {
+                const ZERO: $t = 0;
+                const MAX: $t = <$t>::MAX;
+
+                fn checked_add(self, other: $t) -> Option<$t> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: $t) -> Option<$t> {
+                    <$t>::checked_mul(self, other)
+                }
+
+                fn saturating_add(self, other: $t) -> $t {
+                    <$t>::saturating_add(self, other)
+                }
+
+                fn saturating_mul(self, other: $t) -> $t {
+                    <$t>::saturating_mul(self, other)
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_dimension!(u32, u64);
+
+impl Dimension for f64 {
+    const ZERO: f64 = 0.0;
+    const MAX: f64 = f64::MAX;
+
+    fn checked_add(self, other: f64) -> Option<f64> {
+        finite(self + other)
+    }
+
+    fn checked_mul(self, other: f64) -> Option<f64> {
+        finite(self * other)
+    }
+
+    fn saturating_add(self, other: f64) -> f64 {
+        clamp(self + other)
+    }
+
+    fn saturating_mul(self, other: f64) -> f64 {
+        clamp(self * other)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+fn finite(value: f64) -> Option<f64> {
+    if value.is_finite() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn clamp(value: f64) -> f64 {
+    if value.is_nan() {
+        value
+    } else {
+        value.clamp(f64::MIN, f64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rectangle;
+
+    fn rectangle<T>(width: T, height: T) -> Rectangle<T> {
+        Rectangle { width, height }
+    }
+
+    #[test]
+    fn u32_area_overflow() {
+        let fits = rectangle(65_535u32, 65_537);
+        assert_eq!(fits.checked_area(), Some(u32::MAX));
+        assert_eq!(fits.saturating_area(), u32::MAX);
+
+        let over = rectangle(65_536u32, 65_536);
+        assert_eq!(over.checked_area(), None);
+        assert_eq!(over.saturating_area(), u32::MAX);
+        assert_eq!(rectangle(u32::MAX, u32::MAX).checked_area(), None);
+        assert_eq!(rectangle(u32::MAX, 0).checked_area(), Some(0));
+
+        /* Widening to u64 always holds the product of two u32 sides. */
+        let wide = Rectangle::<u64>::from(rectangle(u32::MAX, u32::MAX));
+        assert_eq!(
+            wide.checked_area(),
+            Some(u64::from(u32::MAX) * u64::from(u32::MAX))
+        );
+    }
+
+    #[test]
+    fn u64_area_overflow() {
+        let over = rectangle(u64::MAX, 2);
+        assert_eq!(over.checked_area(), None);
+        assert_eq!(over.saturating_area(), u64::MAX);
+        assert_eq!(rectangle(1u64 << 32, 1 << 31).checked_area(), Some(1 << 63));
+    }
+
+    #[test]
+    fn perimeter_overflow() {
+        assert_eq!(
+            rectangle(u32::MAX / 4, u32::MAX / 4).checked_perimeter(),
+            Some(u32::MAX - 3)
+        );
+        assert_eq!(rectangle(u32::MAX / 2, 1).checked_perimeter(), None);
+        assert_eq!(rectangle(u32::MAX, 1).checked_perimeter(), None);
+    }
+
+    #[test]
+    fn f64_overflow_is_leaving_the_finite_range() {
+        let over = rectangle(f64::MAX, 2.0);
+        assert_eq!(over.checked_area(), None);
+        assert_eq!(over.saturating_area(), f64::MAX);
+        assert_eq!(rectangle(-f64::MAX, 2.0).saturating_area(), f64::MIN);
+        assert_eq!(rectangle(1e154, 1e154).checked_area(), Some(1e308));
+        assert_eq!(f64::MAX.checked_add(f64::MAX), None);
+        assert_eq!(f64::MAX.saturating_add(f64::MAX), f64::MAX);
+        assert!(f64::NAN.saturating_mul(1.0).is_nan());
+        assert_eq!(f64::NAN.checked_mul(1.0), None);
+    }
+}
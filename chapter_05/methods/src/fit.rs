@@ -0,0 +1,159 @@
+/* Fitting one rectangle inside another.
+ *
+ * 'can_hold' from the book uses a strict comparison on both sides and never
+ * turns the inner rectangle, so a 50x30 rectangle cannot hold another 50x30
+ * or a 30x50 one. 'can_hold' keeps that behavior; 'fit' answers the more
+ * detailed question of how (if at all) the other rectangle fits. */
+
+use crate::dimension::Dimension;
+use crate::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitMode {
+    /* The inner rectangle must be smaller on both sides, like 'can_hold'. */
+    Strict,
+    /* The inner rectangle may touch the edges of the outer one. */
+    Inclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /* Fits as it is. */
+    Fits,
+    /* Only fits when turned by 90 degrees. */
+    FitsRotated,
+    /* Has exactly the same size, possibly after turning it. Only reported
+     * in inclusive mode. */
+    FitsExactly { rotated: bool },
+    DoesNotFit,
+}
+
+impl Fit {
+    pub fn fits(&self) -> bool {
+        *self != Fit::DoesNotFit
+    }
+
+    pub fn is_rotated(&self) -> bool {
+        matches!(self, Fit::FitsRotated | Fit::FitsExactly { rotated: true })
+    }
+}
+
+impl<T: Dimension> Rectangle<T> {
+    /* The same rectangle turned by 90 degrees. */
+    pub fn rotated(&self) -> Rectangle<T> {
+        Rectangle {
+            width: self.height,
+            height: self.width,
+        }
+    }
+
+    /* Like 'can_hold', but with a choice of mode. Never rotates 'other'. */
+    pub fn holds(&self, other: &Rectangle<T>, mode: FitMode) -> bool {
+        match mode {
+            FitMode::Strict => self.can_hold(other),
+            FitMode::Inclusive => self.width >= other.width && self.height >= other.height,
+        }
+    }
+
+    /* Describes how 'other' fits inside self. An exact fit is preferred,
+     * then a fit without rotation, then a rotated one. */
+    pub fn fit(&self, other: &Rectangle<T>, mode: FitMode) -> Fit {
+        let turned = other.rotated();
+        if mode == FitMode::Inclusive {
+            if self.width == other.width && self.height == other.height {
+                return Fit::FitsExactly { rotated: false };
+            }
+            if self.width == turned.width && self.height == turned.height {
+                return Fit::FitsExactly { rotated: true };
+            }
+        }
+        if self.holds(other, mode) {
+            Fit::Fits
+        } else if self.holds(&turned, mode) {
+            Fit::FitsRotated
+        } else {
+            Fit::DoesNotFit
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle<T>(width: T, height: T) -> Rectangle<T> {
+        Rectangle { width, height }
+    }
+
+    #[test]
+    fn fits_in_each_mode() {
+        use Fit::*;
+        use FitMode::*;
+
+        let outer = rectangle(50u32, 30);
+        for &((width, height), strict, inclusive) in &[
+            ((40, 20), Fits, Fits),
+            ((20, 40), FitsRotated, FitsRotated),
+            ((49, 29), Fits, Fits),
+            ((29, 49), FitsRotated, FitsRotated),
+            /* Touching an edge only counts in inclusive mode. */
+            ((50, 20), DoesNotFit, Fits),
+            ((40, 30), DoesNotFit, Fits),
+            ((20, 50), DoesNotFit, FitsRotated),
+            ((30, 45), DoesNotFit, FitsRotated),
+            /* The same size, as it is or turned. */
+            ((50, 30), DoesNotFit, FitsExactly { rotated: false }),
+            ((30, 50), DoesNotFit, FitsExactly { rotated: true }),
+            ((51, 10), DoesNotFit, DoesNotFit),
+            ((10, 51), DoesNotFit, DoesNotFit),
+            ((31, 31), DoesNotFit, DoesNotFit),
+            ((0, 0), Fits, Fits),
+        ] {
+            let inner = rectangle(width, height);
+            assert_eq!(outer.fit(&inner, Strict), strict, "{} in {}", inner, outer);
+            assert_eq!(
+                outer.fit(&inner, Inclusive),
+                inclusive,
+                "{} in {}",
+                inner,
+                outer
+            );
+            /* Strict mode agrees with can_hold unless a turn is needed. */
+            assert_eq!(outer.can_hold(&inner), strict == Fits);
+            assert_eq!(
+                outer.holds(&inner, Inclusive),
+                inclusive.fits() && !inclusive.is_rotated()
+            );
+        }
+    }
+
+    #[test]
+    fn equal_squares() {
+        let square = Rectangle::square(7u32);
+        assert_eq!(square.fit(&square, FitMode::Strict), Fit::DoesNotFit);
+        /* Turning a square changes nothing, so it is not reported. */
+        assert_eq!(
+            square.fit(&square, FitMode::Inclusive),
+            Fit::FitsExactly { rotated: false }
+        );
+        assert_eq!(
+            Rectangle::square(0.0).fit(&Rectangle::square(0.0), FitMode::Inclusive),
+            Fit::FitsExactly { rotated: false }
+        );
+    }
+
+    #[test]
+    fn fit_properties() {
+        assert!(!Fit::DoesNotFit.fits());
+        assert!(!Fit::DoesNotFit.is_rotated());
+        assert!(Fit::Fits.fits() && !Fit::Fits.is_rotated());
+        assert!(Fit::FitsRotated.fits() && Fit::FitsRotated.is_rotated());
+        assert!(Fit::FitsExactly { rotated: true }.is_rotated());
+        assert!(!Fit::FitsExactly { rotated: false }.is_rotated());
+        assert_eq!(rectangle(2.5, 4.0).rotated(), rectangle(4.0, 2.5));
+        assert_eq!(
+            rectangle(2.5, 4.0).fit(&rectangle(3.9, 2.4), FitMode::Strict),
+            Fit::FitsRotated
+        );
+    }
+}
@@ -0,0 +1,396 @@
+/* Positioned rectangles.
+ *
+ * A 'Rectangle' only knows its size. To answer questions such as "do these
+ * two rectangles overlap?" we also need to know where the rectangle is, so
+ * 'Rect' pairs an origin (the top-left corner) with a 'Rectangle' size.
+ *
+ * The y axis grows downwards, like on a screen. A rect covers the half-open
+ * ranges [left, right) and [top, bottom), so two rects that only share an
+ * edge do not overlap.
+ *
+ * A rect's origin must lie within MIN_COORD..=MAX_COORD on both axes. That
+ * leaves room to add a u32 width or height to a coordinate, and to take
+ * the distance between any two edges, without overflowing an i64. 'Rect'
+ * panics when asked to build or move a rect outside those bounds; the
+ * 'checked_' methods return None instead. */
+
+use std::convert::TryFrom;
+
+use crate::Rectangle;
+
+pub const MAX_COORD: i64 = 1 << 61;
+pub const MIN_COORD: i64 = -MAX_COORD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Point {
+        Point { x, y }
+    }
+
+    pub fn origin() -> Point {
+        Point { x: 0, y: 0 }
+    }
+
+    /* Whether the point may be the origin of a rect. */
+    pub fn in_bounds(&self) -> bool {
+        (MIN_COORD..=MAX_COORD).contains(&self.x) && (MIN_COORD..=MAX_COORD).contains(&self.y)
+    }
+
+    /* The point, if both coordinates were worked out without overflowing
+     * and are in bounds. */
+    fn checked(x: Option<i64>, y: Option<i64>) -> Option<Point> {
+        Some(Point::new(x?, y?)).filter(Point::in_bounds)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub origin: Point,
+    pub size: Rectangle,
+}
+
+impl Rect {
+    pub fn new(x: i64, y: i64, width: u32, height: u32) -> Rect {
+        Rect::at(Point { x, y }, Rectangle { width, height })
+    }
+
+    /* Places a Rectangle at the given origin. */
+    pub fn at(origin: Point, size: Rectangle) -> Rect {
+        Rect::checked_at(origin, size).expect("rect origin out of bounds")
+    }
+
+    pub fn checked_at(origin: Point, size: Rectangle) -> Option<Rect> {
+        Some(Rect { origin, size }).filter(|rect| rect.origin.in_bounds())
+    }
+
+    /* Builds the smallest rect spanning two corners, in any order. */
+    pub fn from_corners(a: Point, b: Point) -> Rect {
+        Rect::checked_from_corners(a, b).expect("rect corners too far apart")
+    }
+
+    /* Like 'from_corners', but None when the corners are more than u32::MAX
+     * apart or out of bounds. */
+    pub fn checked_from_corners(a: Point, b: Point) -> Option<Rect> {
+        Rect::from_edges(a.x.min(b.x), a.y.min(b.y), a.x.max(b.x), a.y.max(b.y))
+    }
+
+    fn from_edges(left: i64, top: i64, right: i64, bottom: i64) -> Option<Rect> {
+        Rect::checked_at(
+            Point::new(left, top),
+            Rectangle {
+                width: span(left, right)?,
+                height: span(top, bottom)?,
+            },
+        )
+    }
+
+    pub fn width(&self) -> u32 {
+        self.size.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.size.height
+    }
+
+    pub fn left(&self) -> i64 {
+        self.origin.x
+    }
+
+    pub fn top(&self) -> i64 {
+        self.origin.y
+    }
+
+    pub fn right(&self) -> i64 {
+        self.origin.x + i64::from(self.size.width)
+    }
+
+    pub fn bottom(&self) -> i64 {
+        self.origin.y + i64::from(self.size.height)
+    }
+
+    pub fn center(&self) -> Point {
+        Point {
+            x: self.origin.x + i64::from(self.size.width / 2),
+            y: self.origin.y + i64::from(self.size.height / 2),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    /* Area and perimeter are widened to u64 since positioned rects are often
+     * built from intersections and unions of large regions. */
+    pub fn area(&self) -> u64 {
+        u64::from(self.size.width) * u64::from(self.size.height)
+    }
+
+    pub fn perimeter(&self) -> u64 {
+        2 * (u64::from(self.size.width) + u64::from(self.size.height))
+    }
+
+    pub fn contains_point(&self, point: Point) -> bool {
+        point.x >= self.left()
+            && point.x < self.right()
+            && point.y >= self.top()
+            && point.y < self.bottom()
+    }
+
+    /* True if 'other' lies entirely inside self. Unlike 'Rectangle::can_hold'
+     * this is inclusive: a rect contains itself. */
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.left() >= self.left()
+            && other.right() <= self.right()
+            && other.top() >= self.top()
+            && other.bottom() <= self.bottom()
+    }
+
+    /* True if the two rects share some area. Touching edges do not count,
+     * and neither does an empty rect lying across the other. */
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.left() < other.right()
+            && other.left() < self.right()
+            && self.top() < other.bottom()
+            && other.top() < self.bottom()
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        let left = self.left().max(other.left());
+        let top = self.top().max(other.top());
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        Rect::from_edges(left, top, right, bottom)
+    }
+
+    /* The bounding box of both rects. */
+    pub fn union(&self, other: &Rect) -> Rect {
+        self.checked_union(other)
+            .expect("union of rects too large for one rect")
+    }
+
+    /* Like 'union', but None when the bounding box would be wider or taller
+     * than u32::MAX. */
+    pub fn checked_union(&self, other: &Rect) -> Option<Rect> {
+        Rect::from_edges(
+            self.left().min(other.left()),
+            self.top().min(other.top()),
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        )
+    }
+
+    pub fn translate(&self, dx: i64, dy: i64) -> Rect {
+        self.checked_translate(dx, dy)
+            .expect("rect translated out of bounds")
+    }
+
+    pub fn checked_translate(&self, dx: i64, dy: i64) -> Option<Rect> {
+        let origin = Point::checked(self.origin.x.checked_add(dx), self.origin.y.checked_add(dy))?;
+        Some(Rect {
+            origin,
+            size: self.size,
+        })
+    }
+
+    /* Scales the size of the rect, keeping its origin in place. */
+    pub fn scale(&self, sx: u32, sy: u32) -> Rect {
+        self.checked_scale(sx, sy).expect("rect scaled too large")
+    }
+
+    /* Like 'scale', but None when a side would not fit in a u32. */
+    pub fn checked_scale(&self, sx: u32, sy: u32) -> Option<Rect> {
+        Some(Rect {
+            origin: self.origin,
+            size: Rectangle {
+                width: self.size.width.checked_mul(sx)?,
+                height: self.size.height.checked_mul(sy)?,
+            },
+        })
+    }
+
+    /* Scales both the origin and the size, i.e. scales the rect about the
+     * coordinate origin (0, 0). */
+    pub fn scale_about_origin(&self, sx: u32, sy: u32) -> Rect {
+        self.checked_scale_about_origin(sx, sy)
+            .expect("rect scaled out of bounds")
+    }
+
+    pub fn checked_scale_about_origin(&self, sx: u32, sy: u32) -> Option<Rect> {
+        let origin = Point::checked(
+            self.origin.x.checked_mul(i64::from(sx)),
+            self.origin.y.checked_mul(i64::from(sy)),
+        )?;
+        Some(Rect {
+            origin,
+            size: self.checked_scale(sx, sy)?.size,
+        })
+    }
+}
+
+/* The bounding box of a collection of rects. None if the collection is
+ * empty, or if the rects are spread too far apart for one rect to cover them
+ * (see 'checked_union'). */
+pub fn bounding_box<'a, I>(rects: I) -> Option<Rect>
+where
+    I: IntoIterator<Item = &'a Rect>,
+{
+    let mut rects = rects.into_iter();
+    let first = *rects.next()?;
+    rects.try_fold(first, |acc, rect| acc.checked_union(rect))
+}
+
+/* The edges (left, top, right, bottom) of the area covered by a collection
+ * of rects, or None if it is empty. Unlike 'bounding_box' this cannot fail:
+ * the edges of any rect fit in an i64, however far apart the rects are. */
+pub fn extent<'a, I>(rects: I) -> Option<(i64, i64, i64, i64)>
+where
+    I: IntoIterator<Item = &'a Rect>,
+{
+    let mut rects = rects.into_iter();
+    let first = rects.next()?;
+    let edges = (first.left(), first.top(), first.right(), first.bottom());
+    Some(rects.fold(edges, |(left, top, right, bottom), rect| {
+        (
+            left.min(rect.left()),
+            top.min(rect.top()),
+            right.max(rect.right()),
+            bottom.max(rect.bottom()),
+        )
+    }))
+}
+
+/* Distance between two coordinates as a rect dimension, or None if it is
+ * too large for a u32. */
+fn span(from: i64, to: i64) -> Option<u32> {
+    u32::try_from((to - from).max(0)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_rects_intersect() {
+        let a = Rect::new(0, 0, 50, 40);
+        let b = Rect::new(30, 10, 40, 40);
+        assert!(a.overlaps(&b) && b.overlaps(&a));
+        assert_eq!(a.intersection(&b), Some(Rect::new(30, 10, 20, 30)));
+        assert_eq!(b.intersection(&a), a.intersection(&b));
+        assert_eq!(a.intersection(&a), Some(a));
+    }
+
+    #[test]
+    fn touching_edges_do_not_overlap() {
+        let a = Rect::new(0, 0, 10, 10);
+        for b in &[
+            Rect::new(10, 0, 10, 10),
+            Rect::new(0, 10, 10, 10),
+            Rect::new(-10, -10, 10, 10),
+        ] {
+            assert!(!a.overlaps(b), "{:?}", b);
+            assert_eq!(a.intersection(b), None);
+        }
+    }
+
+    #[test]
+    fn disjoint_rects_do_not_intersect() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(100, -50, 5, 5);
+        assert!(!a.overlaps(&b));
+        assert_eq!(a.intersection(&b), None);
+        assert_eq!(a.union(&b), Rect::new(0, -50, 105, 60));
+    }
+
+    #[test]
+    fn zero_sized_rects_cover_nothing() {
+        let a = Rect::new(0, 0, 10, 10);
+        let line = Rect::new(5, 0, 0, 10);
+        assert!(line.is_empty());
+        assert!(!a.overlaps(&line) && !line.overlaps(&a));
+        assert!(!line.contains_point(Point::new(5, 5)));
+        assert!(a.contains_rect(&line));
+        assert_eq!(line.area(), 0);
+    }
+
+    #[test]
+    fn union_is_the_bounding_box() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(5, 20, 10, 5);
+        let union = a.union(&b);
+        assert_eq!(union, Rect::new(0, 0, 15, 25));
+        assert_eq!(union, b.union(&a));
+        assert!(union.contains_rect(&a) && union.contains_rect(&b));
+        assert_eq!(bounding_box(&[a, b]), Some(union));
+        assert_eq!(bounding_box(&[]), None);
+    }
+
+    #[test]
+    fn contains_is_inclusive_of_edges() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert!(a.contains_rect(&a));
+        assert!(a.contains_rect(&Rect::new(5, 5, 5, 5)));
+        assert!(!a.contains_rect(&Rect::new(5, 5, 6, 5)));
+        assert!(a.contains_point(Point::new(0, 0)));
+        assert!(a.contains_point(Point::new(9, 9)));
+        assert!(!a.contains_point(Point::new(10, 9)));
+        assert!(!a.contains_point(Point::new(-1, 0)));
+    }
+
+    #[test]
+    fn edges_at_the_bounds_do_not_overflow() {
+        let max = Rect::new(MAX_COORD, MAX_COORD, u32::MAX, u32::MAX);
+        let min = Rect::new(MIN_COORD, MIN_COORD, u32::MAX, u32::MAX);
+        assert_eq!(max.right(), MAX_COORD + i64::from(u32::MAX));
+        assert!(!max.overlaps(&min));
+        assert_eq!(max.checked_union(&min), None);
+        assert_eq!(bounding_box(&[max, min]), None);
+        assert_eq!(
+            extent(&[max, min]),
+            Some((MIN_COORD, MIN_COORD, max.right(), max.bottom()))
+        );
+        assert_eq!(Rect::checked_from_corners(min.origin, max.origin), None);
+        assert_eq!(max.checked_scale(2, 1), None);
+        assert_eq!(
+            Rect::new(0, 0, 1, 1).checked_union(&Rect::new(i64::from(u32::MAX) - 1, 0, 1, 1)),
+            Some(Rect::new(0, 0, u32::MAX, 1))
+        );
+        assert_eq!(
+            Rect::new(0, 0, 1, 1).checked_union(&Rect::new(i64::from(u32::MAX), 0, 1, 1)),
+            None
+        );
+        assert_eq!(max.checked_translate(1, 0), None);
+        assert_eq!(min.checked_translate(0, -1), None);
+        assert_eq!(max.checked_translate(i64::MAX, 0), None);
+        assert_eq!(max.checked_scale_about_origin(2, 1), None);
+        assert_eq!(
+            max.checked_translate(-MAX_COORD, 0),
+            Some(Rect::new(0, MAX_COORD, u32::MAX, u32::MAX))
+        );
+        assert_eq!(
+            Rect::checked_at(Point::new(MAX_COORD + 1, 0), Rectangle::square(1)),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "rect translated out of bounds")]
+    fn translating_out_of_bounds_panics() {
+        Rect::new(0, 0, 1, 1).translate(MAX_COORD + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "union of rects too large for one rect")]
+    fn union_too_large_panics() {
+        Rect::new(0, 0, 1, 1).union(&Rect::new(5_000_000_000, 0, 1, 1));
+    }
+}
@@ -0,0 +1,98 @@
+/* The Rectangle type from the 'Method syntax' section lives in the library
+ * so that it can be shared by the 'methods' binary and the geometry code
+ * built on top of it.
+ *
+ * Rectangle is generic over the type of its width and height. It defaults
+ * to u32, as in the book, but u64 and f64 rectangles work too. See the
+ * 'dimension' module for the numeric types that can be used, and the
+ * 'units' module for rectangles measured in pixels, millimeters or inches. */
+
+use std::convert::TryFrom;
+use std::num::TryFromIntError;
+
+pub mod ascii;
+pub mod dimension;
+pub mod fit;
+pub mod geometry;
+pub mod packing;
+pub mod parse;
+pub mod shape;
+pub mod spatial;
+pub mod svg;
+pub mod units;
+
+use dimension::Dimension;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rectangle<T = u32> {
+    pub width: T,
+    pub height: T,
+}
+
+impl<T: Dimension> Rectangle<T> {
+    /* Like the book's version, this overflows for very large integer
+     * rectangles. Use 'checked_area' or 'saturating_area' when the
+     * dimensions are not known to be small. */
+    pub fn area(&self) -> T {
+        self.width * self.height
+    }
+
+    pub fn checked_area(&self) -> Option<T> {
+        self.width.checked_mul(self.height)
+    }
+
+    pub fn saturating_area(&self) -> T {
+        self.width.saturating_mul(self.height)
+    }
+
+    pub fn perimeter(&self) -> T {
+        let half = self.width + self.height;
+        half + half
+    }
+
+    pub fn checked_perimeter(&self) -> Option<T> {
+        let half = self.width.checked_add(self.height)?;
+        half.checked_add(half)
+    }
+
+    pub fn can_hold(&self, other: &Rectangle<T>) -> bool {
+        self.width > other.width && self.height > other.height
+    }
+
+    pub fn square(size: T) -> Rectangle<T> {
+        Rectangle {
+            width: size,
+            height: size,
+        }
+    }
+}
+
+/* Widening conversions never lose information. */
+impl From<Rectangle<u32>> for Rectangle<u64> {
+    fn from(rect: Rectangle<u32>) -> Rectangle<u64> {
+        Rectangle {
+            width: u64::from(rect.width),
+            height: u64::from(rect.height),
+        }
+    }
+}
+
+impl From<Rectangle<u32>> for Rectangle<f64> {
+    fn from(rect: Rectangle<u32>) -> Rectangle<f64> {
+        Rectangle {
+            width: f64::from(rect.width),
+            height: f64::from(rect.height),
+        }
+    }
+}
+
+impl TryFrom<Rectangle<u64>> for Rectangle<u32> {
+    type Error = TryFromIntError;
+
+    fn try_from(rect: Rectangle<u64>) -> Result<Rectangle<u32>, TryFromIntError> {
+        Ok(Rectangle {
+            width: u32::try_from(rect.width)?,
+            height: u32::try_from(rect.height)?,
+        })
+    }
+}
@@ -1,25 +1,16 @@
-#[derive(Debug)]
-struct Rectangle {
-    width: u32,
-    height: u32,
-}
-
-impl Rectangle {
-    fn area(&self) -> u32 {
-        self.width * self.height
-    }
-
-    fn can_hold(&self, other: &Rectangle) -> bool {
-        self.width > other.width && self.height > other.height
-    }
+/* Rectangle and its methods are defined in src/lib.rs so that other code,
+ * such as the geometry module, can build on them. */
 
-    fn square(size: u32) -> Rectangle {
-        Rectangle {
-            width: size,
-            height: size,
-        }
-    }
-}
+use methods::ascii::{self, AsciiOptions};
+use methods::fit::FitMode;
+use methods::geometry::{self, Point, Rect};
+use methods::packing::{self, Algorithm, PackOptions};
+use methods::parse::{self, Format};
+use methods::shape::{self, Circle, Coord, Ellipse, Polygon, Shape, Triangle};
+use methods::spatial::QuadTree;
+use methods::svg::{self, SvgOptions};
+use methods::units::{Inches, Millimeters, Pixels, Size};
+use methods::Rectangle;
 
 fn main() {
     /* Method syntax */
@@ -31,7 +22,7 @@ fn main() {
     * the context of struct (or an enum or a trait object), and their first
     * parameter is always 'self', which corresponds to the instance of the
     * struct the method is being called on. See the above code for Rectangle
-    * struct and method 'area'.
+    * struct and method 'area' (now in src/lib.rs).
        #[derive(Debug)]
        struct Rectangle {
            width: u32,
@@ -112,4 +103,232 @@ fn main() {
     );
 
     /* One can have multiple 'impl' blocks for a sruct */
+
+    /* Positioned rectangles */
+
+    /* A Rectangle only has a size. The geometry module pairs it with an
+     * origin so that we can ask where rectangles are relative to each other. */
+
+    let a = Rect::at(Point::new(0, 0), rect1);
+    let b = Rect::new(30, 10, 40, 40);
+
+    println!("perimeter of {:?} is {}", rect1, rect1.perimeter());
+    println!("Do a and b overlap? {}", a.overlaps(&b));
+    println!("Intersection of a and b is {:?}", a.intersection(&b));
+    println!("Union of a and b is {:?}", a.union(&b));
+    println!(
+        "Does a contain (10, 10)? {}",
+        a.contains_point(Point::new(10, 10))
+    );
+    println!("a moved by (5, 5) is {:?}", a.translate(5, 5));
+    println!("a scaled by 2 is {:?}", a.scale(2, 2));
+    println!(
+        "Bounding box of a, b and a moved by (100, 0) is {:?}",
+        geometry::bounding_box(&[a, b, a.translate(100, 0)])
+    );
+
+    /* Shapes */
+
+    /* The Shape trait lets us treat rectangles, circles, triangles, polygons
+     * and ellipses alike. A Vec of trait objects can hold all of them. */
+
+    let shapes: Vec<Box<dyn Shape>> = vec![
+        Box::new(rect1),
+        Box::new(Circle::new(Coord::new(0.0, 0.0), 10.0)),
+        Box::new(Triangle::new(
+            Coord::new(0.0, 0.0),
+            Coord::new(4.0, 0.0),
+            Coord::new(0.0, 3.0),
+        )),
+        Box::new(Polygon::new(vec![
+            Coord::new(0.0, 0.0),
+            Coord::new(2.0, 0.0),
+            Coord::new(2.0, 2.0),
+            Coord::new(0.0, 2.0),
+        ])),
+        Box::new(Ellipse::new(Coord::new(5.0, 5.0), 4.0, 2.0)),
+    ];
+
+    for s in &shapes {
+        println!(
+            "area {:.2}, perimeter {:.2}, bounding box {:?}",
+            s.area(),
+            s.perimeter(),
+            s.bounding_box()
+        );
+    }
+    println!(
+        "total area of all shapes is {:.2}",
+        shape::total_area(&shapes)
+    );
+    println!(
+        "bounding box of all shapes is {:?}",
+        shape::bounding_box_of(&shapes).and_then(|bbox| bbox.to_rect())
+    );
+
+    /* Packing */
+
+    /* 'can_hold' answers whether one rectangle fits in another. The packing
+     * module places a whole list of rectangles into a container. */
+
+    let items = [
+        rect2,
+        rect3,
+        rect4,
+        Rectangle::square(20),
+        Rectangle::square(60),
+    ];
+    for &algorithm in &[Algorithm::Shelf, Algorithm::Guillotine, Algorithm::MaxRects] {
+        let options = PackOptions {
+            algorithm,
+            allow_rotation: true,
+        };
+        let layout = packing::pack(Rectangle::square(100), &items, options);
+        println!("{:?}: {}", algorithm, layout);
+    }
+
+    /* Dimension types and units */
+
+    /* Rectangle is generic over its dimension type and defaults to u32. A
+     * u32 area overflows for large rectangles, so there are checked and
+     * saturating versions, and u32 rectangles can be widened to u64. */
+
+    let huge = Rectangle::square(100_000u32);
+    println!("checked area of {:?} is {:?}", huge, huge.checked_area());
+    println!(
+        "saturating area of {:?} is {}",
+        huge,
+        huge.saturating_area()
+    );
+    println!(
+        "u64 area of {:?} is {}",
+        huge,
+        Rectangle::<u64>::from(huge).area()
+    );
+
+    let precise = Rectangle {
+        width: 2.5,
+        height: 4.0,
+    };
+    println!("area of {:?} is {}", precise, precise.area());
+
+    /* Size records the unit of a rectangle in its type. */
+
+    let screen: Size<Pixels> = Size::from(rect1);
+    println!(
+        "{} is {:.2} or {:.1}",
+        screen,
+        screen.convert::<Inches>(),
+        screen.convert::<Millimeters>()
+    );
+    println!(
+        "{} on a 300 dpi printer is {:.2}",
+        screen,
+        screen.to_inches_at(300.0)
+    );
+
+    /* Fitting rectangles */
+
+    /* 'can_hold' is strict and never rotates, so rect1 cannot hold a copy of
+     * itself or a turned copy. 'fit' says how a rectangle fits, if at all. */
+
+    let turned = rect1.rotated();
+    println!("Can rect1 hold itself? {}", rect1.can_hold(&rect1));
+    for &mode in &[FitMode::Strict, FitMode::Inclusive] {
+        println!(
+            "{:?}: rect1 in rect1 {:?}, turned rect1 in rect1 {:?}, rect2 in turned rect1 {:?}",
+            mode,
+            rect1.fit(&rect1, mode),
+            rect1.fit(&turned, mode),
+            turned.fit(&rect2, mode)
+        );
+    }
+
+    /* Spatial index */
+
+    /* A QuadTree answers "which rectangles are here?" without checking every
+     * rectangle. Here we fill a 1000x1000 area with a grid of 10x10 cells. */
+
+    let mut tree = QuadTree::new(Rect::new(0, 0, 1000, 1000));
+    for row in 0..50 {
+        for col in 0..50 {
+            tree.insert(Rect::new(col * 20, row * 20, 10, 10));
+        }
+    }
+    let probe = Point::new(205, 405);
+    let hits = tree.containing_point(probe);
+    println!("{} cells, {:?} contains {:?}", tree.len(), probe, hits);
+    println!(
+        "{} cells overlap {:?}",
+        tree.overlapping(&Rect::new(0, 0, 100, 100)).len(),
+        Rect::new(0, 0, 100, 100)
+    );
+    for id in hits {
+        tree.remove(id);
+    }
+    println!(
+        "after removing it, the nearest cell is {:?}",
+        tree.nearest(probe)
+    );
+    let cell = Rectangle::square(10);
+    println!(
+        "{} cells can hold {:?}",
+        tree.can_hold(&cell, FitMode::Inclusive).len(),
+        cell
+    );
+
+    /* Reading and writing rectangles */
+
+    /* Rectangle implements Display, printing "50x30", and FromStr, so the
+     * same text can be parsed back. Lists of rectangles can also be read
+     * from and written to CSV and JSON. */
+
+    println!("rect1 is {}", rect1);
+    let parsed: Rectangle = "50x30".parse().unwrap();
+    println!("\"50x30\" parses to {:?}", parsed);
+
+    let csv = parse::serialize(&[rect1, rect2], Format::Csv);
+    let json = parse::serialize(&[rect1, rect2], Format::Json);
+    println!("as CSV:\n{}as JSON: {}", csv, json);
+    let from_json: Vec<Rectangle> = parse::parse(&json, Format::Json).unwrap();
+    println!("read back from JSON: {:?}", from_json);
+
+    for bad in &["50", "50xabc", "-5x3"] {
+        println!("{:?}: {}", bad, bad.parse::<Rectangle>().unwrap_err());
+    }
+    let bad_csv = "width,height\n50,30\n40\n";
+    println!(
+        "{:?}: {}",
+        bad_csv,
+        parse::parse::<u32>(bad_csv, Format::Csv).unwrap_err()
+    );
+    let bad_json = r#"[{"width": 50, "height": 30}, {"width": 40}]"#;
+    println!(
+        "{}: {}",
+        bad_json,
+        parse::parse::<u32>(bad_json, Format::Json).unwrap_err()
+    );
+
+    /* Drawing rectangles */
+
+    /* The svg module draws rectangles as an SVG image, which is easier to
+     * check than Debug output. Save the output below to a .svg file and
+     * open it in a browser: rect3 does not fit in rect1, so it is dashed. */
+
+    let options = SvgOptions {
+        max_size: 200.0,
+        ..SvgOptions::default()
+    };
+    println!(
+        "{}",
+        svg::render_containment(&rect1, &[rect2, rect3], &options)
+    );
+
+    /* The ascii module draws the same picture in the terminal. */
+
+    let options = AsciiOptions {
+        max_width: 40,
+        ..AsciiOptions::default()
+    };
+    print!("{}", ascii::render_nested(&rect1, &[rect2], &options));
 }
@@ -0,0 +1,513 @@
+/* Rectangle packing.
+ *
+ * 'Rectangle::can_hold' tells us whether one rectangle fits inside another.
+ * Packing asks the bigger question: given a container and a list of
+ * rectangles, where should each rectangle go so that as many as possible fit
+ * without overlapping? Three classic heuristics are provided:
+ *
+ * - Shelf: items are laid out left to right in rows ("shelves"). Fast and
+ *   simple, but wastes the space above short items.
+ * - Guillotine: the free space is kept as a list of rects. Placing an item
+ *   cuts its free rect in two with a single straight cut.
+ * - MaxRects: the free space is kept as the list of all maximal free rects,
+ *   which may overlap each other. Slower, but usually packs the tightest.
+ *
+ * Items may optionally be rotated by 90 degrees. Items with a zero width or
+ * height take no space and are reported as unplaced. */
+
+use std::fmt;
+
+use crate::fit::FitMode;
+use crate::geometry::{Point, Rect};
+use crate::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Shelf,
+    Guillotine,
+    MaxRects,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackOptions {
+    pub algorithm: Algorithm,
+    pub allow_rotation: bool,
+}
+
+impl Default for PackOptions {
+    fn default() -> PackOptions {
+        PackOptions {
+            algorithm: Algorithm::MaxRects,
+            allow_rotation: false,
+        }
+    }
+}
+
+/* Where one input rectangle ended up. 'index' is its position in the list
+ * passed to 'pack', and 'rect' already has the rotation applied. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub index: usize,
+    pub rect: Rect,
+    pub rotated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packing {
+    pub container: Rectangle,
+    pub placements: Vec<Placement>,
+    pub unplaced: Vec<usize>,
+}
+
+impl Packing {
+    pub fn used_area(&self) -> u64 {
+        self.placements.iter().map(|p| p.rect.area()).sum()
+    }
+
+    /* Fraction of the container covered by placed rectangles, from 0 to 1. */
+    pub fn utilization(&self) -> f64 {
+        let total = u64::from(self.container.width) * u64::from(self.container.height);
+        if total == 0 {
+            return 0.0;
+        }
+        self.used_area() as f64 / total as f64
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.unplaced.is_empty()
+    }
+}
+
+/* A plain text report, one placement per line, for inspecting a layout. */
+impl fmt::Display for Packing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "container {}x{}, {} placed, {} unplaced, {:.1}% used",
+            self.container.width,
+            self.container.height,
+            self.placements.len(),
+            self.unplaced.len(),
+            self.utilization() * 100.0
+        )?;
+        for p in &self.placements {
+            writeln!(
+                f,
+                "  #{} {}x{} at ({}, {}){}",
+                p.index,
+                p.rect.width(),
+                p.rect.height(),
+                p.rect.left(),
+                p.rect.top(),
+                if p.rotated { " rotated" } else { "" }
+            )?;
+        }
+        if !self.unplaced.is_empty() {
+            writeln!(f, "  unplaced: {:?}", self.unplaced)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn pack(container: Rectangle, items: &[Rectangle], options: PackOptions) -> Packing {
+    /* Placing big items first leaves the small ones to fill the gaps. */
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by_key(|&i| {
+        let item = items[i];
+        let area = u64::from(item.width) * u64::from(item.height);
+        std::cmp::Reverse((item.width.max(item.height), area))
+    });
+
+    let mut packer: Box<dyn Packer> = match options.algorithm {
+        Algorithm::Shelf => Box::new(ShelfPacker::new(container)),
+        Algorithm::Guillotine => Box::new(GuillotinePacker::new(container)),
+        Algorithm::MaxRects => Box::new(MaxRectsPacker::new(container)),
+    };
+
+    let mut placements = Vec::new();
+    let mut unplaced = Vec::new();
+    for index in order {
+        let item = items[index];
+        if item.width == 0 || item.height == 0 {
+            unplaced.push(index);
+            continue;
+        }
+        match packer.insert(item, options.allow_rotation) {
+            Some((rect, rotated)) => placements.push(Placement {
+                index,
+                rect,
+                rotated,
+            }),
+            None => unplaced.push(index),
+        }
+    }
+    placements.sort_by_key(|p| p.index);
+    unplaced.sort_unstable();
+
+    Packing {
+        container,
+        placements,
+        unplaced,
+    }
+}
+
+trait Packer {
+    /* Places the item somewhere in the free space, returning where it went
+     * and whether it was rotated. */
+    fn insert(&mut self, item: Rectangle, allow_rotation: bool) -> Option<(Rect, bool)>;
+}
+
+/* 'can_hold' is strict, but an item exactly as wide as the free space still
+ * fits when packing. */
+fn fits(space: &Rectangle, item: &Rectangle) -> bool {
+    space.holds(item, FitMode::Inclusive)
+}
+
+/* The orientations worth trying for an item. */
+fn orientations(item: Rectangle, allow_rotation: bool) -> Vec<(Rectangle, bool)> {
+    if allow_rotation && item.width != item.height {
+        vec![(item, false), (item.rotated(), true)]
+    } else {
+        vec![(item, false)]
+    }
+}
+
+struct Shelf {
+    top: u32,
+    height: u32,
+    used_width: u32,
+}
+
+struct ShelfPacker {
+    container: Rectangle,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(container: Rectangle) -> ShelfPacker {
+        ShelfPacker {
+            container,
+            shelves: Vec::new(),
+        }
+    }
+
+    fn next_shelf_top(&self) -> u32 {
+        self.shelves.last().map_or(0, |s| s.top + s.height)
+    }
+}
+
+impl Packer for ShelfPacker {
+    fn insert(&mut self, item: Rectangle, allow_rotation: bool) -> Option<(Rect, bool)> {
+        /* Prefer the existing shelf that wastes the least height. */
+        let mut best: Option<(usize, Rectangle, bool, u32)> = None;
+        for (i, shelf) in self.shelves.iter().enumerate() {
+            for (size, rotated) in orientations(item, allow_rotation) {
+                let space = Rectangle {
+                    width: self.container.width - shelf.used_width,
+                    height: shelf.height,
+                };
+                if !fits(&space, &size) {
+                    continue;
+                }
+                let waste = shelf.height - size.height;
+                if best.is_none_or(|(_, _, _, w)| waste < w) {
+                    best = Some((i, size, rotated, waste));
+                }
+            }
+        }
+        if let Some((i, size, rotated, _)) = best {
+            let shelf = &mut self.shelves[i];
+            let rect = Rect::new(
+                i64::from(shelf.used_width),
+                i64::from(shelf.top),
+                size.width,
+                size.height,
+            );
+            shelf.used_width += size.width;
+            return Some((rect, rotated));
+        }
+
+        /* Otherwise open a new shelf, as low as possible. */
+        let top = self.next_shelf_top();
+        let space = Rectangle {
+            width: self.container.width,
+            height: self.container.height - top,
+        };
+        let (size, rotated) = orientations(item, allow_rotation)
+            .into_iter()
+            .filter(|(size, _)| fits(&space, size))
+            .min_by_key(|(size, _)| size.height)?;
+        self.shelves.push(Shelf {
+            top,
+            height: size.height,
+            used_width: size.width,
+        });
+        Some((
+            Rect::new(0, i64::from(top), size.width, size.height),
+            rotated,
+        ))
+    }
+}
+
+struct GuillotinePacker {
+    free: Vec<Rect>,
+}
+
+impl GuillotinePacker {
+    fn new(container: Rectangle) -> GuillotinePacker {
+        GuillotinePacker {
+            free: vec![Rect::at(Point::origin(), container)],
+        }
+    }
+}
+
+impl Packer for GuillotinePacker {
+    fn insert(&mut self, item: Rectangle, allow_rotation: bool) -> Option<(Rect, bool)> {
+        /* Best area fit: the smallest free rect the item fits into. */
+        let (i, size, rotated) = self
+            .free
+            .iter()
+            .enumerate()
+            .flat_map(|(i, free)| {
+                orientations(item, allow_rotation)
+                    .into_iter()
+                    .filter(move |(size, _)| fits(&free.size, size))
+                    .map(move |(size, rotated)| (i, size, rotated))
+            })
+            .min_by_key(|&(i, _, _)| self.free[i].area())?;
+
+        let free = self.free.swap_remove(i);
+        let placed = Rect::at(free.origin, size);
+
+        /* Cut along the axis that leaves the larger of the two leftovers as
+         * big as possible. */
+        let leftover_w = free.width() - size.width;
+        let leftover_h = free.height() - size.height;
+        let (right, below) = if leftover_w > leftover_h {
+            (
+                Rect::new(placed.right(), free.top(), leftover_w, free.height()),
+                Rect::new(free.left(), placed.bottom(), size.width, leftover_h),
+            )
+        } else {
+            (
+                Rect::new(placed.right(), free.top(), leftover_w, size.height),
+                Rect::new(free.left(), placed.bottom(), free.width(), leftover_h),
+            )
+        };
+        self.free
+            .extend([right, below].iter().filter(|r| !r.is_empty()).copied());
+
+        Some((placed, rotated))
+    }
+}
+
+struct MaxRectsPacker {
+    free: Vec<Rect>,
+}
+
+impl MaxRectsPacker {
+    fn new(container: Rectangle) -> MaxRectsPacker {
+        MaxRectsPacker {
+            free: vec![Rect::at(Point::origin(), container)],
+        }
+    }
+
+    /* Replaces every free rect that overlaps 'used' with the (up to four)
+     * maximal rects around it, then drops free rects inside other ones. */
+    fn split_free(&mut self, used: &Rect) {
+        let mut next = Vec::with_capacity(self.free.len() + 4);
+        for free in &self.free {
+            if !free.overlaps(used) {
+                next.push(*free);
+                continue;
+            }
+            if used.left() > free.left() {
+                next.push(Rect::from_corners(
+                    free.origin,
+                    Point::new(used.left(), free.bottom()),
+                ));
+            }
+            if used.right() < free.right() {
+                next.push(Rect::from_corners(
+                    Point::new(used.right(), free.top()),
+                    Point::new(free.right(), free.bottom()),
+                ));
+            }
+            if used.top() > free.top() {
+                next.push(Rect::from_corners(
+                    free.origin,
+                    Point::new(free.right(), used.top()),
+                ));
+            }
+            if used.bottom() < free.bottom() {
+                next.push(Rect::from_corners(
+                    Point::new(free.left(), used.bottom()),
+                    Point::new(free.right(), free.bottom()),
+                ));
+            }
+        }
+
+        let mut pruned: Vec<Rect> = Vec::with_capacity(next.len());
+        for (i, rect) in next.iter().enumerate() {
+            let redundant = next
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && other.contains_rect(rect) && (other != rect || j < i));
+            if !redundant {
+                pruned.push(*rect);
+            }
+        }
+        self.free = pruned;
+    }
+}
+
+impl Packer for MaxRectsPacker {
+    fn insert(&mut self, item: Rectangle, allow_rotation: bool) -> Option<(Rect, bool)> {
+        /* Best short side fit: the free rect leaving the thinnest sliver. */
+        let (placed, rotated) = self
+            .free
+            .iter()
+            .flat_map(|free| {
+                orientations(item, allow_rotation)
+                    .into_iter()
+                    .filter(move |(size, _)| fits(&free.size, size))
+                    .map(move |(size, rotated)| {
+                        let short = (free.width() - size.width).min(free.height() - size.height);
+                        let long = (free.width() - size.width).max(free.height() - size.height);
+                        ((short, long), Rect::at(free.origin, size), rotated)
+                    })
+            })
+            .min_by_key(|&(score, _, _)| score)
+            .map(|(_, placed, rotated)| (placed, rotated))?;
+
+        self.split_free(&placed);
+        Some((placed, rotated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [Algorithm; 3] =
+        [Algorithm::Shelf, Algorithm::Guillotine, Algorithm::MaxRects];
+
+    fn rectangle(width: u32, height: u32) -> Rectangle {
+        Rectangle { width, height }
+    }
+
+    /* Sizes from a fixed xorshift sequence, so failures can be repeated. */
+    fn random_items(seed: u64, count: usize, max_side: u32) -> Vec<Rectangle> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % u64::from(max_side)) as u32 + 1
+        };
+        (0..count).map(|_| rectangle(next(), next())).collect()
+    }
+
+    /* Every placed rect is inside the bin, has its item's size, and shares
+     * no area with any other; every item is either placed or unplaced. */
+    fn check(packing: &Packing, items: &[Rectangle]) {
+        let bin = Rect::at(Point::origin(), packing.container);
+        let mut seen = vec![false; items.len()];
+        for p in &packing.placements {
+            assert!(bin.contains_rect(&p.rect), "{:?} outside the bin", p);
+            let item = items[p.index];
+            let expected = if p.rotated { item.rotated() } else { item };
+            assert_eq!(p.rect.size, expected, "{:?}", p);
+            assert!(!seen[p.index], "#{} placed twice", p.index);
+            seen[p.index] = true;
+        }
+        for &index in &packing.unplaced {
+            assert!(!seen[index], "#{} both placed and unplaced", index);
+            seen[index] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        for (i, a) in packing.placements.iter().enumerate() {
+            for b in &packing.placements[i + 1..] {
+                assert!(!a.rect.overlaps(&b.rect), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn random_packings_stay_in_the_bin_without_overlapping() {
+        for &algorithm in &ALGORITHMS {
+            for &allow_rotation in &[false, true] {
+                let options = PackOptions {
+                    algorithm,
+                    allow_rotation,
+                };
+                for seed in 1..=20 {
+                    let items = random_items(seed * 7919, 40, 60);
+                    let packing = pack(rectangle(200, 150), &items, options);
+                    check(&packing, &items);
+                    if !allow_rotation {
+                        assert!(packing.placements.iter().all(|p| !p.rotated));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exact_tiling_fills_the_bin() {
+        let items = vec![rectangle(50, 50); 16];
+        for &algorithm in &ALGORITHMS {
+            let options = PackOptions {
+                algorithm,
+                allow_rotation: false,
+            };
+            let packing = pack(rectangle(200, 200), &items, options);
+            check(&packing, &items);
+            assert!(packing.is_complete(), "{:?}", algorithm);
+            assert_eq!(packing.utilization(), 1.0);
+        }
+    }
+
+    #[test]
+    fn items_that_cannot_fit_are_unplaced() {
+        let items = [
+            rectangle(0, 10),
+            rectangle(101, 1),
+            rectangle(10, 0),
+            rectangle(100, 50),
+            rectangle(1, 60),
+        ];
+        for &algorithm in &ALGORITHMS {
+            let options = PackOptions {
+                algorithm,
+                allow_rotation: false,
+            };
+            let packing = pack(rectangle(100, 50), &items, options);
+            check(&packing, &items);
+            assert_eq!(packing.unplaced, [0, 1, 2, 4], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn rotation_lets_tall_items_fit() {
+        let items = [rectangle(10, 100)];
+        for &algorithm in &ALGORITHMS {
+            let options = PackOptions {
+                algorithm,
+                allow_rotation: true,
+            };
+            let packing = pack(rectangle(100, 10), &items, options);
+            check(&packing, &items);
+            assert!(packing.placements[0].rotated, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn empty_bin_holds_nothing() {
+        let items = random_items(42, 5, 10);
+        let packing = pack(rectangle(0, 0), &items, PackOptions::default());
+        check(&packing, &items);
+        assert_eq!(packing.placements, []);
+        assert_eq!(packing.utilization(), 0.0);
+    }
+}
@@ -0,0 +1,713 @@
+/* Reading and writing rectangles as text.
+ *
+ * Three formats are supported:
+ *
+ * - Dimensions: "50x30", one rectangle per line. This is also what
+ *   'Display' prints and what 'str::parse' accepts.
+ * - CSV: "width,height" rows, with an optional header line. The header may
+ *   list the columns in either order.
+ * - JSON: an object such as {"width": 50, "height": 30}, or an array of
+ *   them.
+ *
+ * Positioned rects from the geometry module are written "50x30@10,20".
+ *
+ * Everything is generic over the dimension type, so "2.5x4" parses into a
+ * Rectangle<f64> but is an error for the default Rectangle<u32>. Negative,
+ * NaN and infinite dimensions are errors too.
+ *
+ * Only as much JSON is read as rectangles need; see 'JsonParser'. */
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dimension::Dimension;
+use crate::geometry::{Point, Rect};
+use crate::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dimensions,
+    Csv,
+    Json,
+}
+
+impl Format {
+    /* Guesses the format from a file extension such as "csv". */
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "txt" => Some(Format::Dimensions),
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /* Guesses the format from the text itself: JSON starts with '{' or '[',
+     * CSV has commas but no 'x', and anything else is one "50x30" per line. */
+    pub fn detect(text: &str) -> Format {
+        let text = text.trim_start();
+        if text.starts_with('{') || text.starts_with('[') {
+            Format::Json
+        } else if text
+            .lines()
+            .next()
+            .is_some_and(|line| line.contains(',') && !line.contains(['x', 'X']))
+        {
+            Format::Csv
+        } else {
+            Format::Dimensions
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    /* The text is not of the form WIDTHxHEIGHT (or WIDTHxHEIGHT@X,Y). */
+    MissingSeparator(String),
+    InvalidNumber {
+        field: &'static str,
+        value: String,
+    },
+    WrongColumnCount {
+        expected: usize,
+        found: usize,
+    },
+    MissingField(&'static str),
+    /* A JSON value that should have been a rectangle object. */
+    NotAnObject(String),
+    /* Malformed JSON, with the byte offset where it went wrong. */
+    Json {
+        offset: usize,
+        message: String,
+    },
+    /* Wraps an error from one line of a multi-line input. Lines count from 1. */
+    AtLine {
+        line: usize,
+        error: Box<ParseError>,
+    },
+    /* Wraps an error from one element of a JSON array. Counts from 0. */
+    AtIndex {
+        index: usize,
+        error: Box<ParseError>,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "no rectangle given"),
+            ParseError::MissingSeparator(text) => write!(
+                f,
+                "expected WIDTHxHEIGHT or WIDTHxHEIGHT@X,Y such as 50x30@10,20, found {:?}",
+                text
+            ),
+            ParseError::InvalidNumber { field, value } => {
+                write!(f, "invalid {} {:?}", field, value)
+            }
+            ParseError::WrongColumnCount { expected, found } => {
+                write!(f, "expected {} columns, found {}", expected, found)
+            }
+            ParseError::MissingField(field) => write!(f, "missing field {:?}", field),
+            ParseError::NotAnObject(found) => write!(
+                f,
+                "expected an object with width and height, found {}",
+                found
+            ),
+            ParseError::Json { offset, message } => {
+                write!(f, "invalid JSON at byte {}: {}", offset, message)
+            }
+            ParseError::AtLine { line, error } => write!(f, "line {}: {}", line, error),
+            ParseError::AtIndex { index, error } => write!(f, "item {}: {}", index, error),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/* Prints "50x30", the same form 'str::parse' reads. */
+impl<T: fmt::Display> fmt::Display for Rectangle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/* Reads "50x30". Spaces around the numbers and an upper case 'X' are fine. */
+impl<T: Dimension + FromStr> FromStr for Rectangle<T> {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Rectangle<T>, ParseError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut parts = text.splitn(2, ['x', 'X']);
+        match (parts.next(), parts.next()) {
+            (Some(width), Some(height)) => Ok(Rectangle {
+                width: dimension("width", width)?,
+                height: dimension("height", height)?,
+            }),
+            _ => Err(ParseError::MissingSeparator(text.to_string())),
+        }
+    }
+}
+
+/* Positioned rects are written "50x30@10,20": a size, then the origin. */
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{},{}", self.size, self.origin.x, self.origin.y)
+    }
+}
+
+/* Reads "50x30@10,20". Without an '@' the rect is placed at (0, 0). */
+impl FromStr for Rect {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Rect, ParseError> {
+        let mut parts = text.trim().splitn(2, '@');
+        let size: Rectangle = parts.next().unwrap_or("").parse()?;
+        let origin = match parts.next() {
+            Some(origin) => {
+                let mut coords = origin.splitn(2, ',');
+                match (coords.next(), coords.next()) {
+                    (Some(x), Some(y)) => Point::new(number("x", x)?, number("y", y)?),
+                    _ => return Err(ParseError::MissingSeparator(text.trim().to_string())),
+                }
+            }
+            None => Point::origin(),
+        };
+        Rect::checked_at(origin, size).ok_or_else(|| ParseError::InvalidNumber {
+            field: "origin",
+            value: format!("{},{}", origin.x, origin.y),
+        })
+    }
+}
+
+pub fn parse<T: Dimension + FromStr>(
+    text: &str,
+    format: Format,
+) -> Result<Vec<Rectangle<T>>, ParseError> {
+    match format {
+        Format::Dimensions => parse_lines(text),
+        Format::Csv => parse_csv(text),
+        Format::Json => parse_json(text),
+    }
+}
+
+pub fn serialize<T: Dimension>(rects: &[Rectangle<T>], format: Format) -> String {
+    match format {
+        Format::Dimensions => rects.iter().map(|r| format!("{}\n", r)).collect(),
+        Format::Csv => to_csv(rects),
+        Format::Json => to_json(rects),
+    }
+}
+
+/* One "50x30" per line. Blank lines are skipped. */
+pub fn parse_lines<T: Dimension + FromStr>(text: &str) -> Result<Vec<Rectangle<T>>, ParseError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| line.parse().map_err(|error| at_line(i, error)))
+        .collect()
+}
+
+pub fn parse_csv<T: Dimension + FromStr>(text: &str) -> Result<Vec<Rectangle<T>>, ParseError> {
+    let mut rows = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+
+    /* A header tells us the column order. Without one it is width,height. */
+    let mut width_first = true;
+    if let Some(&(i, line)) = rows.peek() {
+        let columns = split_csv(line);
+        if columns.iter().any(|c| c.eq_ignore_ascii_case("width")) {
+            width_first = match columns.as_slice() {
+                [a, b] if a.eq_ignore_ascii_case("width") && b.eq_ignore_ascii_case("height") => {
+                    true
+                }
+                [a, b] if a.eq_ignore_ascii_case("height") && b.eq_ignore_ascii_case("width") => {
+                    false
+                }
+                [_, _] => return Err(at_line(i, ParseError::MissingField("height"))),
+                _ => {
+                    let error = ParseError::WrongColumnCount {
+                        expected: 2,
+                        found: columns.len(),
+                    };
+                    return Err(at_line(i, error));
+                }
+            };
+            rows.next();
+        }
+    }
+
+    rows.map(|(i, line)| match split_csv(line).as_slice() {
+        [a, b] => {
+            let (width, height) = if width_first { (a, b) } else { (b, a) };
+            Ok(Rectangle {
+                width: dimension("width", width).map_err(|e| at_line(i, e))?,
+                height: dimension("height", height).map_err(|e| at_line(i, e))?,
+            })
+        }
+        columns => Err(at_line(
+            i,
+            ParseError::WrongColumnCount {
+                expected: 2,
+                found: columns.len(),
+            },
+        )),
+    })
+    .collect()
+}
+
+pub fn to_csv<T: Dimension>(rects: &[Rectangle<T>]) -> String {
+    let mut csv = String::from("width,height\n");
+    for rect in rects {
+        csv.push_str(&format!("{},{}\n", rect.width, rect.height));
+    }
+    csv
+}
+
+/* Accepts a single object or an array of objects. Other keys in the objects
+ * are ignored. */
+pub fn parse_json<T: Dimension + FromStr>(text: &str) -> Result<Vec<Rectangle<T>>, ParseError> {
+    let mut parser = JsonParser { text, pos: 0 };
+    let value = parser.document()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected text after the JSON value"));
+    }
+    match value {
+        Json::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                rectangle_from_json(item).map_err(|error| ParseError::AtIndex {
+                    index,
+                    error: Box::new(error),
+                })
+            })
+            .collect(),
+        object => Ok(vec![rectangle_from_json(&object)?]),
+    }
+}
+
+pub fn to_json<T: Dimension>(rects: &[Rectangle<T>]) -> String {
+    let objects: Vec<String> = rects.iter().map(rectangle_to_json).collect();
+    format!("[{}]", objects.join(","))
+}
+
+pub fn rectangle_to_json<T: Dimension>(rect: &Rectangle<T>) -> String {
+    format!("{{\"width\":{},\"height\":{}}}", rect.width, rect.height)
+}
+
+pub fn rect_to_json(rect: &Rect) -> String {
+    format!(
+        "{{\"x\":{},\"y\":{},\"width\":{},\"height\":{}}}",
+        rect.left(),
+        rect.top(),
+        rect.width(),
+        rect.height()
+    )
+}
+
+fn number<T: FromStr>(field: &'static str, value: &str) -> Result<T, ParseError> {
+    value.trim().parse().map_err(|_| ParseError::InvalidNumber {
+        field,
+        value: value.trim().to_string(),
+    })
+}
+
+/* A width or height: a number that is neither negative, NaN nor infinite. */
+fn dimension<T: Dimension + FromStr>(field: &'static str, value: &str) -> Result<T, ParseError> {
+    let parsed: T = number(field, value)?;
+    let as_f64 = parsed.to_f64();
+    if as_f64.is_finite() && as_f64 >= 0.0 {
+        Ok(parsed)
+    } else {
+        Err(ParseError::InvalidNumber {
+            field,
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+fn at_line(index: usize, error: ParseError) -> ParseError {
+    ParseError::AtLine {
+        line: index + 1,
+        error: Box::new(error),
+    }
+}
+
+fn split_csv(line: &str) -> Vec<&str> {
+    line.split(',').map(str::trim).collect()
+}
+
+fn rectangle_from_json<T: Dimension + FromStr>(value: &Json) -> Result<Rectangle<T>, ParseError> {
+    let fields = match value {
+        Json::Object(fields) => fields,
+        other => return Err(ParseError::NotAnObject(other.describe())),
+    };
+    let field = |name: &'static str| -> Result<T, ParseError> {
+        match fields.iter().find(|(key, _)| key == name) {
+            Some((_, Json::Number(text))) => dimension(name, text),
+            Some((_, other)) => Err(ParseError::InvalidNumber {
+                field: name,
+                value: other.describe(),
+            }),
+            None => Err(ParseError::MissingField(name)),
+        }
+    };
+    Ok(Rectangle {
+        width: field("width")?,
+        height: field("height")?,
+    })
+}
+
+/* Just enough JSON to read rectangles: one rectangle object, or an array of
+ * them. Numbers are kept as text so that they can be parsed into whichever
+ * dimension type is wanted.
+ *
+ * This is deliberately not a full JSON parser. Values inside an object must
+ * be numbers, strings, booleans or null, and only the outermost value may be
+ * an array, so nesting is at most an array of objects and the parser never
+ * recurses deeper than that, whatever the input. Strings are checked but not
+ * decoded: escapes are kept as written, so keys must be spelled out plainly
+ * ("width", not "w\u0069dth"). */
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn describe(&self) -> String {
+        match self {
+            Json::Null => String::from("null"),
+            Json::Bool(b) => b.to_string(),
+            Json::Number(n) => n.clone(),
+            Json::String(s) => format!("\"{}\"", s),
+            Json::Array(_) => String::from("an array"),
+            Json::Object(_) => String::from("an object"),
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError::Json {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    /* The outermost value, the only one that may be an array. */
+    fn document(&mut self) -> Result<Json, ParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return self.item();
+        }
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.item()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    /* An element of the outermost array: an object or a plain value. */
+    fn item(&mut self) -> Result<Json, ParseError> {
+        self.skip_whitespace();
+        if self.peek() == Some('{') {
+            self.object()
+        } else {
+            self.scalar()
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, ParseError> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.scalar()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Json, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') | Some('[') => Err(self.error("nested arrays and objects are not supported")),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some(c) if c == '-' || c.is_ascii_digit() => Ok(self.number()),
+            Some(_) => self.keyword(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /* The text between the quotes, escapes and all. */
+    fn string(&mut self) -> Result<String, ParseError> {
+        if self.peek() != Some('"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            match c {
+                '"' => {
+                    let s = self.text[start..self.pos].to_string();
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    let length = match self.peek() {
+                        Some('"') | Some('\\') | Some('/') | Some('b') | Some('f') | Some('n')
+                        | Some('r') | Some('t') => 1,
+                        Some('u') if self.is_hex4(self.pos + 1) => 5,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    self.pos += length;
+                }
+                c => self.pos += c.len_utf8(),
+            }
+        }
+    }
+
+    fn is_hex4(&self, at: usize) -> bool {
+        self.text
+            .get(at..at + 4)
+            .is_some_and(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+    }
+
+    fn number(&mut self) -> Json {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            self.pos += 1;
+        }
+        Json::Number(self.text[start..self.pos].to_string())
+    }
+
+    fn keyword(&mut self) -> Result<Json, ParseError> {
+        let rest = &self.text[self.pos..];
+        let (value, length) = if rest.starts_with("true") {
+            (Json::Bool(true), 4)
+        } else if rest.starts_with("false") {
+            (Json::Bool(false), 5)
+        } else if rest.starts_with("null") {
+            (Json::Null, 4)
+        } else {
+            return Err(self.error("unexpected character"));
+        };
+        self.pos += length;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle<T>(width: T, height: T) -> Rectangle<T> {
+        Rectangle { width, height }
+    }
+
+    fn rects() -> Vec<Rectangle> {
+        vec![rectangle(50, 30), rectangle(0, 7), rectangle(u32::MAX, 1)]
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for &format in &[Format::Dimensions, Format::Csv, Format::Json] {
+            let text = serialize(&rects(), format);
+            assert_eq!(Format::detect(&text), format, "{}", text);
+            assert_eq!(parse::<u32>(&text, format), Ok(rects()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn float_rectangles_round_trip() {
+        let rects = vec![rectangle(2.5, 4.0), rectangle(0.1, 1e-3)];
+        for &format in &[Format::Dimensions, Format::Csv, Format::Json] {
+            let text = serialize(&rects, format);
+            assert_eq!(parse::<f64>(&text, format), Ok(rects.clone()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn positioned_rects_round_trip() {
+        for rect in &[Rect::new(10, 20, 50, 30), Rect::new(-5, -7, 0, 1)] {
+            assert_eq!(rect.to_string().parse::<Rect>(), Ok(*rect));
+        }
+        assert!("1x1@2305843009213693953,0".parse::<Rect>().is_err());
+    }
+
+    #[test]
+    fn json_strings_are_checked_but_not_decoded() {
+        let json = r#"{"name": "café \"x\" \ud83d\b\f", "width": 5, "height": 3}"#;
+        assert_eq!(parse_json::<u32>(json), Ok(vec![rectangle(5, 3)]));
+        /* Keys are compared as written. */
+        let escaped_key = r#"{"w\u0069dth": 5, "height": 3}"#;
+        assert_eq!(
+            parse_json::<u32>(escaped_key),
+            Err(ParseError::MissingField("width"))
+        );
+        for json in &[
+            r#"{"name": "\u00g1", "width": 5, "height": 3}"#,
+            r#"{"name": "\u+0a1", "width": 5, "height": 3}"#,
+            r#"{"name": "\x", "width": 5, "height": 3}"#,
+            r#"{"name": "open, "width": 5, "height": 3}"#,
+        ] {
+            match parse_json::<u32>(json) {
+                Err(ParseError::Json { .. }) => {}
+                other => panic!("{} parsed as {:?}", json, other),
+            }
+        }
+    }
+
+    #[test]
+    fn json_nesting_is_refused() {
+        for (json, offset) in &[
+            (r#"[[{"width": 5, "height": 3}]]"#, 1),
+            (r#"{"width": 5, "height": 3, "tags": ["a"]}"#, 34),
+            (r#"{"width": 5, "height": {"value": 3}}"#, 23),
+        ] {
+            match parse_json::<u32>(json) {
+                Err(ParseError::Json { offset: found, .. }) => {
+                    assert_eq!(found, *offset, "{}", json)
+                }
+                other => panic!("{} parsed as {:?}", json, other),
+            }
+        }
+        /* A long run of '[' fails at once instead of recursing. */
+        let deep = "[".repeat(200_000);
+        assert!(matches!(
+            parse_json::<u32>(&deep),
+            Err(ParseError::Json { offset: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn json_items_must_be_rectangles() {
+        assert_eq!(
+            parse_json::<u32>(r#"[{"width": 5, "height": 3}, 7]"#),
+            Err(ParseError::AtIndex {
+                index: 1,
+                error: Box::new(ParseError::NotAnObject(String::from("7"))),
+            })
+        );
+        assert_eq!(
+            parse_json::<u32>(r#"{"width": "5", "height": 3}"#),
+            Err(ParseError::InvalidNumber {
+                field: "width",
+                value: String::from("\"5\""),
+            })
+        );
+        assert!(matches!(
+            parse_json::<u32>(r#"{"width": 5, "height": 3} x"#),
+            Err(ParseError::Json { offset: 26, .. })
+        ));
+    }
+
+    #[test]
+    fn negative_and_non_finite_dimensions_are_refused() {
+        let invalid = |field, value: &str| ParseError::InvalidNumber {
+            field,
+            value: value.to_string(),
+        };
+        for (text, error) in &[
+            ("-1x2", invalid("width", "-1")),
+            ("2x-0.5", invalid("height", "-0.5")),
+            ("NaNx2", invalid("width", "NaN")),
+            ("2xinf", invalid("height", "inf")),
+            ("-infx2", invalid("width", "-inf")),
+            ("1e400x2", invalid("width", "1e400")),
+        ] {
+            assert_eq!(
+                text.parse::<Rectangle<f64>>().as_ref(),
+                Err(error),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            parse_csv::<f64>("width,height\n2,-3\n"),
+            Err(at_line(1, invalid("height", "-3")))
+        );
+        assert_eq!(
+            parse_json::<f64>(r#"{"width": 1e999, "height": 3}"#),
+            Err(invalid("width", "1e999"))
+        );
+        assert_eq!("0x-0".parse::<Rectangle<f64>>(), Ok(rectangle(0.0, -0.0)));
+        assert!("-1x2".parse::<Rectangle<u32>>().is_err());
+    }
+}
@@ -0,0 +1,555 @@
+/* Shapes.
+ *
+ * 'Rectangle::area' only works on rectangles. The 'Shape' trait describes
+ * what every shape can tell us about itself, so code can work with a mix of
+ * rectangles, circles, triangles, polygons and ellipses, either through
+ * generics ('fn f<S: Shape>(s: &S)') or trait objects ('Box<dyn Shape>').
+ *
+ * Shapes other than Rectangle can sit at fractional coordinates, so all
+ * measurements are f64. As in the geometry module, y grows downwards.
+ *
+ * The constructors panic on a NaN or infinite coordinate and on a radius
+ * that is negative, NaN or infinite, since every measurement of such a
+ * shape would be meaningless; the 'checked_new' constructors return None
+ * instead. */
+
+use std::f64::consts::PI;
+
+use crate::dimension::Dimension;
+use crate::geometry::{Point, Rect};
+use crate::Rectangle;
+
+pub trait Shape {
+    fn area(&self) -> f64;
+    fn perimeter(&self) -> f64;
+    fn bounding_box(&self) -> BoundingBox;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Coord {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Coord {
+    pub fn new(x: f64, y: f64) -> Coord {
+        Coord { x, y }
+    }
+
+    pub fn distance(&self, other: &Coord) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+}
+
+fn is_length(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+/* An axis-aligned box with f64 edges, returned by 'Shape::bounding_box'. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Coord,
+    pub max: Coord,
+}
+
+impl BoundingBox {
+    /* The smallest box holding all the given coordinates. */
+    pub fn around<'a, I>(coords: I) -> Option<BoundingBox>
+    where
+        I: IntoIterator<Item = &'a Coord>,
+    {
+        let mut coords = coords.into_iter();
+        let first = *coords.next()?;
+        let mut bbox = BoundingBox {
+            min: first,
+            max: first,
+        };
+        for coord in coords {
+            bbox.min.x = bbox.min.x.min(coord.x);
+            bbox.min.y = bbox.min.y.min(coord.y);
+            bbox.max.x = bbox.max.x.max(coord.x);
+            bbox.max.y = bbox.max.y.max(coord.y);
+        }
+        Some(bbox)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.y - self.min.y
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Coord::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Coord::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /* The smallest integer rect covering this box, or None if there is no
+     * such Rect: an edge is NaN or infinite, the top-left corner is outside
+     * the bounds of 'geometry', or the box is wider or taller than a u32. */
+    pub fn to_rect(&self) -> Option<Rect> {
+        let top_left = Point::new(to_i64(self.min.x.floor())?, to_i64(self.min.y.floor())?);
+        let bottom_right = Point::new(to_i64(self.max.x.ceil())?, to_i64(self.max.y.ceil())?);
+        if bottom_right.x < top_left.x || bottom_right.y < top_left.y {
+            return None;
+        }
+        Rect::checked_from_corners(top_left, bottom_right)
+    }
+}
+
+/* A whole f64 as an i64, if it is well within range. Anything past 2^62 is
+ * out of the bounds of 'geometry' anyway. */
+fn to_i64(value: f64) -> Option<i64> {
+    const LIMIT: f64 = (1u64 << 62) as f64;
+    if value.is_finite() && value.abs() <= LIMIT {
+        Some(value as i64)
+    } else {
+        None
+    }
+}
+
+/* A Rectangle has no position, so its bounding box starts at (0, 0). */
+impl<T: Dimension> Shape for Rectangle<T> {
+    fn area(&self) -> f64 {
+        self.width.to_f64() * self.height.to_f64()
+    }
+
+    fn perimeter(&self) -> f64 {
+        2.0 * (self.width.to_f64() + self.height.to_f64())
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            min: Coord::default(),
+            max: Coord::new(self.width.to_f64(), self.height.to_f64()),
+        }
+    }
+}
+
+impl Shape for Rect {
+    fn area(&self) -> f64 {
+        Shape::area(&self.size)
+    }
+
+    fn perimeter(&self) -> f64 {
+        Shape::perimeter(&self.size)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            min: Coord::new(self.left() as f64, self.top() as f64),
+            max: Coord::new(self.right() as f64, self.bottom() as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub center: Coord,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(center: Coord, radius: f64) -> Circle {
+        Circle::checked_new(center, radius).expect("invalid circle")
+    }
+
+    pub fn checked_new(center: Coord, radius: f64) -> Option<Circle> {
+        Some(Circle { center, radius }).filter(|_| center.is_finite() && is_length(radius))
+    }
+}
+
+impl Shape for Circle {
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    fn perimeter(&self) -> f64 {
+        2.0 * PI * self.radius
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            min: Coord::new(self.center.x - self.radius, self.center.y - self.radius),
+            max: Coord::new(self.center.x + self.radius, self.center.y + self.radius),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipse {
+    pub center: Coord,
+    pub radius_x: f64,
+    pub radius_y: f64,
+}
+
+impl Ellipse {
+    pub fn new(center: Coord, radius_x: f64, radius_y: f64) -> Ellipse {
+        Ellipse::checked_new(center, radius_x, radius_y).expect("invalid ellipse")
+    }
+
+    pub fn checked_new(center: Coord, radius_x: f64, radius_y: f64) -> Option<Ellipse> {
+        let valid = center.is_finite() && is_length(radius_x) && is_length(radius_y);
+        Some(Ellipse {
+            center,
+            radius_x,
+            radius_y,
+        })
+        .filter(|_| valid)
+    }
+}
+
+impl Shape for Ellipse {
+    fn area(&self) -> f64 {
+        PI * self.radius_x * self.radius_y
+    }
+
+    /* There is no closed form for the perimeter of an ellipse. This is
+     * Ramanujan's second approximation, which is exact for circles. */
+    fn perimeter(&self) -> f64 {
+        let (a, b) = (self.radius_x, self.radius_y);
+        if a + b == 0.0 {
+            return 0.0;
+        }
+        let h = ((a - b) * (a - b)) / ((a + b) * (a + b));
+        PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            min: Coord::new(self.center.x - self.radius_x, self.center.y - self.radius_y),
+            max: Coord::new(self.center.x + self.radius_x, self.center.y + self.radius_y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub a: Coord,
+    pub b: Coord,
+    pub c: Coord,
+}
+
+impl Triangle {
+    pub fn new(a: Coord, b: Coord, c: Coord) -> Triangle {
+        Triangle::checked_new(a, b, c).expect("invalid triangle")
+    }
+
+    pub fn checked_new(a: Coord, b: Coord, c: Coord) -> Option<Triangle> {
+        Some(Triangle { a, b, c }).filter(|_| a.is_finite() && b.is_finite() && c.is_finite())
+    }
+}
+
+impl Shape for Triangle {
+    fn area(&self) -> f64 {
+        shoelace_area(&[self.a, self.b, self.c])
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.a.distance(&self.b) + self.b.distance(&self.c) + self.c.distance(&self.a)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::around(&[self.a, self.b, self.c]).unwrap()
+    }
+}
+
+/* A simple (non self-intersecting) polygon given by its vertices in order.
+ * The last vertex is implicitly joined to the first. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub vertices: Vec<Coord>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Coord>) -> Polygon {
+        Polygon::checked_new(vertices).expect("invalid polygon")
+    }
+
+    pub fn checked_new(vertices: Vec<Coord>) -> Option<Polygon> {
+        if vertices.iter().all(Coord::is_finite) {
+            Some(Polygon { vertices })
+        } else {
+            None
+        }
+    }
+}
+
+impl Shape for Polygon {
+    fn area(&self) -> f64 {
+        shoelace_area(&self.vertices)
+    }
+
+    fn perimeter(&self) -> f64 {
+        let n = self.vertices.len();
+        if n < 2 {
+            return 0.0;
+        }
+        (0..n)
+            .map(|i| self.vertices[i].distance(&self.vertices[(i + 1) % n]))
+            .sum()
+    }
+
+    /* An empty polygon has an empty bounding box at (0, 0). */
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::around(&self.vertices).unwrap_or(BoundingBox {
+            min: Coord::default(),
+            max: Coord::default(),
+        })
+    }
+}
+
+/* Lets generic code take boxed trait objects and references as shapes, so
+ * 'total_area' works on both '&[Circle]' and '&[Box<dyn Shape>]'. */
+impl<S: Shape + ?Sized> Shape for Box<S> {
+    fn area(&self) -> f64 {
+        (**self).area()
+    }
+
+    fn perimeter(&self) -> f64 {
+        (**self).perimeter()
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        (**self).bounding_box()
+    }
+}
+
+impl<S: Shape + ?Sized> Shape for &S {
+    fn area(&self) -> f64 {
+        (**self).area()
+    }
+
+    fn perimeter(&self) -> f64 {
+        (**self).perimeter()
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        (**self).bounding_box()
+    }
+}
+
+pub fn total_area<S: Shape>(shapes: &[S]) -> f64 {
+    shapes.iter().map(Shape::area).sum()
+}
+
+pub fn total_perimeter<S: Shape>(shapes: &[S]) -> f64 {
+    shapes.iter().map(Shape::perimeter).sum()
+}
+
+/* The shape with the largest area, or None if there are no shapes. */
+pub fn largest<S: Shape>(shapes: &[S]) -> Option<&S> {
+    shapes.iter().max_by(|a, b| {
+        a.area()
+            .partial_cmp(&b.area())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
+}
+
+/* The box around every shape, or None if there are no shapes. */
+pub fn bounding_box_of<S: Shape>(shapes: &[S]) -> Option<BoundingBox> {
+    let mut boxes = shapes.iter().map(Shape::bounding_box);
+    let first = boxes.next()?;
+    Some(boxes.fold(first, |acc, bbox| acc.union(&bbox)))
+}
+
+fn shoelace_area(vertices: &[Coord]) -> f64 {
+    let n = vertices.len();
+    if n < 3 {
+        return 0.0;
+    }
+    let twice_area: f64 = (0..n)
+        .map(|i| {
+            let (p, q) = (vertices[i], vertices[(i + 1) % n]);
+            p.x * q.y - q.x * p.y
+        })
+        .sum();
+    twice_area.abs() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::MAX_COORD;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    fn bbox(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> BoundingBox {
+        BoundingBox {
+            min: Coord::new(min_x, min_y),
+            max: Coord::new(max_x, max_y),
+        }
+    }
+
+    #[test]
+    fn measurements_of_each_shape() {
+        let shapes: Vec<(Box<dyn Shape>, f64, f64, BoundingBox)> = vec![
+            (
+                Box::new(Rectangle {
+                    width: 4u32,
+                    height: 3,
+                }),
+                12.0,
+                14.0,
+                bbox(0.0, 0.0, 4.0, 3.0),
+            ),
+            (
+                Box::new(Rect::new(-2, 5, 4, 3)),
+                12.0,
+                14.0,
+                bbox(-2.0, 5.0, 2.0, 8.0),
+            ),
+            (
+                Box::new(Circle::new(Coord::new(1.0, -1.0), 2.0)),
+                4.0 * PI,
+                4.0 * PI,
+                bbox(-1.0, -3.0, 3.0, 1.0),
+            ),
+            /* An ellipse with equal radii is a circle. */
+            (
+                Box::new(Ellipse::new(Coord::new(0.0, 0.0), 2.0, 2.0)),
+                4.0 * PI,
+                4.0 * PI,
+                bbox(-2.0, -2.0, 2.0, 2.0),
+            ),
+            /* Ramanujan's approximation, 1e-8 short of the exact
+             * 19.3768964... for radii 4 and 2. */
+            (
+                Box::new(Ellipse::new(Coord::new(5.0, 5.0), 4.0, 2.0)),
+                8.0 * PI,
+                19.376_896_432_26,
+                bbox(1.0, 3.0, 9.0, 7.0),
+            ),
+            (
+                Box::new(Triangle::new(
+                    Coord::new(0.0, 0.0),
+                    Coord::new(4.0, 0.0),
+                    Coord::new(0.0, 3.0),
+                )),
+                6.0,
+                12.0,
+                bbox(0.0, 0.0, 4.0, 3.0),
+            ),
+            /* An L shape, given clockwise. */
+            (
+                Box::new(Polygon::new(vec![
+                    Coord::new(0.0, 0.0),
+                    Coord::new(2.0, 0.0),
+                    Coord::new(2.0, 1.0),
+                    Coord::new(1.0, 1.0),
+                    Coord::new(1.0, 2.0),
+                    Coord::new(0.0, 2.0),
+                ])),
+                3.0,
+                8.0,
+                bbox(0.0, 0.0, 2.0, 2.0),
+            ),
+        ];
+        for (shape, area, perimeter, bounds) in &shapes {
+            assert!(close(shape.area(), *area), "{} != {}", shape.area(), area);
+            assert!(
+                close(shape.perimeter(), *perimeter),
+                "{} != {}",
+                shape.perimeter(),
+                perimeter
+            );
+            assert_eq!(shape.bounding_box(), *bounds);
+        }
+        assert!(close(
+            total_area(&shapes.iter().map(|s| &s.0).collect::<Vec<_>>()),
+            33.0 + 16.0 * PI
+        ));
+    }
+
+    #[test]
+    fn degenerate_shapes_measure_zero() {
+        let point = Circle::new(Coord::new(3.0, 4.0), 0.0);
+        assert_eq!((point.area(), point.perimeter()), (0.0, 0.0));
+        let flat = Ellipse::new(Coord::default(), 0.0, 0.0);
+        assert_eq!((flat.area(), flat.perimeter()), (0.0, 0.0));
+        let line = Triangle::new(
+            Coord::new(0.0, 0.0),
+            Coord::new(1.0, 1.0),
+            Coord::new(2.0, 2.0),
+        );
+        assert_eq!(line.area(), 0.0);
+        let empty = Polygon::new(Vec::new());
+        assert_eq!((empty.area(), empty.perimeter()), (0.0, 0.0));
+        assert_eq!(empty.bounding_box(), bbox(0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn invalid_shapes_are_refused() {
+        let origin = Coord::default();
+        for &radius in &[-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(Circle::checked_new(origin, radius), None);
+            assert_eq!(Ellipse::checked_new(origin, radius, 1.0), None);
+            assert_eq!(Ellipse::checked_new(origin, 1.0, radius), None);
+        }
+        let nowhere = Coord::new(f64::NAN, 0.0);
+        assert_eq!(Circle::checked_new(nowhere, 1.0), None);
+        assert_eq!(Triangle::checked_new(origin, origin, nowhere), None);
+        assert_eq!(
+            Polygon::checked_new(vec![origin, Coord::new(0.0, f64::INFINITY)]),
+            None
+        );
+        assert!(Circle::checked_new(origin, 0.0).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid circle")]
+    fn negative_radius_panics() {
+        Circle::new(Coord::default(), -1.0);
+    }
+
+    #[test]
+    fn bounding_box_rounds_out_to_a_rect() {
+        assert_eq!(
+            bbox(-0.5, 0.25, 2.1, 3.0).to_rect(),
+            Some(Rect::new(-1, 0, 4, 3))
+        );
+        let shapes = [
+            Circle::new(Coord::new(0.0, 0.0), 1.5),
+            Circle::new(Coord::new(10.0, 0.0), 1.0),
+        ];
+        assert_eq!(
+            bounding_box_of(&shapes).and_then(|b| b.to_rect()),
+            Some(Rect::new(-2, -2, 13, 4))
+        );
+        assert_eq!(bounding_box_of::<Circle>(&[]), None);
+    }
+
+    #[test]
+    fn bounding_box_without_a_rect() {
+        let huge = (1u64 << 61) as f64;
+        for bounds in &[
+            bbox(f64::NAN, 0.0, 1.0, 1.0),
+            bbox(0.0, 0.0, f64::INFINITY, 1.0),
+            bbox(f64::NEG_INFINITY, 0.0, 1.0, 1.0),
+            bbox(0.0, 0.0, 1e300, 1.0),
+            /* Wider than a u32. */
+            bbox(0.0, 0.0, 4_294_967_296.0, 1.0),
+            /* The top-left corner out of bounds. */
+            bbox(2.0 * huge, 0.0, 2.0 * huge + 1.0, 1.0),
+            /* Inside out. */
+            bbox(1.0, 0.0, 0.0, 1.0),
+        ] {
+            assert_eq!(bounds.to_rect(), None, "{:?}", bounds);
+        }
+        /* Near 2^61 an f64 only holds multiples of 512. */
+        assert_eq!(
+            bbox(huge, 0.0, huge + 4_294_966_784.0, 1.0).to_rect(),
+            Some(Rect::new(MAX_COORD, 0, u32::MAX - 511, 1))
+        );
+    }
+}
@@ -0,0 +1,475 @@
+/* A quadtree of positioned rectangles.
+ *
+ * Checking thousands of rects one by one for every "what is under this
+ * point?" question gets slow. A quadtree splits its area into four quadrants,
+ * and each quadrant again into four once it holds too many rects. A query
+ * then only looks at the quadrants that can possibly match.
+ *
+ * Each rect is stored in the smallest quadrant that contains it completely.
+ * Rects that straddle a quadrant boundary, or lie outside the bounds of the
+ * tree altogether, stay in the parent, so any rect can be inserted. Such
+ * rects are never subdivided: every query checks the rects at the root, so
+ * a tree whose bounds miss most of its rects, or where many rects cross the
+ * middle of the bounds, is little faster than a list.
+ *
+ * Each node also remembers the widest and tallest rect below it, so that
+ * 'can_hold' can skip quadrants where every rect is too small. */
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::fit::FitMode;
+use crate::geometry::{Point, Rect};
+use crate::Rectangle;
+
+/* Nodes with more rects than this are split into quadrants... */
+const MAX_ITEMS: usize = 8;
+/* ...unless they are this deep already. */
+const MAX_DEPTH: usize = 12;
+
+/* Identifies a rect inserted into a QuadTree. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemId(usize);
+
+#[derive(Debug)]
+pub struct QuadTree {
+    root: Node,
+    items: HashMap<ItemId, Rect>,
+    next_id: usize,
+}
+
+#[derive(Debug)]
+struct Node {
+    bounds: Rect,
+    depth: usize,
+    items: Vec<(ItemId, Rect)>,
+    children: Option<Box<[Node; 4]>>,
+    /* The largest width and the largest height of the rects in this node
+     * and its children, not necessarily of the same rect. */
+    largest: Rectangle,
+}
+
+impl QuadTree {
+    /* 'bounds' should cover the area where most rects will be. */
+    pub fn new(bounds: Rect) -> QuadTree {
+        QuadTree {
+            root: Node::new(bounds, 0),
+            items: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.root.bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<Rect> {
+        self.items.get(&id).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, Rect)> + '_ {
+        self.items.iter().map(|(&id, &rect)| (id, rect))
+    }
+
+    pub fn insert(&mut self, rect: Rect) -> ItemId {
+        let id = ItemId(self.next_id);
+        self.next_id += 1;
+        self.items.insert(id, rect);
+        self.root.insert(id, rect);
+        id
+    }
+
+    /* Removes a rect, returning it, or None if the id is unknown. */
+    pub fn remove(&mut self, id: ItemId) -> Option<Rect> {
+        let rect = self.items.remove(&id)?;
+        self.root.remove(id, &rect);
+        Some(rect)
+    }
+
+    /* All rects containing the point, sorted by id. */
+    pub fn containing_point(&self, point: Point) -> Vec<ItemId> {
+        let mut found = Vec::new();
+        self.root.visit(
+            &|node| node.bounds.contains_point(point),
+            &mut |id, rect| {
+                if rect.contains_point(point) {
+                    found.push(id);
+                }
+            },
+        );
+        found.sort_unstable();
+        found
+    }
+
+    /* All rects sharing some area with the region, sorted by id. */
+    pub fn overlapping(&self, region: &Rect) -> Vec<ItemId> {
+        let mut found = Vec::new();
+        self.root
+            .visit(&|node| node.bounds.overlaps(region), &mut |id, rect| {
+                if rect.overlaps(region) {
+                    found.push(id);
+                }
+            });
+        found.sort_unstable();
+        found
+    }
+
+    /* All rects big enough to hold a rectangle of the given size, sorted by
+     * id. Quadrants are skipped by size rather than position. */
+    pub fn can_hold(&self, size: &Rectangle, mode: FitMode) -> Vec<ItemId> {
+        let mut found = Vec::new();
+        self.root
+            .visit(&|node| node.largest.holds(size, mode), &mut |id, rect| {
+                if rect.size.holds(size, mode) {
+                    found.push(id);
+                }
+            });
+        found.sort_unstable();
+        found
+    }
+
+    /* The rect closest to the point, with its squared distance. A rect
+     * containing the point has distance 0. Ties go to the lowest id. */
+    pub fn nearest(&self, point: Point) -> Option<(ItemId, u128)> {
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse(Candidate {
+            distance: 0,
+            kind: CandidateKind::Node(&self.root),
+        }));
+
+        while let Some(Reverse(candidate)) = heap.pop() {
+            match candidate.kind {
+                CandidateKind::Item(id) => return Some((id, candidate.distance)),
+                CandidateKind::Node(node) => {
+                    for &(id, rect) in &node.items {
+                        heap.push(Reverse(Candidate {
+                            distance: distance_squared(&rect, point),
+                            kind: CandidateKind::Item(id),
+                        }));
+                    }
+                    if let Some(children) = &node.children {
+                        for child in children.iter() {
+                            heap.push(Reverse(Candidate {
+                                distance: distance_squared(&child.bounds, point),
+                                kind: CandidateKind::Node(child),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Node {
+    fn new(bounds: Rect, depth: usize) -> Node {
+        Node {
+            bounds,
+            depth,
+            items: Vec::new(),
+            children: None,
+            largest: Rectangle {
+                width: 0,
+                height: 0,
+            },
+        }
+    }
+
+    /* The child quadrant that fully contains the rect, if there is one. */
+    fn child_for(&mut self, rect: &Rect) -> Option<&mut Node> {
+        self.children
+            .as_mut()?
+            .iter_mut()
+            .find(|child| child.bounds.contains_rect(rect))
+    }
+
+    fn insert(&mut self, id: ItemId, rect: Rect) {
+        self.largest.width = self.largest.width.max(rect.width());
+        self.largest.height = self.largest.height.max(rect.height());
+        if let Some(child) = self.child_for(&rect) {
+            child.insert(id, rect);
+            return;
+        }
+        self.items.push((id, rect));
+        if self.children.is_none() && self.items.len() > MAX_ITEMS {
+            self.split();
+        }
+    }
+
+    fn split(&mut self) {
+        let b = self.bounds;
+        if self.depth >= MAX_DEPTH || b.width() < 2 || b.height() < 2 {
+            return;
+        }
+        let (w1, h1) = (b.width() / 2, b.height() / 2);
+        let (w2, h2) = (b.width() - w1, b.height() - h1);
+        let (mid_x, mid_y) = (b.left() + i64::from(w1), b.top() + i64::from(h1));
+        let depth = self.depth + 1;
+        self.children = Some(Box::new([
+            Node::new(Rect::new(b.left(), b.top(), w1, h1), depth),
+            Node::new(Rect::new(mid_x, b.top(), w2, h1), depth),
+            Node::new(Rect::new(b.left(), mid_y, w1, h2), depth),
+            Node::new(Rect::new(mid_x, mid_y, w2, h2), depth),
+        ]));
+
+        for (id, rect) in std::mem::take(&mut self.items) {
+            self.insert(id, rect);
+        }
+    }
+
+    /* Follows the same path as 'insert', so finds the node holding the rect. */
+    fn remove(&mut self, id: ItemId, rect: &Rect) -> bool {
+        let removed = match self.child_for(rect) {
+            Some(child) => child.remove(id, rect),
+            None => match self.items.iter().position(|&(item, _)| item == id) {
+                Some(i) => {
+                    self.items.swap_remove(i);
+                    true
+                }
+                None => false,
+            },
+        };
+        if removed {
+            self.update_largest();
+        }
+        removed
+    }
+
+    fn update_largest(&mut self) {
+        let items = self.items.iter().map(|(_, rect)| rect.size);
+        let children = self
+            .children
+            .iter()
+            .flat_map(|c| c.iter().map(|c| c.largest));
+        self.largest = items.chain(children).fold(
+            Rectangle {
+                width: 0,
+                height: 0,
+            },
+            |largest, size| Rectangle {
+                width: largest.width.max(size.width),
+                height: largest.height.max(size.height),
+            },
+        );
+    }
+
+    /* Calls 'f' for each rect in the nodes accepted by 'enter'. The root is
+     * always entered since it may hold rects outside its bounds. */
+    fn visit<E, F>(&self, enter: &E, f: &mut F)
+    where
+        E: Fn(&Node) -> bool,
+        F: FnMut(ItemId, &Rect),
+    {
+        for (id, rect) in &self.items {
+            f(*id, rect);
+        }
+        if let Some(children) = &self.children {
+            for child in children.iter().filter(|child| enter(child)) {
+                child.visit(enter, f);
+            }
+        }
+    }
+}
+
+struct Candidate<'a> {
+    distance: u128,
+    kind: CandidateKind<'a>,
+}
+
+enum CandidateKind<'a> {
+    Node(&'a Node),
+    Item(ItemId),
+}
+
+/* Order by distance. At equal distance, nodes come before items so that a
+ * closer item hidden in a node is never missed, and items by lowest id. */
+impl<'a> Candidate<'a> {
+    fn key(&self) -> (u128, u8, usize) {
+        match self.kind {
+            CandidateKind::Node(_) => (self.distance, 0, 0),
+            CandidateKind::Item(ItemId(id)) => (self.distance, 1, id),
+        }
+    }
+}
+
+impl<'a> PartialEq for Candidate<'a> {
+    fn eq(&self, other: &Candidate<'a>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<'a> Eq for Candidate<'a> {}
+
+impl<'a> PartialOrd for Candidate<'a> {
+    fn partial_cmp(&self, other: &Candidate<'a>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Candidate<'a> {
+    fn cmp(&self, other: &Candidate<'a>) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+fn distance_squared(rect: &Rect, point: Point) -> u128 {
+    let dx = (rect.left() - point.x).max(point.x - rect.right()).max(0);
+    let dy = (rect.top() - point.y).max(point.y - rect.bottom()).max(0);
+    let (dx, dy) = (dx as u128, dy as u128);
+    dx * dx + dy * dy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Rects from a fixed xorshift sequence, some reaching outside the tree's
+     * bounds of 0..1000 on each axis. */
+    fn random_rects(seed: u64, count: usize) -> Vec<Rect> {
+        let mut state = seed;
+        let mut next = move |below: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % below
+        };
+        (0..count)
+            .map(|_| {
+                let x = next(1200) as i64 - 100;
+                let y = next(1200) as i64 - 100;
+                let side = if next(10) == 0 { 400 } else { 40 };
+                Rect::new(x, y, next(side) as u32, next(side) as u32)
+            })
+            .collect()
+    }
+
+    fn tree(rects: &[Rect]) -> (QuadTree, Vec<ItemId>) {
+        let mut tree = QuadTree::new(Rect::new(0, 0, 1000, 1000));
+        let ids = rects.iter().map(|&rect| tree.insert(rect)).collect();
+        (tree, ids)
+    }
+
+    /* The ids of the rects matching 'keep', found by checking every one. */
+    fn scan<F: Fn(&Rect) -> bool>(tree: &QuadTree, keep: F) -> Vec<ItemId> {
+        let mut found: Vec<ItemId> = tree
+            .iter()
+            .filter(|(_, rect)| keep(rect))
+            .map(|(id, _)| id)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    #[test]
+    fn range_queries_match_a_brute_force_scan() {
+        for seed in 1..=10 {
+            let (tree, _) = tree(&random_rects(seed * 104_729, 500));
+            for region in random_rects(seed, 50) {
+                assert_eq!(
+                    tree.overlapping(&region),
+                    scan(&tree, |rect| rect.overlaps(&region)),
+                    "overlapping {:?}",
+                    region
+                );
+                let point = region.origin;
+                assert_eq!(
+                    tree.containing_point(point),
+                    scan(&tree, |rect| rect.contains_point(point)),
+                    "containing {:?}",
+                    point
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_matches_a_brute_force_scan() {
+        let (tree, _) = tree(&random_rects(7, 300));
+        for probe in random_rects(8, 100) {
+            let point = probe.origin;
+            let expected = tree
+                .iter()
+                .map(|(id, rect)| (distance_squared(&rect, point), id))
+                .min()
+                .map(|(distance, id)| (id, distance));
+            assert_eq!(tree.nearest(point), expected, "nearest {:?}", point);
+        }
+        assert_eq!(
+            QuadTree::new(Rect::new(0, 0, 10, 10)).nearest(Point::origin()),
+            None
+        );
+    }
+
+    #[test]
+    fn removed_rects_are_no_longer_found() {
+        let rects = random_rects(3, 400);
+        let (mut tree, ids) = tree(&rects);
+        for (i, (&id, rect)) in ids.iter().zip(&rects).enumerate() {
+            if i % 3 == 0 {
+                assert_eq!(tree.remove(id), Some(*rect));
+                assert_eq!(tree.remove(id), None);
+            }
+        }
+        assert_eq!(tree.len(), rects.len() - rects.len().div_ceil(3));
+        let everything = Rect::new(-1000, -1000, 4000, 4000);
+        assert_eq!(
+            tree.overlapping(&everything),
+            scan(&tree, |rect| rect.overlaps(&everything))
+        );
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(tree.get(id).is_some(), i % 3 != 0);
+        }
+    }
+
+    #[test]
+    fn can_hold_finds_rects_outside_the_bounds() {
+        let (tree, ids) = tree(&[
+            Rect::new(0, 0, 10, 10),
+            Rect::new(500, 500, 20, 5),
+            Rect::new(-50, 2000, 30, 30),
+        ]);
+        let size = Rectangle {
+            width: 10,
+            height: 5,
+        };
+        assert_eq!(tree.can_hold(&size, FitMode::Inclusive), ids);
+        assert_eq!(tree.can_hold(&size, FitMode::Strict), [ids[2]]);
+    }
+
+    #[test]
+    fn can_hold_matches_a_brute_force_scan() {
+        let rects = random_rects(11, 500);
+        let (mut tree, ids) = tree(&rects);
+        let check = |tree: &QuadTree| {
+            for probe in random_rects(12, 100) {
+                for &mode in &[FitMode::Strict, FitMode::Inclusive] {
+                    assert_eq!(
+                        tree.can_hold(&probe.size, mode),
+                        scan(tree, |rect| rect.size.holds(&probe.size, mode)),
+                        "can hold {} {:?}",
+                        probe.size,
+                        mode
+                    );
+                }
+            }
+        };
+        check(&tree);
+        /* Removing the big rects shrinks the sizes kept in each node. */
+        for (&id, rect) in ids.iter().zip(&rects) {
+            if rect.width() >= 40 || rect.height() >= 40 {
+                tree.remove(id);
+            }
+        }
+        assert!(tree.root.largest.width < 40 && tree.root.largest.height < 40);
+        check(&tree);
+    }
+}
@@ -0,0 +1,327 @@
+/* SVG rendering.
+ *
+ * Printing '{:?}' of a packing or of nested rectangles is hard to check by
+ * eye. These functions draw them as SVG documents instead, which any web
+ * browser can show. The container is drawn in grey and each rectangle inside
+ * it in its own color, labelled with its dimensions and area.
+ *
+ * Drawings are scaled so that the container is at most 'max_size' pixels on
+ * its longest side. Labels are left out of rectangles too small to hold
+ * them. */
+
+use std::fmt::Write;
+
+use crate::geometry::{self, Point, Rect};
+use crate::packing::Packing;
+use crate::Rectangle;
+
+const CONTAINER_FILL: &str = "#eeeeee";
+const CONTAINER_STROKE: &str = "#333333";
+const REJECTED_STROKE: &str = "#d62728";
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf", "#bcbd22",
+];
+const FONT_SIZE: f64 = 12.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgOptions {
+    pub max_size: f64,
+    pub margin: f64,
+    pub labels: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            max_size: 800.0,
+            margin: 20.0,
+            labels: true,
+        }
+    }
+}
+
+/* Draws rectangles the way 'can_hold' compares them: all of them anchored
+ * at the top-left corner of the container. Rectangles the container cannot
+ * hold are outlined in red and dashed. */
+pub fn render_containment(
+    container: &Rectangle,
+    contents: &[Rectangle],
+    options: &SvgOptions,
+) -> String {
+    let frame = Rect::at(Point::origin(), *container);
+    let mut canvas = Canvas::new(
+        Some(&frame),
+        contents.iter().map(|r| Rect::at(Point::origin(), *r)),
+        options,
+    );
+    canvas.container(&frame);
+    /* Largest first, so smaller rectangles stay visible on top. */
+    let mut order: Vec<usize> = (0..contents.len()).collect();
+    order.sort_by_key(|&i| {
+        std::cmp::Reverse(u64::from(contents[i].width) * u64::from(contents[i].height))
+    });
+    for i in order {
+        let rect = Rect::at(Point::origin(), contents[i]);
+        canvas.item(&rect, i, !container.can_hold(&contents[i]));
+    }
+    canvas.finish()
+}
+
+/* Draws a layout produced by 'packing::pack'. Unplaced rectangles are not
+ * shown; they are listed below the container. */
+pub fn render_packing(packing: &Packing, options: &SvgOptions) -> String {
+    let frame = Rect::at(Point::origin(), packing.container);
+    let mut canvas = Canvas::new(
+        Some(&frame),
+        packing.placements.iter().map(|p| p.rect),
+        options,
+    );
+    canvas.container(&frame);
+    for p in &packing.placements {
+        canvas.item(&p.rect, p.index, false);
+    }
+    let mut caption = format!("{:.1}% used", packing.utilization() * 100.0);
+    if !packing.unplaced.is_empty() {
+        write!(caption, ", unplaced: {:?}", packing.unplaced).unwrap();
+    }
+    canvas.caption(&caption);
+    canvas.finish()
+}
+
+/* Draws positioned rects with no container, e.g. the contents of a
+ * spatial index. */
+pub fn render_rects(rects: &[Rect], options: &SvgOptions) -> String {
+    let mut canvas = Canvas::new(None, rects.iter().copied(), options);
+    for (i, rect) in rects.iter().enumerate() {
+        canvas.item(rect, i, false);
+    }
+    canvas.finish()
+}
+
+struct Canvas {
+    /* Top-left corner of the drawing in rect coordinates. */
+    origin: Point,
+    scale: f64,
+    options: SvgOptions,
+    width: f64,
+    height: f64,
+    body: String,
+}
+
+impl Canvas {
+    /* Sizes the canvas to show 'frame' and every rect in 'extra', scaled by
+     * the longest side of 'frame', or of everything when there is no frame.
+     * The size comes from 'geometry::extent' rather than 'Rect::union' so
+     * that rects too far apart for one Rect can still be drawn. */
+    fn new<I>(frame: Option<&Rect>, extra: I, options: &SvgOptions) -> Canvas
+    where
+        I: Iterator<Item = Rect>,
+    {
+        let rects: Vec<Rect> = frame.copied().into_iter().chain(extra).collect();
+        let (left, top, right, bottom) = geometry::extent(&rects).unwrap_or((0, 0, 0, 0));
+        let (width, height) = ((right - left) as f64, (bottom - top) as f64);
+        let longest = match frame {
+            Some(frame) => f64::from(frame.width().max(frame.height())),
+            None => width.max(height),
+        };
+        let scale = options.max_size / longest.max(1.0);
+        Canvas {
+            origin: Point::new(left, top),
+            scale,
+            options: *options,
+            width: width * scale + 2.0 * options.margin,
+            height: height * scale + 2.0 * options.margin,
+            body: String::new(),
+        }
+    }
+
+    fn x(&self, x: i64) -> f64 {
+        (x - self.origin.x) as f64 * self.scale + self.options.margin
+    }
+
+    fn y(&self, y: i64) -> f64 {
+        (y - self.origin.y) as f64 * self.scale + self.options.margin
+    }
+
+    fn container(&mut self, rect: &Rect) {
+        self.rect(rect, CONTAINER_FILL, 1.0, CONTAINER_STROKE, false);
+        self.label(rect, CONTAINER_STROKE, true);
+    }
+
+    fn item(&mut self, rect: &Rect, index: usize, rejected: bool) {
+        let color = PALETTE[index % PALETTE.len()];
+        let stroke = if rejected { REJECTED_STROKE } else { color };
+        self.rect(rect, color, 0.35, stroke, rejected);
+        self.label(rect, "#000000", false);
+    }
+
+    fn rect(&mut self, rect: &Rect, fill: &str, opacity: f64, stroke: &str, dashed: bool) {
+        writeln!(
+            self.body,
+            r#"  <rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" fill-opacity="{}" stroke="{}" stroke-width="1.5"{}/>"#,
+            self.x(rect.left()),
+            self.y(rect.top()),
+            f64::from(rect.width()) * self.scale,
+            f64::from(rect.height()) * self.scale,
+            fill,
+            opacity,
+            stroke,
+            if dashed { r#" stroke-dasharray="6 3""# } else { "" }
+        )
+        .unwrap();
+    }
+
+    /* "50x30 (1500)" in the middle of the rect, or just inside its top edge
+     * for the container so it does not collide with the contents. */
+    fn label(&mut self, rect: &Rect, color: &str, at_top: bool) {
+        let text = format!("{} ({})", rect.size, rect.area());
+        let width = f64::from(rect.width()) * self.scale;
+        let height = f64::from(rect.height()) * self.scale;
+        let text_width = text.len() as f64 * FONT_SIZE * 0.6;
+        if !self.options.labels || width < text_width || height < FONT_SIZE * 1.5 {
+            return;
+        }
+        let x = self.x(rect.left()) + width / 2.0;
+        let y = if at_top {
+            self.y(rect.top()) + FONT_SIZE * 1.2
+        } else {
+            self.y(rect.top()) + height / 2.0 + FONT_SIZE / 3.0
+        };
+        writeln!(
+            self.body,
+            r#"  <text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="{}" text-anchor="middle" fill="{}">{}</text>"#,
+            x,
+            y,
+            FONT_SIZE,
+            color,
+            escape(&text)
+        )
+        .unwrap();
+    }
+
+    /* A line of text below the drawing. */
+    fn caption(&mut self, text: &str) {
+        let y = self.height + FONT_SIZE;
+        self.height += FONT_SIZE * 2.0;
+        writeln!(
+            self.body,
+            r#"  <text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="{}" fill="{}">{}</text>"#,
+            self.options.margin,
+            y,
+            FONT_SIZE,
+            CONTAINER_STROKE,
+            escape(text)
+        )
+        .unwrap();
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.2} {h:.2}\">\n{body}</svg>\n",
+            w = self.width,
+            h = self.height,
+            body = self.body
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packing::{self, PackOptions};
+
+    fn rectangle(width: u32, height: u32) -> Rectangle {
+        Rectangle { width, height }
+    }
+
+    fn options() -> SvgOptions {
+        SvgOptions {
+            max_size: 100.0,
+            margin: 10.0,
+            labels: true,
+        }
+    }
+
+    fn count(svg: &str, tag: &str) -> usize {
+        svg.matches(tag).count()
+    }
+
+    #[test]
+    fn containment_is_scaled_to_the_container() {
+        let contents = [rectangle(40, 20), rectangle(60, 10)];
+        let svg = render_containment(&rectangle(50, 30), &contents, &options());
+        /* 2 pixels per unit; the 60 wide rectangle sticks out past the
+         * container and widens the drawing. */
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"140\" height=\"80\" viewBox=\"0 0 140.00 80.00\">\n"
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(count(&svg, "<rect "), 3);
+        assert!(svg.contains(r#"<rect x="10.00" y="10.00" width="120.00" height="20.00""#));
+        /* Only the rectangle the container cannot hold is dashed. */
+        assert_eq!(count(&svg, "stroke-dasharray"), 1);
+        assert!(svg.contains(">50x30 (1500)</text>"));
+        assert!(svg.contains(">40x20 (800)</text>"));
+    }
+
+    #[test]
+    fn one_rect_per_placement() {
+        let items = [rectangle(10, 10), rectangle(20, 5), rectangle(100, 100)];
+        let layout = packing::pack(rectangle(40, 20), &items, PackOptions::default());
+        let svg = render_packing(&layout, &options());
+        assert_eq!(count(&svg, "<rect "), 1 + layout.placements.len());
+        assert!(svg.contains(", unplaced: [2]</text>"));
+        /* The caption adds a line below the drawing. */
+        assert!(svg.contains("viewBox=\"0 0 120.00 94.00\""));
+    }
+
+    #[test]
+    fn rects_far_apart_are_drawn() {
+        let rects = [Rect::new(0, 0, 1, 1), Rect::new(5_000_000_000, 0, 1, 1)];
+        let svg = render_rects(&rects, &options());
+        assert_eq!(count(&svg, "<rect "), 2);
+        assert!(svg.contains("viewBox=\"0 0 120.00 20.00\""));
+        assert_eq!(
+            render_rects(&[], &options()),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"20\" viewBox=\"0 0 20.00 20.00\">\n</svg>\n"
+        );
+    }
+
+    #[test]
+    fn labels_can_be_left_out() {
+        let options = SvgOptions {
+            labels: false,
+            ..options()
+        };
+        let svg = render_containment(&rectangle(50, 30), &[rectangle(40, 20)], &options);
+        assert_eq!(count(&svg, "<text"), 0);
+        /* Too small to hold its label. */
+        let svg = render_containment(
+            &rectangle(500, 500),
+            &[rectangle(1, 1)],
+            &SvgOptions::default(),
+        );
+        assert!(!svg.contains(">1x1 (1)</text>"));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            escape(r#"<a href="x">&amp;</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;amp;&lt;/a&gt;"
+        );
+        assert_eq!(escape("50x30 (1500)"), "50x30 (1500)");
+        let mut canvas = Canvas::new(None, std::iter::empty(), &options());
+        canvas.caption("a < b & \"c\"");
+        assert!(canvas
+            .finish()
+            .contains(">a &lt; b &amp; &quot;c&quot;</text>"));
+    }
+}
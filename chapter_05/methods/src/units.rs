@@ -0,0 +1,198 @@
+/* Rectangles with units.
+ *
+ * The struct_example crate prints areas in "square pixels", but nothing in
+ * the types says so: a Rectangle of 50x30 could be pixels, millimeters or
+ * inches. 'Size<U>' wraps an f64 Rectangle and records its unit in the type,
+ * so a Size<Millimeters> cannot be mixed up with a Size<Inches> by accident,
+ * and converting between them is explicit.
+ *
+ * Pixels are converted to physical units at the CSS reference density of 96
+ * pixels per inch. Use 'to_inches_at' and 'Size::from_inches_at' for other
+ * screen densities. */
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::Rectangle;
+
+pub trait Unit {
+    /* Short name used when printing, such as "mm". */
+    const SYMBOL: &'static str;
+    /* How many of this unit make up one inch. */
+    const PER_INCH: f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pixels;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Millimeters;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inches;
+
+impl Unit for Pixels {
+    const SYMBOL: &'static str = "px";
+    const PER_INCH: f64 = 96.0;
+}
+
+impl Unit for Millimeters {
+    const SYMBOL: &'static str = "mm";
+    const PER_INCH: f64 = 25.4;
+}
+
+impl Unit for Inches {
+    const SYMBOL: &'static str = "in";
+    const PER_INCH: f64 = 1.0;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Size<U: Unit> {
+    pub rect: Rectangle<f64>,
+    unit: PhantomData<U>,
+}
+
+impl<U: Unit> Size<U> {
+    pub fn new(width: f64, height: f64) -> Size<U> {
+        Size {
+            rect: Rectangle { width, height },
+            unit: PhantomData,
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.rect.width
+    }
+
+    pub fn height(&self) -> f64 {
+        self.rect.height
+    }
+
+    /* The area in square units of U. */
+    pub fn area(&self) -> f64 {
+        self.rect.area()
+    }
+
+    pub fn perimeter(&self) -> f64 {
+        self.rect.perimeter()
+    }
+
+    pub fn can_hold(&self, other: &Size<U>) -> bool {
+        self.rect.can_hold(&other.rect)
+    }
+
+    /* Converts to another unit, e.g. 'size.convert::<Inches>()'. */
+    pub fn convert<V: Unit>(&self) -> Size<V> {
+        let factor = V::PER_INCH / U::PER_INCH;
+        Size::new(self.rect.width * factor, self.rect.height * factor)
+    }
+}
+
+impl Size<Pixels> {
+    /* Converts to inches for a screen with the given pixels per inch. */
+    pub fn to_inches_at(&self, dpi: f64) -> Size<Inches> {
+        Size::new(self.rect.width / dpi, self.rect.height / dpi)
+    }
+
+    pub fn from_inches_at(inches: &Size<Inches>, dpi: f64) -> Size<Pixels> {
+        Size::new(inches.rect.width * dpi, inches.rect.height * dpi)
+    }
+}
+
+/* Any integer Rectangle can be given a unit. */
+impl<U: Unit> From<Rectangle<u32>> for Size<U> {
+    fn from(rect: Rectangle<u32>) -> Size<U> {
+        Size::new(f64::from(rect.width), f64::from(rect.height))
+    }
+}
+
+/* Honors a precision, so '{:.1}' prints "13.2 x 7.9 mm". */
+impl<U: Unit> fmt::Display for Size<U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(p) => write!(
+                f,
+                "{:.*} x {:.*} {}",
+                p,
+                self.rect.width,
+                p,
+                self.rect.height,
+                U::SYMBOL
+            ),
+            None => write!(
+                f,
+                "{} x {} {}",
+                self.rect.width,
+                self.rect.height,
+                U::SYMBOL
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn one_inch_in_each_unit() {
+        let inch: Size<Inches> = Size::new(1.0, 2.0);
+        assert_eq!(inch.convert::<Pixels>(), Size::new(96.0, 192.0));
+        assert_eq!(inch.convert::<Millimeters>(), Size::new(25.4, 50.8));
+        assert_eq!(inch.convert::<Inches>(), inch);
+
+        let a4: Size<Millimeters> = Size::new(210.0, 297.0);
+        let pixels = a4.convert::<Pixels>();
+        assert!(close(pixels.width(), 793.700_787_401_574_8));
+        assert!(close(pixels.height(), 1_122.519_685_039_370_1));
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let size: Size<Pixels> = Size::new(1_920.0, 1_080.0);
+        let back = size
+            .convert::<Millimeters>()
+            .convert::<Inches>()
+            .convert::<Pixels>();
+        assert!(close(back.width(), size.width()) && close(back.height(), size.height()));
+
+        let inches = size.to_inches_at(160.0);
+        assert_eq!(inches, Size::new(12.0, 6.75));
+        assert_eq!(Size::from_inches_at(&inches, 160.0), size);
+    }
+
+    #[test]
+    fn area_is_in_square_units() {
+        let size: Size<Inches> = Size::new(2.0, 3.0);
+        assert_eq!(size.area(), 6.0);
+        assert_eq!(size.perimeter(), 10.0);
+        /* Converting scales the area by the square of the factor. */
+        assert!(close(
+            size.convert::<Millimeters>().area(),
+            6.0 * 25.4 * 25.4
+        ));
+        assert!(size.can_hold(&Size::new(1.0, 2.9)));
+        assert!(!size.can_hold(&Size::new(2.0, 1.0)));
+    }
+
+    #[test]
+    fn display_rounds_to_the_precision_given() {
+        let size: Size<Pixels> = Size::from(Rectangle {
+            width: 50,
+            height: 30,
+        });
+        assert_eq!(size.to_string(), "50 x 30 px");
+        let mm = size.convert::<Millimeters>();
+        assert_eq!(format!("{:.1}", mm), "13.2 x 7.9 mm");
+        assert_eq!(format!("{:.0}", mm), "13 x 8 mm");
+        assert_eq!(format!("{:.3}", mm.convert::<Inches>()), "0.521 x 0.312 in");
+        assert_eq!(
+            format!("{:.2}", Size::<Inches>::new(0.125, 0.375)),
+            "0.12 x 0.38 in"
+        );
+    }
+}
@@ -0,0 +1,785 @@
+/* A record of every change made to users.
+ *
+ * Each change to a user appends an event to an 'AuditLog': who made it,
+ * when, and the old and new value of each field that changed. Events are
+ * never edited or removed, so the log can answer what a user looked like at
+ * any moment in the past, by replaying their events up to that moment.
+ *
+ * 'AuditedStore' wraps a UserStore and logs everything done through it. A
+ * change made directly to a User, like 'user1.email = ...' in main.rs, can
+ * be logged with 'AuditLog::record_change' by passing the user from before
+ * and after.
+ *
+ * A log opened from a file appends its events to it as JSON lines, one per
+ * event, when 'flush' is called. Nothing is written before then, so a caller
+ * that saves users elsewhere can save them first and flush only once that
+ * has worked; otherwise the log could describe changes that were never
+ * saved. Events not flushed before the log is dropped are lost. Password
+ * hashes are never logged, only whether a password is set, so a
+ * user rebuilt from the log has no password. */
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::json::{self, Value};
+use crate::role::UnknownRole;
+use crate::session::{Clock, SessionToken, Sessions, SystemClock};
+use crate::store::{StoreError, UserStore};
+use crate::{Credentials, Email, PasswordHash, Role, User, Username};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Username,
+    Email,
+    Active,
+    SignInCount,
+    Password,
+    FailedAttempts,
+    Roles,
+}
+
+const FIELDS: [Field; 7] = [
+    Field::Username,
+    Field::Email,
+    Field::Active,
+    Field::SignInCount,
+    Field::Password,
+    Field::FailedAttempts,
+    Field::Roles,
+];
+
+impl Field {
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Username => "username",
+            Field::Email => "email",
+            Field::Active => "active",
+            Field::SignInCount => "sign_in_count",
+            Field::Password => "password",
+            Field::FailedAttempts => "failed_attempts",
+            Field::Roles => "roles",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Field> {
+        FIELDS.iter().copied().find(|field| field.name() == name)
+    }
+
+    /* The field's value as it is logged. */
+    fn value_of(self, user: &User) -> String {
+        match self {
+            Field::Username => user.username.to_string(),
+            Field::Email => user.email.to_string(),
+            Field::Active => user.active.to_string(),
+            Field::SignInCount => user.sign_in_count.to_string(),
+            Field::Password => match user.credentials.password {
+                Some(_) => String::from("set"),
+                None => String::from("not set"),
+            },
+            Field::FailedAttempts => user.credentials.failed_attempts.to_string(),
+            Field::Roles => user.roles.to_string(),
+        }
+    }
+
+    /* Sets the field from a logged value. Passwords are not logged, so they
+     * are left alone. */
+    fn apply(self, user: &mut User, value: &str) -> Option<()> {
+        match self {
+            Field::Username => user.username = Username::parse(value).ok()?,
+            Field::Email => user.email = Email::parse(value).ok()?,
+            Field::Active => user.active = value.parse().ok()?,
+            Field::SignInCount => user.sign_in_count = value.parse().ok()?,
+            Field::Password => {}
+            Field::FailedAttempts => user.credentials.failed_attempts = value.parse().ok()?,
+            Field::Roles => user.roles = value.parse().ok()?,
+        }
+        Some(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /* The user as first created, without a password. */
+    Created(User),
+    Updated {
+        field: Field,
+        old: String,
+        new: String,
+    },
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /* Position in the log, starting at 1. */
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+    pub actor: String,
+    /* The username before the change. */
+    pub username: String,
+    pub change: Change,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} by {}: ",
+            self.sequence, self.username, self.actor
+        )?;
+        match &self.change {
+            Change::Created(user) => write!(f, "created with email {}", user.email),
+            Change::Updated { field, old, new } => {
+                write!(f, "{} changed from {:?} to {:?}", field.name(), old, new)
+            }
+            Change::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    Store(StoreError),
+    /* The log file could not be read or written. */
+    Io(io::Error),
+    /* A line of a log file that cannot be read back. */
+    Corrupt { line: usize, message: String },
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditError::Store(e) => write!(f, "{}", e),
+            AuditError::Io(e) => write!(f, "cannot write audit log: {}", e),
+            AuditError::Corrupt { line, message } => {
+                write!(f, "audit log line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl Error for AuditError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AuditError::Store(e) => Some(e),
+            AuditError::Io(e) => Some(e),
+            AuditError::Corrupt { .. } => None,
+        }
+    }
+}
+
+impl From<StoreError> for AuditError {
+    fn from(e: StoreError) -> AuditError {
+        AuditError::Store(e)
+    }
+}
+
+impl From<io::Error> for AuditError {
+    fn from(e: io::Error) -> AuditError {
+        AuditError::Io(e)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    events: Vec<Event>,
+    file: Option<File>,
+    /* How many of 'events' are in the file. */
+    written: usize,
+}
+
+impl AuditLog {
+    /* A log kept only in memory. */
+    pub fn new() -> AuditLog {
+        AuditLog::default()
+    }
+
+    /* Reads the events already in the file, creating it if needed. New
+     * events are appended to it by 'flush'. */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<AuditLog, AuditError> {
+        let path = path.as_ref();
+        let events = match fs::read_to_string(path) {
+            Ok(text) => from_json_lines(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog {
+            written: events.len(),
+            events,
+            file: Some(file),
+        })
+    }
+
+    /* Appends the events recorded since the last flush to the file, if the
+     * log has one. */
+    pub fn flush(&mut self) -> Result<(), AuditError> {
+        if let Some(file) = &mut self.file {
+            let mut lines = String::new();
+            for event in &self.events[self.written..] {
+                lines.push_str(&encode_event(event).to_json());
+                lines.push('\n');
+            }
+            file.write_all(lines.as_bytes())?;
+            file.flush()?;
+        }
+        self.written = self.events.len();
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /* Logs the difference between a user before and after a change: None
+     * before means the user was created, None after that it was removed.
+     * Nothing is logged if nothing changed. */
+    pub fn record_change(
+        &mut self,
+        timestamp: SystemTime,
+        actor: &str,
+        before: Option<&User>,
+        after: Option<&User>,
+    ) {
+        let changes = match (before, after) {
+            (None, Some(after)) => {
+                let mut created = after.clone();
+                created.credentials.password = None;
+                vec![(after.username.to_string(), Change::Created(created))]
+            }
+            (Some(before), None) => vec![(before.username.to_string(), Change::Removed)],
+            (Some(before), Some(after)) => FIELDS
+                .iter()
+                .map(|&field| (field, field.value_of(before), field.value_of(after)))
+                .filter(|(_, old, new)| old != new)
+                .map(|(field, old, new)| {
+                    (
+                        before.username.to_string(),
+                        Change::Updated { field, old, new },
+                    )
+                })
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+        for (username, change) in changes {
+            let event = Event {
+                sequence: self.events.len() as u64 + 1,
+                timestamp,
+                actor: actor.to_string(),
+                username,
+                change,
+            };
+            self.events.push(event);
+        }
+    }
+
+    /* Every event about the user now called 'username', oldest first,
+     * including those from before any renames. */
+    pub fn history(&self, username: &str) -> Vec<&Event> {
+        let mut name = Username::normalize(username);
+        let mut history = Vec::new();
+        for event in self.events.iter().rev() {
+            if let Change::Updated {
+                field: Field::Username,
+                old,
+                new,
+            } = &event.change
+            {
+                if *new == name {
+                    name = old.clone();
+                    history.push(event);
+                    continue;
+                }
+            }
+            if event.username == name {
+                history.push(event);
+                /* An earlier user of the same name is someone else. */
+                if let Change::Created(_) = event.change {
+                    break;
+                }
+            }
+        }
+        history.reverse();
+        history
+    }
+
+    /* The user now called 'username' as they were at 'at', rebuilt from
+     * the log. None if they did not exist then. */
+    pub fn state_at(&self, username: &str, at: SystemTime) -> Option<User> {
+        let mut user = None;
+        for event in self.history(username) {
+            if event.timestamp > at {
+                break;
+            }
+            match &event.change {
+                Change::Created(created) => user = Some(created.clone()),
+                Change::Updated { field, new, .. } => {
+                    field.apply(user.as_mut()?, new)?;
+                }
+                Change::Removed => user = None,
+            }
+        }
+        user
+    }
+
+    pub fn by_actor<'a>(&'a self, actor: &'a str) -> impl Iterator<Item = &'a Event> + 'a {
+        self.events.iter().filter(move |event| event.actor == actor)
+    }
+
+    /* Events from 'from' up to but not including 'to'. */
+    pub fn between(&self, from: SystemTime, to: SystemTime) -> impl Iterator<Item = &Event> {
+        self.events
+            .iter()
+            .filter(move |event| event.timestamp >= from && event.timestamp < to)
+    }
+}
+
+/* A UserStore whose every change is logged. Each method takes the name of
+ * whoever is making the change. */
+#[derive(Debug)]
+pub struct AuditedStore<C: Clock = SystemClock> {
+    store: UserStore,
+    log: AuditLog,
+    clock: C,
+}
+
+impl AuditedStore<SystemClock> {
+    pub fn new(store: UserStore, log: AuditLog) -> AuditedStore<SystemClock> {
+        AuditedStore::with_clock(store, log, SystemClock)
+    }
+}
+
+impl<C: Clock> AuditedStore<C> {
+    pub fn with_clock(store: UserStore, log: AuditLog, clock: C) -> AuditedStore<C> {
+        AuditedStore { store, log, clock }
+    }
+
+    pub fn store(&self) -> &UserStore {
+        &self.store
+    }
+
+    pub fn log(&self) -> &AuditLog {
+        &self.log
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn into_parts(self) -> (UserStore, AuditLog) {
+        (self.store, self.log)
+    }
+
+    pub fn create(&mut self, actor: &str, user: User) -> Result<(), AuditError> {
+        let username = user.username.to_string();
+        self.audited(actor, &username, &username, |store| {
+            store.create(user).map(|_| ())
+        })
+    }
+
+    pub fn update_email(
+        &mut self,
+        actor: &str,
+        username: &str,
+        email: Email,
+    ) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.update_email(username, email).map(|_| ())
+        })
+    }
+
+    pub fn rename(
+        &mut self,
+        actor: &str,
+        username: &str,
+        new_username: Username,
+    ) -> Result<(), AuditError> {
+        let new_name = new_username.to_string();
+        self.audited(actor, username, &new_name, |store| {
+            store.rename(username, new_username).map(|_| ())
+        })
+    }
+
+    pub fn activate(&mut self, actor: &str, username: &str) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.activate(username).map(|_| ())
+        })
+    }
+
+    pub fn deactivate(&mut self, actor: &str, username: &str) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.deactivate(username).map(|_| ())
+        })
+    }
+
+    pub fn grant(&mut self, actor: &str, username: &str, role: Role) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.grant(username, role).map(|_| ())
+        })
+    }
+
+    pub fn revoke(&mut self, actor: &str, username: &str, role: Role) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.revoke(username, role).map(|_| ())
+        })
+    }
+
+    pub fn set_password(
+        &mut self,
+        actor: &str,
+        username: &str,
+        password: PasswordHash,
+    ) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.set_password(username, password).map(|_| ())
+        })
+    }
+
+    pub fn unlock(&mut self, actor: &str, username: &str) -> Result<(), AuditError> {
+        self.audited(actor, username, username, |store| {
+            store.unlock(username).map(|_| ())
+        })
+    }
+
+    /* Signing in changes the sign-in count, or on a wrong password the
+     * failed attempts, so both are logged with the user as the actor. */
+    pub fn sign_in(&mut self, username: &str, password: &str) -> Result<u64, AuditError> {
+        self.audited(username, username, username, |store| {
+            store.sign_in(username, password)
+        })
+    }
+
+    /* Signs in as 'sign_in' does and starts a session, the audited
+     * counterpart of 'Sessions::sign_in'. */
+    pub fn start_session<S: Clock>(
+        &mut self,
+        sessions: &mut Sessions<S>,
+        username: &str,
+        password: &str,
+    ) -> Result<SessionToken, AuditError> {
+        self.sign_in(username, password)?;
+        Ok(sessions.start(&self.store, username))
+    }
+
+    pub fn remove(&mut self, actor: &str, username: &str) -> Result<User, AuditError> {
+        self.audited(actor, username, username, |store| store.remove(username))
+    }
+
+    /* Runs 'change' on the store and logs how the user differs afterwards,
+     * even when 'change' fails, since a failed sign-in still counts. */
+    fn audited<T, F>(
+        &mut self,
+        actor: &str,
+        username: &str,
+        new_username: &str,
+        change: F,
+    ) -> Result<T, AuditError>
+    where
+        F: FnOnce(&mut UserStore) -> Result<T, StoreError>,
+    {
+        let before = self.store.get(username).cloned();
+        let result = change(&mut self.store);
+        /* A failed rename leaves the user under the old name. */
+        let after = self.store.get(if result.is_ok() {
+            new_username
+        } else {
+            username
+        });
+        self.log
+            .record_change(self.clock.now(), actor, before.as_ref(), after);
+        Ok(result?)
+    }
+}
+
+fn encode_event(event: &Event) -> Value {
+    let nanos = event
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut fields = vec![
+        ("sequence".to_string(), Value::number(event.sequence)),
+        ("time_ns".to_string(), Value::number(nanos)),
+        ("actor".to_string(), Value::string(event.actor.as_str())),
+        (
+            "username".to_string(),
+            Value::string(event.username.as_str()),
+        ),
+    ];
+    match &event.change {
+        Change::Created(user) => {
+            fields.push(("change".to_string(), Value::string("created")));
+            fields.push(("email".to_string(), Value::string(user.email.as_str())));
+            fields.push(("active".to_string(), Value::Bool(user.active)));
+            fields.push((
+                "sign_in_count".to_string(),
+                Value::number(user.sign_in_count),
+            ));
+            fields.push(("roles".to_string(), Value::string(user.roles.to_string())));
+        }
+        Change::Updated { field, old, new } => {
+            fields.push(("change".to_string(), Value::string("updated")));
+            fields.push(("field".to_string(), Value::string(field.name())));
+            fields.push(("old".to_string(), Value::string(old.as_str())));
+            fields.push(("new".to_string(), Value::string(new.as_str())));
+        }
+        Change::Removed => fields.push(("change".to_string(), Value::string("removed"))),
+    }
+    Value::Object(fields)
+}
+
+fn from_json_lines(text: &str) -> Result<Vec<Event>, AuditError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            decode_event(line).map_err(|message| AuditError::Corrupt {
+                line: i + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+fn decode_event(line: &str) -> Result<Event, String> {
+    let record = json::parse(line)?;
+    let text = |name: &str| {
+        record
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("missing or invalid field {:?}", name))
+    };
+    let number = |name: &str| {
+        record
+            .get(name)
+            .and_then(Value::as_u64)
+            .ok_or_else(|| format!("missing or invalid field {:?}", name))
+    };
+
+    let username = text("username")?;
+    let change = match text("change")?.as_str() {
+        "created" => Change::Created(User {
+            username: Username::parse(&username).map_err(|e| e.to_string())?,
+            email: Email::parse(&text("email")?).map_err(|e| e.to_string())?,
+            active: record
+                .get("active")
+                .and_then(Value::as_bool)
+                .ok_or("missing or invalid field \"active\"")?,
+            sign_in_count: number("sign_in_count")?,
+            credentials: Credentials::default(),
+            roles: text("roles")?
+                .parse()
+                .map_err(|e: UnknownRole| e.to_string())?,
+        }),
+        "updated" => Change::Updated {
+            field: Field::from_name(&text("field")?).ok_or("unknown field")?,
+            old: text("old")?,
+            new: text("new")?,
+        },
+        "removed" => Change::Removed,
+        other => return Err(format!("unknown change {:?}", other)),
+    };
+    Ok(Event {
+        sequence: number("sequence")?,
+        timestamp: UNIX_EPOCH + Duration::from_nanos(number("time_ns")?),
+        actor: text("actor")?,
+        username,
+        change,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_user;
+    use crate::password::MIN_ITERATIONS;
+    use crate::session::{ManualClock, SessionError};
+
+    fn audited() -> AuditedStore<ManualClock> {
+        let clock = ManualClock::new(UNIX_EPOCH);
+        let mut audited = AuditedStore::with_clock(UserStore::new(), AuditLog::new(), clock);
+        let user = build_user("ann@example.com".parse().unwrap(), "ann".parse().unwrap());
+        audited.create("admin", user).unwrap();
+        let hash = PasswordHash::with_iterations("correct horse", MIN_ITERATIONS).unwrap();
+        audited.set_password("admin", "ann", hash).unwrap();
+        audited
+    }
+
+    #[test]
+    fn roles_are_logged() {
+        let mut audited = audited();
+        audited.grant("admin", "ann", Role::Editor).unwrap();
+        audited.revoke("admin", "ann", Role::Viewer).unwrap();
+        let changes: Vec<String> = audited
+            .log()
+            .history("ann")
+            .iter()
+            .skip(2)
+            .map(|event| event.to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "#3 ann by admin: roles changed from \"viewer\" to \"viewer;editor\"",
+                "#4 ann by admin: roles changed from \"viewer;editor\" to \"editor\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn start_session_logs_the_sign_in() {
+        let mut audited = audited();
+        let mut sessions =
+            Sessions::with_clock(ManualClock::new(UNIX_EPOCH), Duration::from_secs(60));
+        let token = audited
+            .start_session(&mut sessions, "ann", "correct horse")
+            .unwrap();
+        assert_eq!(audited.log().by_actor("ann").count(), 1);
+        assert!(sessions.validate(audited.store(), token.as_str()).is_ok());
+
+        assert!(matches!(
+            audited.start_session(&mut sessions, "ann", "wrong horse"),
+            Err(AuditError::Store(StoreError::WrongPassword(_)))
+        ));
+        assert_eq!(audited.log().by_actor("ann").count(), 2);
+        assert_eq!(sessions.len(), 1);
+
+        audited.deactivate("admin", "ann").unwrap();
+        assert_eq!(
+            sessions.validate(audited.store(), token.as_str()),
+            Err(SessionError::Store(StoreError::Inactive("ann".to_string())))
+        );
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn state_at_replays_changes_up_to_then() {
+        let mut audited = audited();
+        audited.clock().advance(Duration::from_secs(10));
+        audited
+            .update_email("admin", "ann", "ann@example.org".parse().unwrap())
+            .unwrap();
+        audited.clock().advance(Duration::from_secs(10));
+        audited.deactivate("admin", "ann").unwrap();
+        audited.clock().advance(Duration::from_secs(10));
+        audited.remove("admin", "ann").unwrap();
+        let log = audited.log();
+
+        let created = log.state_at("ann", at(0)).unwrap();
+        assert_eq!(created.email.as_str(), "ann@example.com");
+        assert!(created.active);
+        /* Passwords are not logged. */
+        assert!(created.credentials.password.is_none());
+
+        let moved = log.state_at("ann", at(15)).unwrap();
+        assert_eq!(moved.email.as_str(), "ann@example.org");
+        assert!(moved.active);
+        /* Changes happen at the moment they are logged. */
+        assert!(!log.state_at("ann", at(20)).unwrap().active);
+        assert_eq!(log.state_at("ann", at(30)), None);
+        assert_eq!(log.state_at("bob", at(30)), None);
+    }
+
+    #[test]
+    fn state_at_is_none_before_the_user_was_created() {
+        let mut audited = audited();
+        audited.clock().advance(Duration::from_secs(5));
+        let bob = build_user("bob@example.com".parse().unwrap(), "bob".parse().unwrap());
+        audited.create("admin", bob).unwrap();
+        assert_eq!(audited.log().state_at("bob", at(4)), None);
+        assert!(audited.log().state_at("bob", at(5)).is_some());
+    }
+
+    #[test]
+    fn history_follows_a_chain_of_renames() {
+        let mut audited = audited();
+        audited.clock().advance(Duration::from_secs(10));
+        audited
+            .rename("admin", "ann", "anna".parse().unwrap())
+            .unwrap();
+        audited
+            .rename("admin", "anna", "anne".parse().unwrap())
+            .unwrap();
+        /* A new user takes the first name; their history is their own. */
+        let newcomer = build_user("new@example.com".parse().unwrap(), "ann".parse().unwrap());
+        audited.create("admin", newcomer).unwrap();
+        let log = audited.log();
+
+        let sequences = |name| -> Vec<u64> {
+            log.history(name)
+                .iter()
+                .map(|event| event.sequence)
+                .collect()
+        };
+        assert_eq!(sequences("anne"), [1, 2, 3, 4]);
+        assert_eq!(sequences("ANNE"), [1, 2, 3, 4]);
+        assert_eq!(sequences("ann"), [5]);
+        assert_eq!(sequences("bob"), Vec::<u64>::new());
+
+        let before = log.state_at("anne", at(0)).unwrap();
+        assert_eq!(before.username.as_str(), "ann");
+        assert_eq!(before.email.as_str(), "ann@example.com");
+        let after = log.state_at("anne", at(10)).unwrap();
+        assert_eq!(after.username.as_str(), "anne");
+    }
+
+    #[test]
+    fn file_log_round_trips_through_open() {
+        let dir = std::env::temp_dir().join(format!("structs-audit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+        let _ = fs::remove_file(&path);
+
+        let log = AuditLog::open(&path).unwrap();
+        let clock = ManualClock::new(at(1_000));
+        let mut audited = AuditedStore::with_clock(UserStore::new(), log, clock);
+        let user = build_user("ann@example.com".parse().unwrap(), "ann".parse().unwrap());
+        audited.create("admin", user).unwrap();
+        audited.clock().advance(Duration::from_millis(1_500));
+        audited.grant("admin", "ann", Role::Admin).unwrap();
+        audited
+            .rename("admin", "ann", "anne".parse().unwrap())
+            .unwrap();
+        let (store, mut log) = audited.into_parts();
+        let unflushed = AuditLog::open(&path).unwrap().len();
+        log.flush().unwrap();
+        let reopened = AuditLog::open(&path).unwrap();
+
+        /* Events recorded after opening again go after the old ones. */
+        let mut audited = AuditedStore::with_clock(store, reopened, ManualClock::new(at(2_000)));
+        audited.deactivate("admin", "anne").unwrap();
+        let (_, mut log) = audited.into_parts();
+        log.flush().unwrap();
+        let all = AuditLog::open(&path).unwrap();
+
+        fs::write(&path, "{\"sequence\":1}\n").unwrap();
+        let corrupt = AuditLog::open(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unflushed, 0);
+        assert_eq!(all.events(), log.events());
+        assert_eq!(all.events()[3].sequence, 4);
+        assert_eq!(
+            all.events()[1].timestamp,
+            at(1_000) + Duration::from_millis(1_500)
+        );
+        assert!(!all.state_at("anne", at(3_000)).unwrap().active);
+        assert!(matches!(corrupt, Err(AuditError::Corrupt { line: 1, .. })));
+    }
+}
@@ -0,0 +1,610 @@
+/* user: manages the users kept in a users file.
+ *
+ *   user add USERNAME EMAIL          creates an active user
+ *   user list                        table of every user
+ *   user show USERNAME               one user in detail
+ *   user activate USERNAME
+ *   user deactivate USERNAME
+ *   user rename USERNAME NEW_NAME
+ *   user grant USERNAME ROLE         gives a user admin, editor or viewer
+ *   user revoke USERNAME ROLE
+ *   user import [FILE]               adds users from CSV or JSON
+ *   user export [FILE]               writes users as CSV or JSON
+ *
+ * Users are kept in the file given with --file, or in $USERS_FILE, or in
+ * users.jsonl in the current directory (see the 'storage' module). The file
+ * is only written when a command succeeds; an import with one bad user adds
+ * none of them.
+ *
+ * Every change goes through an 'AuditedStore' and, once the users file is
+ * saved, is appended to the audit log given with --log, or in $USERS_LOG,
+ * or next to the users file with '.audit.jsonl' in place of its extension.
+ * Changes are logged under the name given with --actor, or $USER. Commands
+ * that only read users leave the log alone.
+ *
+ * Exit status is 0 on success, 1 for invalid or conflicting users and
+ * missing files, and 2 for invalid usage. */
+
+use std::convert::TryInto;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+use structs::audit::{AuditError, AuditLog, AuditedStore};
+use structs::builder::BuildError;
+use structs::format::{self, Format, ImportError};
+use structs::role::UnknownRole;
+use structs::storage::{FileStorage, StorageError};
+use structs::store::{StoreError, UserStore};
+use structs::validation::ValidationError;
+use structs::{Role, Roles, User, UserBuilder};
+
+const DEFAULT_FILE: &str = "users.jsonl";
+const LOG_EXTENSION: &str = "audit.jsonl";
+const DEFAULT_ACTOR: &str = "unknown";
+
+const USAGE: &str = "usage: user [--file FILE] <command> [options] [ARGS...]
+
+commands:
+  add USERNAME EMAIL          create a user
+  list                        list all users
+  show USERNAME               show one user
+  activate USERNAME           allow a user to sign in
+  deactivate USERNAME         stop a user from signing in
+  rename USERNAME NEW_NAME    change a username
+  grant USERNAME ROLE         give a user a role (admin, editor, viewer)
+  revoke USERNAME ROLE        take a role away from a user
+  import [FILE]               add users from FILE, or standard input
+  export [FILE]               write users to FILE, or standard output
+
+options:
+  --file FILE                 users file (default: $USERS_FILE or users.jsonl)
+  --log FILE                  audit log (default: $USERS_LOG, or the users
+                              file with the extension audit.jsonl)
+  --actor NAME                who is making the change (default: $USER)
+  --inactive                  add: create the user inactive
+  --role ROLE                 add: give the user ROLE instead of viewer;
+                              may be repeated
+  --json                      list, show: print JSON instead of a table
+  --format csv|json           import, export: format of the data
+                              (default: from the file extension, else
+                              detected on import and CSV on export)
+  -h, --help                  show this help";
+
+enum CliError {
+    Usage(String),
+    Input(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Input(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Input(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<ValidationError> for CliError {
+    fn from(error: ValidationError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl From<BuildError> for CliError {
+    fn from(error: BuildError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl From<StoreError> for CliError {
+    fn from(error: StoreError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl From<AuditError> for CliError {
+    fn from(error: AuditError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl From<UnknownRole> for CliError {
+    fn from(error: UnknownRole) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+impl From<ImportError> for CliError {
+    fn from(error: ImportError) -> CliError {
+        CliError::Input(error.to_string())
+    }
+}
+
+struct Options {
+    file: String,
+    /* Empty until parsing is done, then the log next to 'file' if no log
+     * was given. */
+    log: String,
+    actor: String,
+    command: String,
+    args: Vec<String>,
+    inactive: bool,
+    roles: Vec<Role>,
+    json: bool,
+    format: Option<Format>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    match parse_args(args).and_then(|options| run(&options)) {
+        Ok(output) => print!("{}", output),
+        Err(error) => {
+            eprintln!("user: {}", error);
+            process::exit(error.exit_code());
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, CliError> {
+    let mut options = Options {
+        file: env::var("USERS_FILE").unwrap_or_else(|_| String::from(DEFAULT_FILE)),
+        log: env::var("USERS_LOG").unwrap_or_default(),
+        actor: env::var("USER").unwrap_or_else(|_| String::from(DEFAULT_ACTOR)),
+        command: String::new(),
+        args: Vec::new(),
+        inactive: false,
+        roles: Vec::new(),
+        json: false,
+        format: None,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => options.file = value_of(&arg, args.next())?,
+            "--log" => options.log = value_of(&arg, args.next())?,
+            "--actor" => options.actor = value_of(&arg, args.next())?,
+            "--inactive" => options.inactive = true,
+            "--role" => options.roles.push(value_of(&arg, args.next())?.parse()?),
+            "--json" => options.json = true,
+            "--format" => {
+                options.format = Some(match value_of(&arg, args.next())?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    other => return Err(usage(format!("unknown format {:?}", other))),
+                })
+            }
+            flag if flag.starts_with("--") => {
+                return Err(usage(format!("unknown option {:?}", flag)))
+            }
+            _ if options.command.is_empty() => options.command = arg,
+            _ => options.args.push(arg),
+        }
+    }
+    if options.command.is_empty() {
+        return Err(usage(String::from("missing command")));
+    }
+    if options.log.is_empty() {
+        options.log = Path::new(&options.file)
+            .with_extension(LOG_EXTENSION)
+            .to_string_lossy()
+            .into_owned();
+    }
+    Ok(options)
+}
+
+fn value_of(flag: &str, value: Option<String>) -> Result<String, CliError> {
+    value.ok_or_else(|| usage(format!("{} needs a value", flag)))
+}
+
+fn usage(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+fn run(options: &Options) -> Result<String, CliError> {
+    let mut storage = FileStorage::new(&options.file);
+    let users = UserStore::load(&storage).map_err(|error| storage_error(options, error))?;
+
+    match options.command.as_str() {
+        "list" => Ok(list(options, &users)),
+        "show" => show(options, &users),
+        "export" => export(options, &users),
+        "add" | "activate" | "deactivate" | "rename" | "grant" | "revoke" | "import" => {
+            let log = AuditLog::open(&options.log)
+                .map_err(|error| CliError::Input(format!("{}: {}", options.log, error)))?;
+            let mut users = AuditedStore::new(users, log);
+            let output = change(options, &mut users)?;
+            /* The log is only written once the users are saved, so that it
+             * never records a change that was lost. */
+            let (users, mut log) = users.into_parts();
+            users
+                .save(&mut storage)
+                .map_err(|error| storage_error(options, error))?;
+            log.flush()
+                .map_err(|error| CliError::Input(format!("{}: {}", options.log, error)))?;
+            Ok(output)
+        }
+        other => Err(usage(format!("unknown command {:?}", other))),
+    }
+}
+
+/* Runs one of the commands that change users. */
+fn change(options: &Options, users: &mut AuditedStore) -> Result<String, CliError> {
+    let actor = options.actor.as_str();
+    match options.command.as_str() {
+        "add" => add(options, users),
+        "activate" => {
+            let [username] = arguments::<1>(options, "USERNAME")?;
+            users.activate(actor, &username)?;
+            Ok(format!("activated {}\n", user(users, &username).username))
+        }
+        "deactivate" => {
+            let [username] = arguments::<1>(options, "USERNAME")?;
+            users.deactivate(actor, &username)?;
+            Ok(format!("deactivated {}\n", user(users, &username).username))
+        }
+        "rename" => {
+            let [old, new] = arguments::<2>(options, "USERNAME NEW_NAME")?;
+            users.rename(actor, &old, new.parse()?)?;
+            Ok(format!(
+                "renamed {} to {}\n",
+                old,
+                user(users, &new).username
+            ))
+        }
+        "grant" => {
+            let [username, role] = arguments::<2>(options, "USERNAME ROLE")?;
+            users.grant(actor, &username, role.parse()?)?;
+            let user = user(users, &username);
+            Ok(format!("{} now has roles {}\n", user.username, user.roles))
+        }
+        "revoke" => {
+            let [username, role] = arguments::<2>(options, "USERNAME ROLE")?;
+            users.revoke(actor, &username, role.parse()?)?;
+            let user = user(users, &username);
+            Ok(format!("{} now has roles {}\n", user.username, user.roles))
+        }
+        "import" => import(options, users),
+        other => unreachable!("{:?} is not a command that changes users", other),
+    }
+}
+
+/* A user that a command has just changed, so is known to exist. */
+fn user<'a>(users: &'a AuditedStore, username: &str) -> &'a User {
+    users.store().get(username).unwrap()
+}
+
+fn storage_error(options: &Options, error: StorageError) -> CliError {
+    CliError::Input(format!("{}: {}", options.file, error))
+}
+
+/* Exactly N positional arguments, described by 'names' in errors. */
+fn arguments<const N: usize>(options: &Options, names: &str) -> Result<[String; N], CliError> {
+    let args: Vec<String> = options.args.clone();
+    let found = args.len();
+    args.try_into().map_err(|_| {
+        usage(format!(
+            "{} expects {}, found {} argument(s)",
+            options.command, names, found
+        ))
+    })
+}
+
+/* At most one optional argument, a file name. */
+fn optional_file(options: &Options) -> Result<Option<&str>, CliError> {
+    match &options.args[..] {
+        [] => Ok(None),
+        [file] => Ok(Some(file)),
+        _ => Err(usage(format!(
+            "{} expects at most one FILE",
+            options.command
+        ))),
+    }
+}
+
+fn add(options: &Options, users: &mut AuditedStore) -> Result<String, CliError> {
+    let [username, email] = arguments::<2>(options, "USERNAME EMAIL")?;
+    let mut builder = UserBuilder::new()
+        .username(username.parse()?)
+        .email(email.parse()?)
+        .active(!options.inactive);
+    if !options.roles.is_empty() {
+        builder = builder.roles(options.roles.iter().copied().collect::<Roles>());
+    }
+    let user = builder.build()?;
+    let username = user.username.to_string();
+    users.create(&options.actor, user)?;
+    let user = self::user(users, &username);
+    Ok(format!("added {} <{}>\n", user.username, user.email))
+}
+
+fn list(options: &Options, users: &UserStore) -> String {
+    let users: Vec<User> = users.iter().cloned().collect();
+    if options.json {
+        return format::to_json(&users);
+    }
+    if users.is_empty() {
+        return String::from("no users\n");
+    }
+    table(&users)
+}
+
+fn show(options: &Options, users: &UserStore) -> Result<String, CliError> {
+    let [username] = arguments::<1>(options, "USERNAME")?;
+    let user = users
+        .get(&username)
+        .ok_or_else(|| CliError::from(StoreError::NotFound(username)))?;
+    if options.json {
+        return Ok(format::to_json(std::slice::from_ref(user)));
+    }
+    Ok(format!(
+        "username:  {}\nemail:     {}\nactive:    {}\nsign-ins:  {}\nroles:     {}\npassword:  {}\nlocked:    {}\n",
+        user.username,
+        user.email,
+        yes_no(user.active),
+        user.sign_in_count,
+        user.roles,
+        if user.credentials.password.is_some() {
+            "set"
+        } else {
+            "not set"
+        },
+        yes_no(user.credentials.is_locked()),
+    ))
+}
+
+fn import(options: &Options, users: &mut AuditedStore) -> Result<String, CliError> {
+    let file = optional_file(options)?;
+    let text = match file {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|error| CliError::Input(format!("cannot read {}: {}", path, error)))?,
+        None => read_stdin()?,
+    };
+    let format = options
+        .format
+        .or_else(|| file.and_then(|path| Format::from_extension(Path::new(path))))
+        .unwrap_or_else(|| Format::detect(&text));
+    let imported = format::import(&text, format)?;
+    let count = imported.len();
+    /* Try the whole import on a copy first, so that a bad user leaves
+     * nothing behind in the audit log either. */
+    let mut trial = users.store().clone();
+    for user in &imported {
+        trial.create(user.clone())?;
+    }
+    for user in imported {
+        users.create(&options.actor, user)?;
+    }
+    Ok(format!("imported {} user(s)\n", count))
+}
+
+fn export(options: &Options, users: &UserStore) -> Result<String, CliError> {
+    let file = optional_file(options)?;
+    let format = options
+        .format
+        .or_else(|| file.and_then(|path| Format::from_extension(Path::new(path))))
+        .unwrap_or(Format::Csv);
+    let users: Vec<User> = users.iter().cloned().collect();
+    let text = format::export(&users, format);
+    match file {
+        Some(path) => {
+            fs::write(path, text)
+                .map_err(|error| CliError::Input(format!("cannot write {}: {}", path, error)))?;
+            Ok(format!("exported {} user(s) to {}\n", users.len(), path))
+        }
+        None => Ok(text),
+    }
+}
+
+fn read_stdin() -> Result<String, CliError> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|error| CliError::Input(format!("cannot read standard input: {}", error)))?;
+    Ok(input)
+}
+
+fn table(users: &[User]) -> String {
+    let header = ["USERNAME", "EMAIL", "ACTIVE", "SIGN-INS", "ROLES"];
+    let rows: Vec<[String; 5]> = users
+        .iter()
+        .map(|user| {
+            [
+                user.username.to_string(),
+                user.email.to_string(),
+                yes_no(user.active).to_string(),
+                user.sign_in_count.to_string(),
+                user.roles.to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    let mut line = |cells: [&str; 5]| {
+        /* Counts are right-aligned, everything else left-aligned. */
+        let text = format!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}  {}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            cells[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        );
+        out.push_str(text.trim_end());
+        out.push('\n');
+    };
+    line(header);
+    for row in &rows {
+        line([&row[0], &row[1], &row[2], &row[3], &row[4]]);
+    }
+    out
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /* A fresh directory for one test's users file and audit log. */
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("structs-user-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run_args(args: &[&str]) -> Result<String, CliError> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect())
+            .and_then(|options| run(&options))
+    }
+
+    /* Runs a command on the users file in 'dir', as "admin". */
+    fn user(dir: &Path, args: &[&str]) -> Result<String, CliError> {
+        let file = dir.join("users.jsonl");
+        let mut all = vec!["--file", file.to_str().unwrap(), "--actor", "admin"];
+        all.extend_from_slice(args);
+        run_args(&all)
+    }
+
+    fn output(result: Result<String, CliError>) -> String {
+        match result {
+            Ok(output) => output,
+            Err(error) => panic!("user failed: {}", error),
+        }
+    }
+
+    fn exit_code(result: Result<String, CliError>) -> i32 {
+        result.err().map_or(0, |error| error.exit_code())
+    }
+
+    fn log_lines(path: &Path) -> usize {
+        fs::read_to_string(path).unwrap_or_default().lines().count()
+    }
+
+    #[test]
+    fn commands_change_and_show_users() {
+        let dir = scratch("commands");
+        let outputs = [
+            user(&dir, &["add", "Ann", "Ann@Example.com"]),
+            user(&dir, &["grant", "ann", "editor"]),
+            user(&dir, &["rename", "ann", "anne"]),
+            user(&dir, &["deactivate", "anne"]),
+            user(&dir, &["list"]),
+            user(&dir, &["export", "--format", "csv"]),
+        ]
+        .map(output);
+        let logged = log_lines(&dir.join("users.audit.jsonl"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(outputs[0], "added ann <ann@example.com>\n");
+        assert_eq!(outputs[1], "ann now has roles viewer;editor\n");
+        assert_eq!(outputs[2], "renamed ann to anne\n");
+        assert_eq!(outputs[3], "deactivated anne\n");
+        assert_eq!(
+            outputs[4],
+            "USERNAME  EMAIL            ACTIVE  SIGN-INS  ROLES\n\
+             anne      ann@example.com  no             0  viewer;editor\n"
+        );
+        assert_eq!(
+            outputs[5],
+            "username,email,active,sign_in_count,roles\n\
+             anne,ann@example.com,false,0,viewer;editor\n"
+        );
+        /* Created, roles, username and active. */
+        assert_eq!(logged, 4);
+    }
+
+    #[test]
+    fn errors_set_the_exit_code() {
+        let dir = scratch("errors");
+        output(user(&dir, &["add", "ann", "ann@example.com"]));
+        let codes = [
+            exit_code(run_args(&[])),
+            exit_code(user(&dir, &["frobnicate"])),
+            exit_code(user(&dir, &["add", "bob"])),
+            exit_code(user(&dir, &["list", "--bogus"])),
+            exit_code(user(&dir, &["add", "ann", "other@example.com"])),
+            exit_code(user(&dir, &["add", "bob", "not an email"])),
+            exit_code(user(&dir, &["show", "nobody"])),
+            exit_code(user(&dir, &["grant", "ann", "owner"])),
+            exit_code(user(&dir, &["show", "ann"])),
+        ];
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(codes, [2, 2, 2, 2, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn failed_import_adds_and_logs_nobody() {
+        let dir = scratch("import");
+        let csv = dir.join("new.csv");
+        fs::write(
+            &csv,
+            "username,email\nbob,bob@example.com\nbob,bob@example.org\n",
+        )
+        .unwrap();
+        let result = user(&dir, &["import", csv.to_str().unwrap()]);
+        let listed = output(user(&dir, &["list"]));
+        let logged = log_lines(&dir.join("users.audit.jsonl"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exit_code(result), 1);
+        assert_eq!(listed, "no users\n");
+        assert_eq!(logged, 0);
+    }
+
+    #[test]
+    fn nothing_is_logged_when_saving_fails() {
+        let dir = scratch("unsaved");
+        let file = dir.join("missing").join("users.jsonl");
+        let log = dir.join("audit.jsonl");
+        let result = run_args(&[
+            "--file",
+            file.to_str().unwrap(),
+            "--log",
+            log.to_str().unwrap(),
+            "add",
+            "ann",
+            "ann@example.com",
+        ]);
+        let logged = log_lines(&log);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exit_code(result), 1);
+        assert_eq!(logged, 0);
+    }
+}
@@ -0,0 +1,212 @@
+/* Building users step by step.
+ *
+ * Writing out a User literal means choosing a value for every field, and
+ * struct update syntax ('..user1') quietly fills in whatever was left out
+ * from another user: its sign-in count, and even its password. The builder
+ * asks only for what a new user must have, a username and an email, and
+ * starts everything else from a sensible default:
+ *
+ *   active          true
+ *   sign_in_count   0
+ *   credentials     no password
+ *   roles           viewer
+ *
+ * 'clone_with' derives a new user from an existing one. It keeps only the
+ * settings of the existing user (whether it is active, and its roles), never
+ * its identity, history or password, so the username and email have to be
+ * given again. */
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{Credentials, Email, PasswordHash, Role, Roles, User, Username};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    MissingUsername,
+    MissingEmail,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::MissingUsername => write!(f, "a user needs a username"),
+            BuildError::MissingEmail => write!(f, "a user needs an email"),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    username: Option<Username>,
+    email: Option<Email>,
+    active: bool,
+    sign_in_count: u64,
+    credentials: Credentials,
+    roles: Roles,
+}
+
+impl Default for UserBuilder {
+    fn default() -> UserBuilder {
+        UserBuilder {
+            username: None,
+            email: None,
+            active: true,
+            sign_in_count: 0,
+            credentials: Credentials::default(),
+            roles: Roles::default(),
+        }
+    }
+}
+
+impl UserBuilder {
+    pub fn new() -> UserBuilder {
+        UserBuilder::default()
+    }
+
+    pub fn username(mut self, username: Username) -> UserBuilder {
+        self.username = Some(username);
+        self
+    }
+
+    pub fn email(mut self, email: Email) -> UserBuilder {
+        self.email = Some(email);
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> UserBuilder {
+        self.active = active;
+        self
+    }
+
+    /* For users brought over from elsewhere with a history of sign-ins. */
+    pub fn sign_in_count(mut self, sign_in_count: u64) -> UserBuilder {
+        self.sign_in_count = sign_in_count;
+        self
+    }
+
+    pub fn password(mut self, password: PasswordHash) -> UserBuilder {
+        self.credentials.password = Some(password);
+        self
+    }
+
+    /* Replaces the roles, including the default viewer role. */
+    pub fn roles(mut self, roles: Roles) -> UserBuilder {
+        self.roles = roles;
+        self
+    }
+
+    /* Adds a role to those the user has so far. */
+    pub fn role(mut self, role: Role) -> UserBuilder {
+        self.roles.insert(role);
+        self
+    }
+
+    pub fn build(self) -> Result<User, BuildError> {
+        Ok(User {
+            username: self.username.ok_or(BuildError::MissingUsername)?,
+            email: self.email.ok_or(BuildError::MissingEmail)?,
+            active: self.active,
+            sign_in_count: self.sign_in_count,
+            credentials: self.credentials,
+            roles: self.roles,
+        })
+    }
+}
+
+impl User {
+    pub fn builder() -> UserBuilder {
+        UserBuilder::new()
+    }
+
+    /* A builder for a new user with the same settings as this one. */
+    pub fn clone_with(&self) -> UserBuilder {
+        UserBuilder::new()
+            .active(self.active)
+            .roles(self.roles.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::password::MIN_ITERATIONS;
+
+    fn ann() -> UserBuilder {
+        User::builder()
+            .username("ann".parse().unwrap())
+            .email("ann@example.com".parse().unwrap())
+    }
+
+    #[test]
+    fn everything_but_the_name_and_email_has_a_default() {
+        let user = ann().build().unwrap();
+        assert_eq!(user.username.as_ref(), "ann");
+        assert_eq!(user.email.as_ref(), "ann@example.com");
+        assert!(user.active);
+        assert_eq!(user.sign_in_count, 0);
+        assert_eq!(user.credentials, Credentials::default());
+        assert_eq!(user.roles, Roles::from(Role::Viewer));
+    }
+
+    #[test]
+    fn settings_override_the_defaults() {
+        let password = PasswordHash::with_iterations("correct horse", MIN_ITERATIONS).unwrap();
+        let user = ann()
+            .active(false)
+            .sign_in_count(7)
+            .password(password.clone())
+            .role(Role::Editor)
+            .build()
+            .unwrap();
+        assert!(!user.active);
+        assert_eq!(user.sign_in_count, 7);
+        assert_eq!(user.credentials.password, Some(password));
+        assert!(user.roles.contains(Role::Viewer) && user.roles.contains(Role::Editor));
+
+        let user = ann().roles(Roles::from(Role::Admin)).build().unwrap();
+        assert_eq!(user.roles, Roles::from(Role::Admin));
+    }
+
+    #[test]
+    fn username_and_email_are_required() {
+        let username = "ann".parse().unwrap();
+        let email = "ann@example.com".parse().unwrap();
+        assert_eq!(
+            User::builder().email(email).build(),
+            Err(BuildError::MissingUsername)
+        );
+        assert_eq!(
+            User::builder().username(username).build(),
+            Err(BuildError::MissingEmail)
+        );
+        assert_eq!(User::builder().build(), Err(BuildError::MissingUsername));
+    }
+
+    #[test]
+    fn clone_with_keeps_settings_but_not_identity_or_password() {
+        let password = PasswordHash::with_iterations("correct horse", MIN_ITERATIONS).unwrap();
+        let mut ann = ann()
+            .active(false)
+            .password(password)
+            .role(Role::Admin)
+            .build()
+            .unwrap();
+        ann.sign_in_count = 3;
+        ann.credentials.failed_attempts = 2;
+
+        assert_eq!(ann.clone_with().build(), Err(BuildError::MissingUsername));
+        let bob = ann
+            .clone_with()
+            .username("bob".parse().unwrap())
+            .email("bob@example.com".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(!bob.active);
+        assert_eq!(bob.roles, ann.roles);
+        assert_eq!(bob.sign_in_count, 0);
+        assert_eq!(bob.credentials, Credentials::default());
+    }
+}
@@ -0,0 +1,460 @@
+/* Colors.
+ *
+ * The book's 'struct Color(i32, i32, i32)' can hold -1 or 300 as a channel.
+ * Here each channel is a u8, so only 0 to 255 fit, and an alpha channel
+ * says how opaque the color is (255 is fully opaque). Converting from i32
+ * values checks that they are in range.
+ *
+ * Colors are written in hex, as on the web: "#ff8800", or "#ff880080" with
+ * alpha. The short forms "#f80" and "#f808" are read too. The '#' is
+ * required, so "f80" and "##f80" are refused. Colors convert to and from HSL
+ * (hue, saturation, lightness) and HSV (hue, saturation, value), with hue in
+ * degrees and the rest between 0 and 1.
+ *
+ * 'contrast_ratio' follows WCAG 2: 1 for two equal colors up to 21 for black
+ * on white. Text needs a ratio of at least 4.5 to be easy to read. */
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorError {
+    /* A channel given as an i32 outside 0 to 255. */
+    OutOfRange { channel: char, value: i32 },
+    InvalidHex(String),
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorError::OutOfRange { channel, value } => write!(
+                f,
+                "channel {} must be between 0 and 255, found {}",
+                channel, value
+            ),
+            ColorError::InvalidHex(text) => write!(
+                f,
+                "invalid hex color {:?}, expected #rgb, #rgba, #rrggbb or #rrggbbaa",
+                text
+            ),
+        }
+    }
+}
+
+impl Error for ColorError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/* Hue in degrees from 0 up to 360; saturation and lightness from 0 to 1. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    pub h: f64,
+    pub s: f64,
+    pub l: f64,
+}
+
+/* Hue in degrees from 0 up to 360; saturation and value from 0 to 1. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    pub h: f64,
+    pub s: f64,
+    pub v: f64,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub fn with_alpha(self, a: u8) -> Color {
+        Color { a, ..self }
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self.a == 255
+    }
+
+    /* "#rrggbb", or "#rrggbbaa" when not fully opaque. */
+    pub fn to_hex(self) -> String {
+        if self.is_opaque() {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    pub fn from_hex(text: &str) -> Result<Color, ColorError> {
+        let invalid = || ColorError::InvalidHex(text.to_string());
+        let digits = text.trim().strip_prefix('#').ok_or_else(invalid)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channels: Vec<u8> = match digits.len() {
+            /* Each digit is doubled: "f80" is "ff8800". */
+            3 | 4 => digits
+                .chars()
+                .map(|c| c.to_digit(16).unwrap() as u8 * 0x11)
+                .collect(),
+            6 | 8 => (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+                .collect(),
+            _ => return Err(invalid()),
+        };
+        Ok(Color {
+            r: channels[0],
+            g: channels[1],
+            b: channels[2],
+            a: channels.get(3).copied().unwrap_or(255),
+        })
+    }
+
+    pub fn to_hsl(self) -> Hsl {
+        let (h, max, min) = self.hue_max_min();
+        let l = (max + min) / 2.0;
+        let delta = max - min;
+        let s = if delta == 0.0 {
+            0.0
+        } else {
+            delta / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        Hsl { h, s, l }
+    }
+
+    /* The alpha channel is opaque. */
+    pub fn from_hsl(hsl: Hsl) -> Color {
+        let (s, l) = (clamp_unit(hsl.s), clamp_unit(hsl.l));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Color::from_hue(hsl.h, chroma, l - chroma / 2.0)
+    }
+
+    pub fn to_hsv(self) -> Hsv {
+        let (h, max, min) = self.hue_max_min();
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsv { h, s, v: max }
+    }
+
+    /* The alpha channel is opaque. */
+    pub fn from_hsv(hsv: Hsv) -> Color {
+        let (s, v) = (clamp_unit(hsv.s), clamp_unit(hsv.v));
+        let chroma = v * s;
+        Color::from_hue(hsv.h, chroma, v - chroma)
+    }
+
+    /* Hue in degrees and the largest and smallest channel, from 0 to 1. */
+    fn hue_max_min(self) -> (f64, f64, f64) {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| f64::from(c) / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (h, max, min)
+    }
+
+    /* Shared by HSL and HSV: a hue, the chroma, and the amount added to
+     * every channel. */
+    fn from_hue(hue: f64, chroma: f64, m: f64) -> Color {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Color::rgb(to_channel(r + m), to_channel(g + m), to_channel(b + m))
+    }
+
+    /* Draws this color on top of 'background', letting the background show
+     * through as much as this color is transparent. */
+    pub fn over(self, background: Color) -> Color {
+        let top = f64::from(self.a) / 255.0;
+        let bottom = f64::from(background.a) / 255.0 * (1.0 - top);
+        let alpha = top + bottom;
+        if alpha == 0.0 {
+            return Color::TRANSPARENT;
+        }
+        let channel = |front: u8, back: u8| {
+            to_channel((f64::from(front) * top + f64::from(back) * bottom) / alpha / 255.0)
+        };
+        Color {
+            r: channel(self.r, background.r),
+            g: channel(self.g, background.g),
+            b: channel(self.b, background.b),
+            a: to_channel(alpha),
+        }
+    }
+
+    /* The color 't' of the way from this one to 'other', with 't' from 0
+     * (this color) to 1 ('other'). */
+    pub fn mix(self, other: Color, t: f64) -> Color {
+        let t = clamp_unit(t);
+        let channel = |from: u8, to: u8| {
+            (f64::from(from) + (f64::from(to) - f64::from(from)) * t).round() as u8
+        };
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: channel(self.a, other.a),
+        }
+    }
+
+    /* How bright the color looks, from 0 for black to 1 for white. Alpha is
+     * ignored. */
+    pub fn relative_luminance(self) -> f64 {
+        let linear = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    /* From 1 to 21; the order of the two colors does not matter. */
+    pub fn contrast_ratio(self, other: Color) -> f64 {
+        let (a, b) = (self.relative_luminance(), other.relative_luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
+fn clamp_unit(value: f64) -> f64 {
+    value.clamp(0.0, 1.0)
+}
+
+fn to_channel(value: f64) -> u8 {
+    (clamp_unit(value) * 255.0).round() as u8
+}
+
+/* For colors written like the book's 'Color(0, 0, 0)'. */
+impl TryFrom<(i32, i32, i32)> for Color {
+    type Error = ColorError;
+
+    fn try_from((r, g, b): (i32, i32, i32)) -> Result<Color, ColorError> {
+        let channel = |channel, value: i32| {
+            u8::try_from(value).map_err(|_| ColorError::OutOfRange { channel, value })
+        };
+        Ok(Color::rgb(
+            channel('r', r)?,
+            channel('g', g)?,
+            channel('b', b)?,
+        ))
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((r, g, b): (u8, u8, u8)) -> Color {
+        Color::rgb(r, g, b)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(text: &str) -> Result<Color, ColorError> {
+        Color::from_hex(text)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Display for Hsl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hsl({:.0}, {:.0}%, {:.0}%)",
+            self.h,
+            self.s * 100.0,
+            self.l * 100.0
+        )
+    }
+}
+
+impl fmt::Display for Hsv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hsv({:.0}, {:.0}%, {:.0}%)",
+            self.h,
+            self.s * 100.0,
+            self.v * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.005
+    }
+
+    fn red() -> Color {
+        Color::rgb(255, 0, 0)
+    }
+
+    /* A spread of colors over the whole RGB cube. */
+    fn colors() -> impl Iterator<Item = Color> {
+        let steps = || (0..=255).step_by(15);
+        steps()
+            .flat_map(move |r| steps().flat_map(move |g| steps().map(move |b| Color::rgb(r, g, b))))
+    }
+
+    #[test]
+    fn hex_is_read_in_every_form() {
+        let orange = Color::rgb(0xff, 0x88, 0x00);
+        assert_eq!(Color::from_hex("#ff8800"), Ok(orange));
+        assert_eq!(Color::from_hex(" #FF8800 "), Ok(orange));
+        assert_eq!(Color::from_hex("#f80"), Ok(orange));
+        assert_eq!(Color::from_hex("#f808"), Ok(orange.with_alpha(0x88)));
+        assert_eq!(Color::from_hex("#ff880080"), Ok(orange.with_alpha(0x80)));
+        for text in &[
+            "ff8800", "f80", "##f80", "#", "", "#ff88f", "#ff88000", "#gg8800", "#+f8",
+        ] {
+            assert_eq!(
+                Color::from_hex(text),
+                Err(ColorError::InvalidHex(text.to_string())),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(Color::rgb(0xff, 0x88, 0x00).to_hex(), "#ff8800");
+        assert_eq!(Color::rgba(1, 2, 3, 4).to_hex(), "#01020304");
+        for color in colors() {
+            for &color in &[color, color.with_alpha(color.g)] {
+                assert_eq!(color.to_string().parse(), Ok(color));
+            }
+        }
+    }
+
+    #[test]
+    fn channels_out_of_range_are_refused() {
+        assert_eq!(Color::try_from((0, 128, 255)), Ok(Color::rgb(0, 128, 255)));
+        assert_eq!(
+            Color::try_from((0, 256, -1)),
+            Err(ColorError::OutOfRange {
+                channel: 'g',
+                value: 256
+            })
+        );
+    }
+
+    #[test]
+    fn hsl_and_hsv_of_known_colors() {
+        let hsl = Color::rgb(0xff, 0x88, 0x00).to_hsl();
+        assert!(close(hsl.h, 32.0) && close(hsl.s, 1.0) && close(hsl.l, 0.5));
+        let hsv = Color::rgb(0x33, 0x66, 0x99).to_hsv();
+        assert!(close(hsv.h, 210.0) && close(hsv.s, 2.0 / 3.0) && close(hsv.v, 0.6));
+        assert_eq!(Color::BLACK.to_hsl().to_string(), "hsl(0, 0%, 0%)");
+        assert_eq!(Color::WHITE.to_hsv().to_string(), "hsv(0, 0%, 100%)");
+        assert_eq!(
+            Color::from_hsl(Hsl {
+                h: 120.0,
+                s: 1.0,
+                l: 0.25
+            }),
+            Color::rgb(0, 128, 0)
+        );
+        /* Hue wraps around; saturation and lightness are clamped. */
+        assert_eq!(
+            Color::from_hsv(Hsv {
+                h: -120.0,
+                s: 2.0,
+                v: 1.0
+            }),
+            Color::rgb(0, 0, 255)
+        );
+    }
+
+    #[test]
+    fn hsl_and_hsv_round_trip() {
+        for color in colors() {
+            assert_eq!(Color::from_hsl(color.to_hsl()), color, "{}", color);
+            assert_eq!(Color::from_hsv(color.to_hsv()), color, "{}", color);
+        }
+    }
+
+    #[test]
+    fn alpha_blending() {
+        let red = red();
+        let blue = Color::rgb(0, 0, 255);
+        assert_eq!(red.over(blue), red);
+        assert_eq!(Color::TRANSPARENT.over(blue), blue);
+        assert_eq!(red.with_alpha(128).over(blue), Color::rgb(128, 0, 127));
+        assert_eq!(
+            red.with_alpha(128).over(Color::TRANSPARENT),
+            red.with_alpha(128)
+        );
+        /* Two half-transparent layers let a quarter through. */
+        assert_eq!(
+            red.with_alpha(128).over(blue.with_alpha(128)),
+            Color::rgba(170, 0, 85, 192)
+        );
+        assert_eq!(
+            Color::TRANSPARENT.over(Color::TRANSPARENT),
+            Color::TRANSPARENT
+        );
+        assert_eq!(red.mix(blue, 0.5), Color::rgb(128, 0, 128));
+        assert_eq!(red.mix(blue, 2.0), blue);
+    }
+
+    #[test]
+    fn wcag_contrast_ratios() {
+        assert!(close(Color::BLACK.contrast_ratio(Color::WHITE), 21.0));
+        assert!(close(Color::WHITE.contrast_ratio(Color::BLACK), 21.0));
+        assert!(close(red().contrast_ratio(red()), 1.0));
+        /* The usual examples: #767676 is the lightest grey that passes on
+         * white, #777777 just fails. */
+        assert!(close(
+            Color::rgb(0x76, 0x76, 0x76).contrast_ratio(Color::WHITE),
+            4.54
+        ));
+        assert!(close(
+            Color::rgb(0x77, 0x77, 0x77).contrast_ratio(Color::WHITE),
+            4.48
+        ));
+        assert!(close(red().contrast_ratio(Color::WHITE), 4.0));
+        assert!(close(
+            Color::rgb(0, 0, 255).contrast_ratio(Color::BLACK),
+            2.44
+        ));
+    }
+}
@@ -0,0 +1,500 @@
+/* Moving users in and out as CSV or JSON.
+ *
+ * CSV has a header naming the columns, in any order:
+ *
+ *   username,email,active,sign_in_count,roles
+ *   abc,abc@xyz.com,true,1,editor;viewer
+ *
+ * A field holding a comma or a double quote is wrapped in double quotes,
+ * with each quote inside doubled: "a,b" or "say ""hi""". A field may not
+ * span lines.
+ *
+ * JSON is an array of objects with the same fields, with roles as an array:
+ *
+ *   [{"username":"abc","email":"abc@xyz.com","active":true,"sign_in_count":1,
+ *     "roles":["editor","viewer"]}]
+ *
+ * Only username and email are required; missing fields take the defaults
+ * of 'UserBuilder'. Passwords are never exported, and imported users have
+ * none. */
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use crate::builder::BuildError;
+use crate::json::{self, Value};
+use crate::validation::ValidationError;
+use crate::{User, UserBuilder};
+
+const COLUMNS: [&str; 5] = ["username", "email", "active", "sign_in_count", "roles"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /* JSON starts with '['; anything else is taken to be CSV. */
+    pub fn detect(text: &str) -> Format {
+        if text.trim_start().starts_with('[') {
+            Format::Json
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    Empty,
+    MissingColumn(&'static str),
+    UnknownColumn(String),
+    WrongColumnCount {
+        expected: usize,
+        found: usize,
+    },
+    MissingField(&'static str),
+    /* A field opened with a double quote but never closed. */
+    UnclosedQuote,
+    InvalidValue {
+        field: &'static str,
+        value: String,
+    },
+    Invalid(ValidationError),
+    Build(BuildError),
+    Json(String),
+    NotAnArray,
+    NotAnObject,
+    AtLine {
+        line: usize,
+        error: Box<ImportError>,
+    },
+    AtIndex {
+        index: usize,
+        error: Box<ImportError>,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Empty => write!(f, "no header line"),
+            ImportError::MissingColumn(column) => write!(f, "missing column {:?}", column),
+            ImportError::UnknownColumn(column) => write!(f, "unknown column {:?}", column),
+            ImportError::WrongColumnCount { expected, found } => {
+                write!(f, "expected {} columns, found {}", expected, found)
+            }
+            ImportError::MissingField(field) => write!(f, "missing field {:?}", field),
+            ImportError::UnclosedQuote => write!(f, "unclosed double quote"),
+            ImportError::InvalidValue { field, value } => {
+                write!(f, "invalid {}: {:?}", field, value)
+            }
+            ImportError::Invalid(error) => write!(f, "{}", error),
+            ImportError::Build(error) => write!(f, "{}", error),
+            ImportError::Json(message) => write!(f, "invalid JSON: {}", message),
+            ImportError::NotAnArray => write!(f, "expected an array of users"),
+            ImportError::NotAnObject => write!(f, "expected an object"),
+            ImportError::AtLine { line, error } => write!(f, "line {}: {}", line, error),
+            ImportError::AtIndex { index, error } => write!(f, "user {}: {}", index, error),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+impl From<ValidationError> for ImportError {
+    fn from(error: ValidationError) -> ImportError {
+        ImportError::Invalid(error)
+    }
+}
+
+impl From<BuildError> for ImportError {
+    fn from(error: BuildError) -> ImportError {
+        ImportError::Build(error)
+    }
+}
+
+pub fn import(text: &str, format: Format) -> Result<Vec<User>, ImportError> {
+    match format {
+        Format::Csv => from_csv(text),
+        Format::Json => from_json(text),
+    }
+}
+
+pub fn export(users: &[User], format: Format) -> String {
+    match format {
+        Format::Csv => to_csv(users),
+        Format::Json => to_json(users),
+    }
+}
+
+pub fn to_csv(users: &[User]) -> String {
+    let mut out = COLUMNS.join(",");
+    out.push('\n');
+    for user in users {
+        let fields = [
+            quote(user.username.as_str()),
+            quote(user.email.as_str()),
+            user.active.to_string(),
+            user.sign_in_count.to_string(),
+            quote(&user.roles.to_string()),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/* Wraps a field in double quotes if it holds a comma or a quote. */
+fn quote(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/* Splits a line into fields, undoing 'quote'. Space around a field is
+ * dropped. */
+fn split_fields(line: &str) -> Result<Vec<String>, ImportError> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next().ok_or(ImportError::UnclosedQuote)? {
+                    '"' if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    '"' => break,
+                    c => field.push(c),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        }
+        while let Some(c) = chars.next_if(|&c| c != ',') {
+            field.push(c);
+        }
+        field.truncate(field.trim_end().len());
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+/* Blank lines are skipped. Fields may be wrapped in double quotes. */
+pub fn from_csv(text: &str) -> Result<Vec<User>, ImportError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let (number, header) = lines.next().ok_or(ImportError::Empty)?;
+    let columns = split_fields(header)
+        .map_err(|error| ImportError::AtLine {
+            line: number,
+            error: Box::new(error),
+        })?
+        .into_iter()
+        .map(|name| {
+            let name = name.to_ascii_lowercase();
+            COLUMNS
+                .iter()
+                .find(|&&column| column == name)
+                .copied()
+                .ok_or(ImportError::UnknownColumn(name))
+        })
+        .collect::<Result<Vec<&'static str>, ImportError>>()?;
+    for required in &COLUMNS[..2] {
+        if !columns.contains(required) {
+            return Err(ImportError::MissingColumn(required));
+        }
+    }
+
+    lines
+        .map(|(line, text)| {
+            csv_user(&columns, text).map_err(|error| ImportError::AtLine {
+                line,
+                error: Box::new(error),
+            })
+        })
+        .collect()
+}
+
+fn csv_user(columns: &[&'static str], line: &str) -> Result<User, ImportError> {
+    let fields = split_fields(line)?;
+    if fields.len() != columns.len() {
+        return Err(ImportError::WrongColumnCount {
+            expected: columns.len(),
+            found: fields.len(),
+        });
+    }
+    user_from_fields(|name| {
+        columns
+            .iter()
+            .position(|&column| column == name)
+            .map(|i| fields[i].as_str())
+    })
+}
+
+pub fn to_json(users: &[User]) -> String {
+    let items: Vec<Value> = users
+        .iter()
+        .map(|user| {
+            Value::Object(vec![
+                (
+                    "username".to_string(),
+                    Value::string(user.username.as_str()),
+                ),
+                ("email".to_string(), Value::string(user.email.as_str())),
+                ("active".to_string(), Value::Bool(user.active)),
+                (
+                    "sign_in_count".to_string(),
+                    Value::number(user.sign_in_count),
+                ),
+                (
+                    "roles".to_string(),
+                    Value::Array(
+                        user.roles
+                            .iter()
+                            .map(|role| Value::string(role.name()))
+                            .collect(),
+                    ),
+                ),
+            ])
+        })
+        .collect();
+    let mut out = Value::Array(items).to_json();
+    out.push('\n');
+    out
+}
+
+pub fn from_json(text: &str) -> Result<Vec<User>, ImportError> {
+    let items = match json::parse(text).map_err(ImportError::Json)? {
+        Value::Array(items) => items,
+        _ => return Err(ImportError::NotAnArray),
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let user = match item {
+                Value::Object(fields) => {
+                    /* Turn each value back into text so that CSV and JSON
+                     * share one set of checks. */
+                    let text: Vec<(&str, String)> = fields
+                        .iter()
+                        .map(|(key, value)| {
+                            let text = match value {
+                                Value::String(s) | Value::Number(s) => s.clone(),
+                                /* Roles, written as in CSV. */
+                                Value::Array(items) => items
+                                    .iter()
+                                    .map(|item| match item {
+                                        Value::String(s) => s.clone(),
+                                        other => other.to_json(),
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join(";"),
+                                other => other.to_json(),
+                            };
+                            (key.as_str(), text)
+                        })
+                        .collect();
+                    user_from_fields(|name| {
+                        text.iter()
+                            .find(|(key, _)| *key == name)
+                            .map(|(_, value)| value.as_str())
+                    })
+                }
+                _ => Err(ImportError::NotAnObject),
+            };
+            user.map_err(|error| ImportError::AtIndex {
+                index,
+                error: Box::new(error),
+            })
+        })
+        .collect()
+}
+
+fn user_from_fields<'a, F>(field: F) -> Result<User, ImportError>
+where
+    F: Fn(&'static str) -> Option<&'a str>,
+{
+    let required = |name| field(name).ok_or(ImportError::MissingField(name));
+    let invalid = |name: &'static str, value: &str| ImportError::InvalidValue {
+        field: name,
+        value: value.to_string(),
+    };
+
+    let mut builder = UserBuilder::new()
+        .username(required("username")?.parse()?)
+        .email(required("email")?.parse()?);
+    if let Some(value) = field("active") {
+        builder = builder.active(match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => true,
+            "false" | "no" | "0" => false,
+            _ => return Err(invalid("active", value)),
+        });
+    }
+    if let Some(value) = field("sign_in_count") {
+        builder =
+            builder.sign_in_count(value.parse().map_err(|_| invalid("sign_in_count", value))?);
+    }
+    if let Some(value) = field("roles") {
+        builder = builder.roles(value.parse().map_err(|_| invalid("roles", value))?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Role, Roles};
+
+    fn users() -> Vec<User> {
+        let ann = UserBuilder::new()
+            .username("ann".parse().unwrap())
+            .email("ann@example.com".parse().unwrap())
+            .build()
+            .unwrap();
+        let bob = UserBuilder::new()
+            .username("bob".parse().unwrap())
+            .email("o'brien@example.com".parse().unwrap())
+            .active(false)
+            .sign_in_count(3)
+            .roles(
+                [Role::Admin, Role::Viewer]
+                    .iter()
+                    .copied()
+                    .collect::<Roles>(),
+            )
+            .build()
+            .unwrap();
+        vec![ann, bob]
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        let csv = to_csv(&users());
+        assert_eq!(
+            csv,
+            "username,email,active,sign_in_count,roles\n\
+             ann,ann@example.com,true,0,viewer\n\
+             bob,o'brien@example.com,false,3,viewer;admin\n"
+        );
+        assert_eq!(from_csv(&csv).unwrap(), users());
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let json = to_json(&users());
+        assert!(json.starts_with(
+            r#"[{"username":"ann","email":"ann@example.com","active":true,"sign_in_count":0,"roles":["viewer"]}"#
+        ));
+        assert_eq!(from_json(&json).unwrap(), users());
+        assert_eq!(Format::detect(&json), Format::Json);
+        assert_eq!(Format::detect("username,email\n"), Format::Csv);
+    }
+
+    #[test]
+    fn fields_with_commas_and_quotes_are_quoted() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("a,b"), r#""a,b""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say ""hi""""#);
+        for field in &["plain", "a,b", r#"say "hi""#, r#"",""#, ""] {
+            assert_eq!(split_fields(&quote(field)), Ok(vec![field.to_string()]));
+        }
+        assert_eq!(
+            split_fields(r#" a , "b,c" ,"d ""e""",,"#),
+            Ok(vec![
+                "a".to_string(),
+                "b,c".to_string(),
+                r#"d "e""#.to_string(),
+                String::new(),
+                String::new(),
+            ])
+        );
+        assert_eq!(split_fields(r#"a,"b"#), Err(ImportError::UnclosedQuote));
+    }
+
+    #[test]
+    fn quoted_csv_is_imported() {
+        let csv = "\"Username\",\"Email\",roles\n\"ann\",\"ann@example.com\",\"admin;viewer\"\n";
+        let users = from_csv(csv).unwrap();
+        assert_eq!(users[0].username.as_str(), "ann");
+        assert!(users[0].roles.contains(Role::Admin));
+    }
+
+    #[test]
+    fn bad_csv_is_refused() {
+        assert_eq!(from_csv(""), Err(ImportError::Empty));
+        assert_eq!(
+            from_csv("username\nann\n"),
+            Err(ImportError::MissingColumn("email"))
+        );
+        assert_eq!(
+            from_csv("username,email,age\n"),
+            Err(ImportError::UnknownColumn(String::from("age")))
+        );
+        assert_eq!(
+            from_csv("username,email\nann\n"),
+            Err(ImportError::AtLine {
+                line: 2,
+                error: Box::new(ImportError::WrongColumnCount {
+                    expected: 2,
+                    found: 1
+                }),
+            })
+        );
+        assert_eq!(
+            from_csv("username,email,active\nann,ann@example.com,maybe\n"),
+            Err(ImportError::AtLine {
+                line: 2,
+                error: Box::new(ImportError::InvalidValue {
+                    field: "active",
+                    value: String::from("maybe"),
+                }),
+            })
+        );
+        assert_eq!(
+            from_csv("username,email\n\"ann,ann@example.com\n"),
+            Err(ImportError::AtLine {
+                line: 2,
+                error: Box::new(ImportError::UnclosedQuote),
+            })
+        );
+    }
+
+    #[test]
+    fn bad_json_is_refused() {
+        assert_eq!(from_json("{}"), Err(ImportError::NotAnArray));
+        assert_eq!(
+            from_json("[1]"),
+            Err(ImportError::AtIndex {
+                index: 0,
+                error: Box::new(ImportError::NotAnObject),
+            })
+        );
+        assert_eq!(
+            from_json(r#"[{"username":"ann"}]"#),
+            Err(ImportError::AtIndex {
+                index: 0,
+                error: Box::new(ImportError::MissingField("email")),
+            })
+        );
+        assert!(matches!(from_json("[{"), Err(ImportError::Json(_))));
+    }
+}
@@ -0,0 +1,50 @@
+/* The User struct from the 'Defining and Instantiating Structs' section
+ * lives in the library so that the user store and other code can build on
+ * it. See src/main.rs for the walkthrough. */
+
+pub mod audit;
+pub mod builder;
+pub mod color;
+pub mod format;
+mod json;
+pub mod password;
+pub mod point;
+pub mod role;
+pub mod session;
+pub mod storage;
+pub mod store;
+pub mod validation;
+
+pub use builder::UserBuilder;
+pub use color::Color;
+pub use password::{Credentials, PasswordHash};
+pub use point::Point;
+pub use role::{Action, Resource, Role, Roles};
+pub use validation::{Email, Username};
+
+/* Unlike the book's version, username and email are validated types rather
+ * than Strings, so a User cannot hold a malformed or swapped value. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub username: Username,
+    pub email: Email,
+    pub sign_in_count: u64,
+    pub active: bool,
+    pub credentials: Credentials,
+    pub roles: Roles,
+}
+
+/* New users start active and without sign-ins, as with 'UserBuilder'. */
+pub fn build_user(email: Email, username: Username) -> User {
+    User {
+        //email: email,
+        //shorthand since parameter name and field name are same
+        email,
+        //username: username,
+        username,
+        active: true,
+        sign_in_count: 0,
+        credentials: Credentials::default(),
+        roles: Roles::default(),
+    }
+}
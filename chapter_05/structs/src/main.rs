@@ -2,21 +2,43 @@
  * 'struct'. Inside the curly brackets, we define the names and types of
  * the pieces of data, which we call 'fields'. */
 
-struct User {
-    username: String,
-    email: String,
-    sign_in_count: u64,
-    active: bool,
-}
+/* The User struct and 'build_user' are defined in src/lib.rs, so that the
+ * user store can build on them. There, username and email are validated
+ * types (see src/validation.rs) rather than Strings:
+ *
+ * struct User {
+ *     username: Username,
+ *     email: Email,
+ *     sign_in_count: u64,
+ *     active: bool,
+ *     credentials: Credentials,
+ *     roles: Roles,
+ * }
+ * */
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::time::Duration;
+
+use structs::audit::{AuditLog, AuditedStore};
+use structs::session::{Clock, ManualClock, Sessions};
+use structs::storage::{FileStorage, MemoryStorage};
+use structs::store::UserStore;
+use structs::{
+    build_user, Action, Color, Credentials, Email, PasswordHash, Point, Resource, Role, Roles,
+    User, Username,
+};
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     /* Creating an instance of the struct */
 
     let user1 = User {
-        email: String::from("abc@xyz.com"),
-        username: String::from("abc"),
+        email: "abc@xyz.com".parse().unwrap(),
+        username: "abc".parse().unwrap(),
         active: true,
         sign_in_count: 1,
+        credentials: Credentials::default(),
+        roles: Roles::default(),
     };
 
     /* We can use dot notation to get specific value from the struct instance */
@@ -30,15 +52,17 @@ fn main() {
      * instance of the struct has to be mutable, and not indivual fields. */
 
     let mut user1 = User {
-        email: String::from("abc@xyz.com"),
-        username: String::from("abc"),
+        email: "abc@xyz.com".parse().unwrap(),
+        username: "abc".parse().unwrap(),
         active: true,
         sign_in_count: 1,
+        credentials: Credentials::default(),
+        roles: Roles::default(),
     };
 
     /* We can use dot notation to get specific value from the struct instance */
 
-    user1.email = String::from("new_email@abc.com");
+    user1.email = "new_email@abc.com".parse().unwrap();
     println!(
         "username is {}, email is {}, active?: {}, sign in count is {}",
         user1.username, user1.email, user1.active, user1.sign_in_count
@@ -47,8 +71,8 @@ fn main() {
     /* One can also create a function to return the struct instance as an
      * expression. */
 
-    let user_email = String::from("aaa@bbb.com");
-    let user_username = String::from("aaabbb");
+    let user_email = "aaa@bbb.com".parse().unwrap();
+    let user_username = "aaabbb".parse().unwrap();
     let user2 = build_user(user_email, user_username);
     println!(
         "username is {}, email is {}, active?: {}, sign in count is {}",
@@ -60,24 +84,72 @@ fn main() {
     /* Creating user3 from user1 */
     let user3 = User {
         // we want email and username to be different for user3. rest of the
-        // fields are same as user1
-        email: String::from("user3"),
-        username: String::from("user3@newuser.com"),
-        ..user1
+        // fields are same as user1. credentials is not Copy, so taking it
+        // from user1 would move it; user1 is cloned since it is used later.
+        email: "user3@newuser.com".parse().unwrap(),
+        username: "user3".parse().unwrap(),
+        ..user1.clone()
     };
+    println!(
+        "username is {}, email is {}, active?: {}, sign in count is {}",
+        user3.username, user3.email, user3.active, user3.sign_in_count
+    );
+
+    /* Struct update syntax takes every field not written out from user1,
+     * including its sign-in count and password, which is rarely what a new
+     * user should get. 'clone_with' keeps only user1's settings and asks for
+     * the username and email again; everything else starts afresh. */
+
+    let user4 = user1
+        .clone_with()
+        .email("user4@newuser.com".parse().unwrap())
+        .username("user4".parse().unwrap())
+        .build()?;
+    println!(
+        "username is {}, email is {}, active?: {}, sign in count is {}",
+        user4.username, user4.email, user4.active, user4.sign_in_count
+    );
+
+    /* The builder makes users with sensible defaults, and refuses to build
+     * one without a username or email. */
+
+    let mut user5 = User::builder()
+        .username("user5".parse().unwrap())
+        .email("user5@newuser.com".parse().unwrap())
+        .build()?;
+    println!("{:?}", user5);
+    if let Err(e) = User::builder().username("user6".parse().unwrap()).build() {
+        println!("cannot build user: {}", e);
+    }
+
+    /* Username and Email are checked when they are parsed. Had the email
+     * and username of user3 been swapped, parsing would have failed: */
+
+    if let Err(e) = "user3".parse::<Email>() {
+        println!("\"user3\" is not an email: {}", e);
+    }
+    if let Err(e) = "user3@newuser.com".parse::<Username>() {
+        println!("\"user3@newuser.com\" is not a username: {}", e);
+    }
 
     /* Tuple structs */
 
     /* When you don't want to name the individual fileds with just types*/
 
-    struct Color(i32, i32, i32);
-    struct Point(i32, i32, i32);
+    /* The book defines 'struct Point(i32, i32, i32)' here. Point is now in
+     * src/point.rs, still a tuple struct, but generic so that it can hold
+     * i32s or f64s. */
 
-    let black = Color(0, 0, 0);
     let origin = Point(0, 0, 0);
 
-    /* Although black and origin have i32 as individual types, they are not
-     * same since they are instance of different tuple structs. */
+    /* The book also defines 'struct Color(i32, i32, i32)' here. Color now
+     * lives in src/color.rs with a u8 per channel, so a channel can't be
+     * negative or above 255. Values written as i32s are checked instead. */
+
+    let black = Color::try_from((0, 0, 0)).unwrap();
+
+    /* Although black and origin were both made from three i32s, they are
+     * not the same since they are instances of different types. */
 
     /* Unit-Like structs without any fields */
 
@@ -92,16 +164,283 @@ fn main() {
      * and email fields, since the instances of the struct should own data as
      * long as the entire struct is valid. It's possible to use references to
      * data owned by something else, but to do so requires the use of lifetimes.*/
-}
 
-fn build_user(email: String, username: String) -> User {
-    User {
-        //email: email,
-        //shorthand since parameter name and field name are same
-        email,
-        //username: username,
-        username,
-        active: false,
-        sign_in_count: 2,
+    /* A user store */
+
+    /* Structs on their own don't stop two users from having the same
+     * username or email. The UserStore keeps users and checks that. */
+
+    let mut users = UserStore::new();
+    users.create(user1).unwrap();
+    users.create(user2).unwrap();
+    if let Err(e) = users.create(build_user(
+        "other@xyz.com".parse().unwrap(),
+        "AaaBbb".parse().unwrap(),
+    )) {
+        println!("cannot create user: {}", e);
+    }
+
+    /* Signing in takes a password. Only a hash of it is kept. */
+
+    let password = PasswordHash::new("correct horse").unwrap();
+    users.set_password("aaabbb", password).unwrap();
+    println!(
+        "aaabbb signed in {} times",
+        users.sign_in("aaabbb", "correct horse").unwrap()
+    );
+    if let Err(e) = PasswordHash::new("short") {
+        println!("cannot set password: {}", e);
+    }
+    for _ in 0..6 {
+        if let Err(e) = users.sign_in("aaabbb", "wrong horse") {
+            println!("cannot sign in: {}", e);
+        }
+    }
+    users.unlock("aaabbb").unwrap();
+
+    /* Sessions */
+
+    /* Signing in hands out a session token to use instead of the password
+     * until the session has been idle too long or the user is deactivated.
+     * The clock here is moved by hand instead of waiting. */
+
+    let clock = ManualClock::new(std::time::SystemTime::now());
+    let mut sessions = Sessions::with_clock(clock, Duration::from_secs(15 * 60));
+    let token = sessions
+        .sign_in(&mut users, "aaabbb", "correct horse")
+        .unwrap();
+    sessions.clock().advance(Duration::from_secs(10 * 60));
+    println!("{:?}", sessions.validate(&users, token.as_str()).unwrap());
+    sessions.clock().advance(Duration::from_secs(20 * 60));
+    if let Err(e) = sessions.validate(&users, token.as_str()) {
+        println!("cannot use session: {}", e);
+    }
+    let token = sessions
+        .sign_in(&mut users, "aaabbb", "correct horse")
+        .unwrap();
+    println!(
+        "deactivating aaabbb ended {} session(s)",
+        sessions.deactivate(&mut users, "aaabbb").unwrap()
+    );
+    if let Err(e) = sessions.validate(&users, token.as_str()) {
+        println!("cannot use session: {}", e);
+    }
+    users.deactivate("aaabbb").unwrap();
+    if let Err(e) = users.sign_in("aaabbb", "correct horse") {
+        println!("cannot sign in: {}", e);
+    }
+    println!(
+        "user with email new_email@abc.com is {:?}",
+        users.find_by_email("new_email@abc.com")
+    );
+
+    /* Saving users */
+
+    /* The store lives in memory and is gone when the program ends. Saving it
+     * to storage and loading it back at startup keeps the users around. */
+
+    let mut memory = MemoryStorage::new();
+    users.save(&mut memory).unwrap();
+    println!(
+        "loaded {} users from memory",
+        UserStore::load(&memory).unwrap().len()
+    );
+
+    let mut file = FileStorage::new(std::env::temp_dir().join("structs-users.jsonl"));
+    match users.save(&mut file).and_then(|_| UserStore::load(&file)) {
+        Ok(loaded) => {
+            println!("saved users to {}:", file.path().display());
+            for user in loaded.iter() {
+                println!("  {:?}", user);
+            }
+        }
+        Err(e) => println!("cannot save users: {}", e),
+    }
+
+    /* Auditing changes */
+
+    /* Every change made through an AuditedStore is logged with who made it
+     * and when, so a user's past can be looked up later. */
+
+    let clock = ManualClock::new(std::time::SystemTime::now());
+    let created = clock.now();
+    let mut audited = AuditedStore::with_clock(UserStore::new(), AuditLog::new(), clock);
+    audited.create("admin", user4).unwrap();
+    audited.clock().advance(Duration::from_secs(60));
+    audited
+        .update_email("admin", "user4", "user4@changed.com".parse().unwrap())
+        .unwrap();
+    audited
+        .rename("admin", "user4", "user44".parse().unwrap())
+        .unwrap();
+    let password = PasswordHash::new("correct horse").unwrap();
+    audited.set_password("admin", "user44", password).unwrap();
+    let token = audited
+        .start_session(&mut sessions, "user44", "correct horse")
+        .unwrap();
+    audited.deactivate("admin", "user44").unwrap();
+    if let Err(e) = sessions.validate(audited.store(), token.as_str()) {
+        println!("cannot use session: {}", e);
+    }
+    for event in audited.log().history("user44") {
+        println!("{}", event);
+    }
+    let past = audited.log().state_at("user44", created).unwrap();
+    println!(
+        "when created, user44 was {} <{}>, active?: {}",
+        past.username, past.email, past.active
+    );
+    let earlier = created - Duration::from_secs(1);
+    println!(
+        "before that, user44 {}",
+        match audited.log().state_at("user44", earlier) {
+            Some(_) => "existed",
+            None => "did not exist yet",
+        }
+    );
+
+    /* Changes made to a User directly can be logged by giving the user
+     * from before and after. */
+
+    let (_, mut log) = audited.into_parts();
+    let before = user5.clone();
+    user5.email = "new_email@user5.com".parse().unwrap();
+    log.record_change(
+        std::time::SystemTime::now(),
+        "main",
+        Some(&before),
+        Some(&user5),
+    );
+    println!("{}", log.events().last().unwrap());
+
+    /* Roles and permissions */
+
+    /* Users are viewers unless given other roles. Each role can do what the
+     * roles below it can, and an inactive user can do nothing at all. */
+
+    let mut editor = User::builder()
+        .username("editor".parse().unwrap())
+        .email("editor@newuser.com".parse().unwrap())
+        .role(Role::Editor)
+        .build()?;
+    for &(action, resource) in &[
+        (Action::Read, Resource::Content),
+        (Action::Update, Resource::Content),
+        (Action::Read, Resource::Users),
+        (Action::Delete, Resource::Users),
+    ] {
+        println!(
+            "{} ({}) can {:?} {:?}: {}",
+            editor.username,
+            editor.roles,
+            action,
+            resource,
+            editor.can(action, resource)
+        );
+    }
+    editor.active = false;
+    println!(
+        "inactive {} can Read Content: {}",
+        editor.username,
+        editor.can(Action::Read, Resource::Content)
+    );
+
+    /* Colors */
+
+    /* Colors read and write hex as on the web, convert to HSL and HSV, and
+     * can be laid over one another. The contrast ratio tells whether text in
+     * one color is readable on another: at least 4.5 is needed. */
+
+    let orange: Color = "#ff8800".parse().unwrap();
+    println!("{} is {} and {}", orange, orange.to_hsl(), orange.to_hsv());
+    println!(
+        "{} back from HSL: {}",
+        orange,
+        Color::from_hsl(orange.to_hsl())
+    );
+    if let Err(e) = Color::try_from((0, 300, -1)) {
+        println!("Color(0, 300, -1) is not a color: {}", e);
+    }
+    for text in &["#ff880", "orange"] {
+        if let Err(e) = text.parse::<Color>() {
+            println!("{}", e);
+        }
+    }
+    let glass = Color::rgb(0, 0, 255).with_alpha(128);
+    println!(
+        "{} over {} is {}",
+        glass,
+        Color::WHITE,
+        glass.over(Color::WHITE)
+    );
+    println!(
+        "halfway from {} to {} is {}",
+        black,
+        Color::WHITE,
+        black.mix(Color::WHITE, 0.5)
+    );
+    for &text in &[Color::BLACK, orange, Color::rgb(0x77, 0x77, 0x77)] {
+        println!(
+            "{} on {}: contrast {:.2}",
+            text,
+            Color::WHITE,
+            text.contrast_ratio(Color::WHITE)
+        );
+    }
+
+    /* Points and vectors */
+
+    /* Points add, subtract and scale with the usual operators. The cross
+     * product is at right angles to both vectors, so its dot product with
+     * each is zero. Lengths and distances are f64 whatever the coordinates. */
+
+    let a = Point(1, 2, 3);
+    let b = Point::from((4, -5, 6));
+    println!("{} + {} = {}", a, b, a + b);
+    println!(
+        "{} - {} = {}, {} * 2 = {}, -{} = {}",
+        a,
+        b,
+        a - b,
+        a,
+        a * 2,
+        a,
+        -a
+    );
+    let normal = a.cross(b);
+    println!(
+        "{} x {} = {}, at right angles: {} and {}",
+        a,
+        b,
+        normal,
+        normal.dot(a),
+        normal.dot(b)
+    );
+    println!("{} x {} = {}", b, a, b.cross(a));
+    println!(
+        "from {} to {} is {:.3}, to {} is {:.3}",
+        origin,
+        a,
+        origin.distance(a),
+        b,
+        origin.distance(b)
+    );
+    let direction = Point(3.0, 0.0, 4.0);
+    if let Some(unit) = direction.normalize() {
+        println!(
+            "{} normalized is {} with length {}",
+            direction,
+            unit,
+            unit.length()
+        );
     }
+    println!(
+        "{} normalized is {:?}",
+        Point::<f64>::origin(),
+        Point::<f64>::origin().normalize()
+    );
+    let mut position = a.to_f64();
+    position += direction * 0.5;
+    println!("{} moved half of {} is {}", a, direction, position);
+    Ok(())
 }
@@ -0,0 +1,337 @@
+/* Passwords.
+ *
+ * Passwords are never kept as they were typed. Setting one picks a random
+ * salt and runs PBKDF2-HMAC-SHA256 over the password many times; only the
+ * salt, the iteration count and the result are stored. Checking a password
+ * repeats the work with the stored salt and compares the results. The many
+ * iterations make each guess slow, and the salt means two users with the
+ * same password get different hashes.
+ *
+ * A hash is written out as text with the algorithm and iteration count in
+ * front, so the count can be raised later without breaking older hashes:
+ *
+ *   pbkdf2-sha256$100000$<salt in hex>$<hash in hex>
+ *
+ * Hashes with fewer than MIN_ITERATIONS iterations are refused, both when
+ * hashing and when reading a stored hash, so a weakened or tampered hash
+ * cannot make guessing cheap. Hashes with more than MAX_ITERATIONS are
+ * refused too: a tampered count of four billion would otherwise tie up a
+ * login for hours. */
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use sha2::Sha256;
+
+const ALGORITHM: &str = "pbkdf2-sha256";
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+pub const MIN_ITERATIONS: u32 = 10_000;
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+pub const MIN_LENGTH: usize = 8;
+/* Wrong passwords in a row before a user is locked. */
+pub const MAX_FAILED_ATTEMPTS: u32 = 5;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordError {
+    TooShort(usize),
+    TooFewIterations(u32),
+    TooManyIterations(u32),
+    /* Text that is not a hash written by 'PasswordHash'. */
+    Malformed,
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswordError::TooShort(found) => write!(
+                f,
+                "password must be at least {} characters long, found {}",
+                MIN_LENGTH, found
+            ),
+            PasswordError::TooFewIterations(found) => write!(
+                f,
+                "password hash must use at least {} iterations, found {}",
+                MIN_ITERATIONS, found
+            ),
+            PasswordError::TooManyIterations(found) => write!(
+                f,
+                "password hash must use at most {} iterations, found {}",
+                MAX_ITERATIONS, found
+            ),
+            PasswordError::Malformed => write!(f, "malformed password hash"),
+        }
+    }
+}
+
+impl Error for PasswordError {}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct PasswordHash {
+    iterations: u32,
+    salt: [u8; SALT_LEN],
+    hash: [u8; HASH_LEN],
+}
+
+impl PasswordHash {
+    pub fn new(password: &str) -> Result<PasswordHash, PasswordError> {
+        PasswordHash::with_iterations(password, DEFAULT_ITERATIONS)
+    }
+
+    /* Fewer iterations are faster to check but also faster to guess, so at
+     * least MIN_ITERATIONS are needed, and at most MAX_ITERATIONS are
+     * allowed. */
+    pub fn with_iterations(password: &str, iterations: u32) -> Result<PasswordHash, PasswordError> {
+        let length = password.chars().count();
+        if length < MIN_LENGTH {
+            return Err(PasswordError::TooShort(length));
+        }
+        check_iterations(iterations)?;
+        let mut salt = [0; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Ok(PasswordHash {
+            iterations,
+            salt,
+            hash: derive(password, &salt, iterations),
+        })
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        let hash = derive(password, &self.salt, self.iterations);
+        /* Compare every byte so the time taken does not give away how much
+         * of the hash matched. */
+        hash.iter()
+            .zip(self.hash.iter())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+}
+
+fn check_iterations(iterations: u32) -> Result<(), PasswordError> {
+    if iterations < MIN_ITERATIONS {
+        Err(PasswordError::TooFewIterations(iterations))
+    } else if iterations > MAX_ITERATIONS {
+        Err(PasswordError::TooManyIterations(iterations))
+    } else {
+        Ok(())
+    }
+}
+
+fn derive(password: &str, salt: &[u8], iterations: u32) -> [u8; HASH_LEN] {
+    let mut hash = [0; HASH_LEN];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut hash);
+    hash
+}
+
+/* Keeps the hash out of debug output. */
+impl fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PasswordHash({}, {} iterations)",
+            ALGORITHM, self.iterations
+        )
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}${}${}${}",
+            ALGORITHM,
+            self.iterations,
+            to_hex(&self.salt),
+            to_hex(&self.hash)
+        )
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = PasswordError;
+
+    fn from_str(text: &str) -> Result<PasswordHash, PasswordError> {
+        let parts: Vec<&str> = text.split('$').collect();
+        match parts[..] {
+            [ALGORITHM, iterations, salt, hash] => {
+                if !iterations.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(PasswordError::Malformed);
+                }
+                let iterations = iterations.parse().map_err(|_| PasswordError::Malformed)?;
+                check_iterations(iterations)?;
+                Ok(PasswordHash {
+                    iterations,
+                    salt: from_hex(salt)?,
+                    hash: from_hex(hash)?,
+                })
+            }
+            _ => Err(PasswordError::Malformed),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex<const N: usize>(text: &str) -> Result<[u8; N], PasswordError> {
+    let mut bytes = [0; N];
+    if text.len() != N * 2 || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(PasswordError::Malformed);
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = text
+            .get(i * 2..i * 2 + 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or(PasswordError::Malformed)?;
+    }
+    Ok(bytes)
+}
+
+/* What a user signs in with. A user without a password cannot sign in. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Credentials {
+    pub password: Option<PasswordHash>,
+    /* Wrong passwords given since the last successful sign-in. */
+    pub failed_attempts: u32,
+}
+
+impl Credentials {
+    pub fn is_locked(&self) -> bool {
+        self.failed_attempts >= MAX_FAILED_ATTEMPTS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(password: &str) -> PasswordHash {
+        PasswordHash::with_iterations(password, MIN_ITERATIONS).unwrap()
+    }
+
+    #[test]
+    fn verifies_only_the_right_password() {
+        let hash = hash("correct horse");
+        assert!(hash.verify("correct horse"));
+        assert!(!hash.verify("correct horsE"));
+        assert!(!hash.verify(""));
+    }
+
+    #[test]
+    fn same_password_gets_a_different_salt() {
+        assert_ne!(hash("correct horse"), hash("correct horse"));
+    }
+
+    #[test]
+    fn short_passwords_are_refused() {
+        assert_eq!(
+            PasswordHash::new("seven77"),
+            Err(PasswordError::TooShort(7))
+        );
+    }
+
+    #[test]
+    fn too_few_iterations_are_refused() {
+        for &iterations in &[0, 1, MIN_ITERATIONS - 1] {
+            assert_eq!(
+                PasswordHash::with_iterations("correct horse", iterations),
+                Err(PasswordError::TooFewIterations(iterations))
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_iterations_are_refused() {
+        for &iterations in &[MAX_ITERATIONS + 1, u32::MAX] {
+            assert_eq!(
+                PasswordHash::with_iterations("correct horse", iterations),
+                Err(PasswordError::TooManyIterations(iterations))
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let hash = hash("correct horse");
+        let text = hash.to_string();
+        assert!(text.starts_with(&format!("pbkdf2-sha256${}$", MIN_ITERATIONS)));
+        let parsed: PasswordHash = text.parse().unwrap();
+        assert_eq!(parsed, hash);
+        assert!(parsed.verify("correct horse"));
+    }
+
+    #[test]
+    fn stored_hash_with_too_few_iterations_is_refused() {
+        let text = hash("correct horse").to_string();
+        let weakened = text.replacen(&MIN_ITERATIONS.to_string(), "1", 1);
+        assert_eq!(
+            weakened.parse::<PasswordHash>(),
+            Err(PasswordError::TooFewIterations(1))
+        );
+    }
+
+    #[test]
+    fn stored_hash_with_too_many_iterations_is_refused() {
+        let text = hash("correct horse").to_string();
+        let slowed = text.replacen(&MIN_ITERATIONS.to_string(), "4294967295", 1);
+        assert_eq!(
+            slowed.parse::<PasswordHash>(),
+            Err(PasswordError::TooManyIterations(u32::MAX))
+        );
+    }
+
+    #[test]
+    fn malformed_hashes_are_refused() {
+        let text = hash("correct horse").to_string();
+        let parts: Vec<&str> = text.split('$').collect();
+        for bad in &[
+            String::new(),
+            format!("md5${}${}${}", parts[1], parts[2], parts[3]),
+            format!("{}$+{}${}${}", parts[0], parts[1], parts[2], parts[3]),
+            format!("{}${}${}", parts[0], parts[1], parts[2]),
+            format!("{}${}${}${}", parts[0], parts[1], &parts[2][2..], parts[3]),
+            format!(
+                "{}${}$+f{}${}",
+                parts[0],
+                parts[1],
+                &parts[2][2..],
+                parts[3]
+            ),
+        ] {
+            assert_eq!(
+                bad.parse::<PasswordHash>(),
+                Err(PasswordError::Malformed),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn debug_output_hides_the_hash() {
+        let hash = hash("correct horse");
+        let debug = format!("{:?}", hash);
+        assert!(!debug.contains(&to_hex(&hash.hash)));
+        assert!(!debug.contains(&to_hex(&hash.salt)));
+    }
+
+    #[test]
+    fn locked_after_max_failed_attempts() {
+        let mut credentials = Credentials::default();
+        for _ in 0..MAX_FAILED_ATTEMPTS - 1 {
+            credentials.failed_attempts += 1;
+            assert!(!credentials.is_locked());
+        }
+        credentials.failed_attempts += 1;
+        assert!(credentials.is_locked());
+    }
+}
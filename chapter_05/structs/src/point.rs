@@ -0,0 +1,309 @@
+/* Points and vectors in three dimensions.
+ *
+ * Point is still the book's tuple struct, 'Point(0, 0, 0)', but it is generic
+ * over the type of its coordinates: Point<i32> for whole numbers, as in the
+ * book, and Point<f64> for everything else. A Point doubles as a vector, the
+ * arrow from the origin to the point, so two points can be added and
+ * subtracted and a point multiplied by a number, with the usual operators.
+ *
+ * Dot and cross products work on either kind and give back the same kind.
+ * Lengths and distances are square roots, so they are always f64; integer
+ * points are converted first. As with plain i32s, integer arithmetic that
+ * overflows panics in debug builds. */
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point<T = f64>(pub T, pub T, pub T);
+
+impl<T> Point<T> {
+    pub fn new(x: T, y: T, z: T) -> Point<T> {
+        Point(x, y, z)
+    }
+}
+
+impl<T: Default> Point<T> {
+    pub fn origin() -> Point<T> {
+        Point::default()
+    }
+}
+
+impl<T> Point<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    pub fn dot(self, other: Point<T>) -> T {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2
+    }
+
+    /* At right angles to both vectors, so its dot product with either is 0.
+     * Swapping the two vectors flips it round. */
+    pub fn cross(self, other: Point<T>) -> Point<T> {
+        Point(
+            self.1 * other.2 - self.2 * other.1,
+            self.2 * other.0 - self.0 * other.2,
+            self.0 * other.1 - self.1 * other.0,
+        )
+    }
+}
+
+impl<T: Into<f64>> Point<T> {
+    pub fn to_f64(self) -> Point<f64> {
+        Point(self.0.into(), self.1.into(), self.2.into())
+    }
+}
+
+impl<T: Into<f64> + Copy> Point<T> {
+    /* The distance from the origin. */
+    pub fn length(self) -> f64 {
+        let p = self.to_f64();
+        p.dot(p).sqrt()
+    }
+
+    pub fn distance(self, other: Point<T>) -> f64 {
+        (other.to_f64() - self.to_f64()).length()
+    }
+}
+
+impl Point<f64> {
+    /* The vector pointing the same way with a length of 1, or None for the
+     * zero vector, which points nowhere, and for vectors with infinite or
+     * NaN coordinates. Dividing by the largest coordinate first keeps the
+     * squares in 'length' from overflowing for huge vectors, or coming out
+     * as 0 for tiny ones. */
+    pub fn normalize(self) -> Option<Point<f64>> {
+        let coords = [self.0, self.1, self.2];
+        if !coords.iter().all(|c| c.is_finite()) {
+            return None;
+        }
+        let largest = coords
+            .iter()
+            .fold(0.0, |largest: f64, c| largest.max(c.abs()));
+        if largest == 0.0 {
+            return None;
+        }
+        let scaled = self / largest;
+        Some(scaled / scaled.length())
+    }
+}
+
+impl<T> From<(T, T, T)> for Point<T> {
+    fn from((x, y, z): (T, T, T)) -> Point<T> {
+        Point(x, y, z)
+    }
+}
+
+impl<T> From<Point<T>> for (T, T, T) {
+    fn from(Point(x, y, z): Point<T>) -> (T, T, T) {
+        (x, y, z)
+    }
+}
+
+impl<T: Add<Output = T>> Add for Point<T> {
+    type Output = Point<T>;
+
+    fn add(self, other: Point<T>) -> Point<T> {
+        Point(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Point<T> {
+    type Output = Point<T>;
+
+    fn sub(self, other: Point<T>) -> Point<T> {
+        Point(self.0 - other.0, self.1 - other.1, self.2 - other.2)
+    }
+}
+
+impl<T: Neg<Output = T>> Neg for Point<T> {
+    type Output = Point<T>;
+
+    fn neg(self) -> Point<T> {
+        Point(-self.0, -self.1, -self.2)
+    }
+}
+
+/* Scaling by a number: 'point * 2'. */
+impl<T: Copy + Mul<Output = T>> Mul<T> for Point<T> {
+    type Output = Point<T>;
+
+    fn mul(self, scalar: T) -> Point<T> {
+        Point(self.0 * scalar, self.1 * scalar, self.2 * scalar)
+    }
+}
+
+impl<T: Copy + Div<Output = T>> Div<T> for Point<T> {
+    type Output = Point<T>;
+
+    fn div(self, scalar: T) -> Point<T> {
+        Point(self.0 / scalar, self.1 / scalar, self.2 / scalar)
+    }
+}
+
+impl<T: AddAssign> AddAssign for Point<T> {
+    fn add_assign(&mut self, other: Point<T>) {
+        self.0 += other.0;
+        self.1 += other.1;
+        self.2 += other.2;
+    }
+}
+
+impl<T: SubAssign> SubAssign for Point<T> {
+    fn sub_assign(&mut self, other: Point<T>) {
+        self.0 -= other.0;
+        self.1 -= other.1;
+        self.2 -= other.2;
+    }
+}
+
+impl<T: Copy + MulAssign> MulAssign<T> for Point<T> {
+    fn mul_assign(&mut self, scalar: T) {
+        self.0 *= scalar;
+        self.1 *= scalar;
+        self.2 *= scalar;
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.0, self.1, self.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-12;
+
+    /* A small xorshift generator with a fixed seed, so that every run
+     * checks the same points and a failure can be repeated. */
+    struct Random(u64);
+
+    impl Random {
+        fn new() -> Random {
+            Random(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /* Between -limit and limit. */
+        fn int(&mut self, limit: i64) -> i64 {
+            (self.next() % (2 * limit as u64 + 1)) as i64 - limit
+        }
+
+        /* From -1 up to 1. */
+        fn float(&mut self) -> f64 {
+            (self.next() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+        }
+
+        /* Points with small coordinates, which are often 0 or parallel,
+         * and large ones, whose products need the full width of an i64
+         * (a dot product with a cross product is of the order limit^3). */
+        fn points(&mut self, count: usize) -> Vec<Point<i64>> {
+            (0..count)
+                .map(|i| {
+                    let limit = if i % 2 == 0 { 3 } else { 1_000_000 };
+                    Point(self.int(limit), self.int(limit), self.int(limit))
+                })
+                .collect()
+        }
+
+        /* Points anywhere from about 1e-300 to 1e300 from the origin. */
+        fn float_point(&mut self) -> Point<f64> {
+            let scale = 10f64.powi(self.int(300) as i32);
+            Point(self.float(), self.float(), self.float()) * scale
+        }
+    }
+
+    #[test]
+    fn cross_product_is_anti_commutative() {
+        let points = Random::new().points(200);
+        for &a in &points {
+            for &b in &points {
+                assert_eq!(a.cross(b), -b.cross(a), "{} x {}", a, b);
+            }
+            assert_eq!(a.cross(a), Point::origin());
+        }
+        assert_eq!(Point(1, 0, 0).cross(Point(0, 1, 0)), Point(0, 0, 1));
+    }
+
+    #[test]
+    fn cross_product_is_at_right_angles() {
+        let points = Random::new().points(200);
+        for &a in &points {
+            for &b in &points {
+                let c = a.cross(b);
+                assert_eq!(c.dot(a), 0, "{} x {}", a, b);
+                assert_eq!(c.dot(b), 0, "{} x {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_product_is_symmetric() {
+        let points = Random::new().points(200);
+        for &a in &points {
+            for &b in &points {
+                assert_eq!(a.dot(b), b.dot(a), "{} . {}", a, b);
+            }
+        }
+        assert_eq!(Point(1, 2, 3).dot(Point(-4, 5, -6)), -12);
+        let a = Point(0.5, -1.25, 2.0);
+        let b = Point(3.0, 0.1, -7.5);
+        assert_eq!(a.dot(b), b.dot(a));
+    }
+
+    #[test]
+    fn normalized_vectors_have_unit_length() {
+        let mut random = Random::new();
+        for _ in 0..10_000 {
+            let p = random.float_point();
+            let unit = match p.normalize() {
+                Some(unit) => unit,
+                None => {
+                    assert_eq!(p, Point::origin());
+                    continue;
+                }
+            };
+            assert!((unit.length() - 1.0).abs() < EPSILON, "{}", p);
+            /* Still pointing the same way. Compared at a scale where the
+             * length of 'p' can be computed. */
+            let largest = p.0.abs().max(p.1.abs()).max(p.2.abs());
+            let q = p / largest;
+            assert!(unit.cross(q).length() < EPSILON * q.length(), "{}", p);
+            assert!(unit.dot(q) > 0.0, "{}", p);
+        }
+        for &p in &[
+            Point(1e-200, 0.0, 0.0),
+            Point(1e200, -1e200, 1e200),
+            Point(f64::MAX, f64::MAX, 0.0),
+            Point(f64::MIN_POSITIVE / 4.0, 0.0, 0.0),
+        ] {
+            let unit = p.normalize().unwrap();
+            assert!((unit.length() - 1.0).abs() < EPSILON, "{}", p);
+        }
+    }
+
+    #[test]
+    fn zero_and_non_finite_vectors_do_not_normalize() {
+        assert_eq!(Point::<f64>::origin().normalize(), None);
+        assert_eq!(Point(-0.0, 0.0, -0.0).normalize(), None);
+        assert_eq!(Point(f64::INFINITY, 0.0, 0.0).normalize(), None);
+        assert_eq!(Point(f64::NAN, 1.0, 0.0).normalize(), None);
+        assert_eq!(Point(1.0, f64::NAN, 0.0).normalize(), None);
+    }
+
+    #[test]
+    fn lengths_and_distances() {
+        assert_eq!(Point(3, 4, 0).length(), 5.0);
+        assert_eq!(Point(1, 2, 3).distance(Point(4, 6, 3)), 5.0);
+        assert_eq!(Point(2.0, 3.0, 6.0).length(), 7.0);
+    }
+}
@@ -0,0 +1,285 @@
+/* Roles and what they allow.
+ *
+ * A user holds one or more roles. Each role grants some actions on some
+ * resources and inherits everything granted to the role below it:
+ *
+ *   viewer   read content
+ *   editor   create, update and delete content, read users
+ *   admin    everything
+ *
+ * so an admin can do all an editor can, and an editor all a viewer can.
+ * 'User::can' answers whether a user may do something; an inactive user may
+ * do nothing, whatever their roles. */
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::User;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Read,
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Content,
+    Users,
+    Settings,
+}
+
+impl Role {
+    /* The role whose permissions this one also has. */
+    pub fn inherits(self) -> Option<Role> {
+        match self {
+            Role::Viewer => None,
+            Role::Editor => Some(Role::Viewer),
+            Role::Admin => Some(Role::Editor),
+        }
+    }
+
+    /* Whether this role, or one it inherits from, allows the action. */
+    pub fn allows(self, action: Action, resource: Resource) -> bool {
+        self.grants(action, resource)
+            || self
+                .inherits()
+                .is_some_and(|role| role.allows(action, resource))
+    }
+
+    /* What this role adds to the one it inherits from. */
+    fn grants(self, action: Action, resource: Resource) -> bool {
+        match self {
+            Role::Viewer => action == Action::Read && resource == Resource::Content,
+            Role::Editor => {
+                resource == Resource::Content
+                    || (action == Action::Read && resource == Resource::Users)
+            }
+            Role::Admin => true,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRole(pub String);
+
+impl fmt::Display for UnknownRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown role {:?}, expected admin, editor or viewer",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownRole {}
+
+impl FromStr for Role {
+    type Err = UnknownRole;
+
+    fn from_str(text: &str) -> Result<Role, UnknownRole> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            _ => Err(UnknownRole(text.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/* The roles of one user. New users are viewers. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roles(BTreeSet<Role>);
+
+impl Default for Roles {
+    fn default() -> Roles {
+        Roles::from(Role::Viewer)
+    }
+}
+
+impl Roles {
+    /* No roles at all, so no permissions. */
+    pub fn none() -> Roles {
+        Roles(BTreeSet::new())
+    }
+
+    pub fn contains(&self, role: Role) -> bool {
+        self.0.contains(&role)
+    }
+
+    /* Returns whether the role was newly added. */
+    pub fn insert(&mut self, role: Role) -> bool {
+        self.0.insert(role)
+    }
+
+    /* Returns whether the role was there to remove. */
+    pub fn remove(&mut self, role: Role) -> bool {
+        self.0.remove(&role)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /* Lowest role first. */
+    pub fn iter(&self) -> impl Iterator<Item = Role> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn allow(&self, action: Action, resource: Resource) -> bool {
+        self.iter().any(|role| role.allows(action, resource))
+    }
+}
+
+impl From<Role> for Roles {
+    fn from(role: Role) -> Roles {
+        Roles(std::iter::once(role).collect())
+    }
+}
+
+impl std::iter::FromIterator<Role> for Roles {
+    fn from_iter<I: IntoIterator<Item = Role>>(roles: I) -> Roles {
+        Roles(roles.into_iter().collect())
+    }
+}
+
+/* Roles are written separated by ';', as in "editor;viewer". */
+impl fmt::Display for Roles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.iter().map(Role::name).collect();
+        f.write_str(&names.join(";"))
+    }
+}
+
+impl FromStr for Roles {
+    type Err = UnknownRole;
+
+    fn from_str(text: &str) -> Result<Roles, UnknownRole> {
+        text.split(';')
+            .filter(|name| !name.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl User {
+    pub fn can(&self, action: Action, resource: Resource) -> bool {
+        self.active && self.roles.allow(action, resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLES: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    /* Whether a viewer, an editor and an admin may do each thing. */
+    const PERMISSIONS: [(Action, Resource, [bool; 3]); 12] = [
+        (Action::Read, Resource::Content, [true, true, true]),
+        (Action::Create, Resource::Content, [false, true, true]),
+        (Action::Update, Resource::Content, [false, true, true]),
+        (Action::Delete, Resource::Content, [false, true, true]),
+        (Action::Read, Resource::Users, [false, true, true]),
+        (Action::Create, Resource::Users, [false, false, true]),
+        (Action::Update, Resource::Users, [false, false, true]),
+        (Action::Delete, Resource::Users, [false, false, true]),
+        (Action::Read, Resource::Settings, [false, false, true]),
+        (Action::Create, Resource::Settings, [false, false, true]),
+        (Action::Update, Resource::Settings, [false, false, true]),
+        (Action::Delete, Resource::Settings, [false, false, true]),
+    ];
+
+    fn user(roles: Roles, active: bool) -> User {
+        User::builder()
+            .username("ann".parse().unwrap())
+            .email("ann@example.com".parse().unwrap())
+            .roles(roles)
+            .active(active)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn each_role_inherits_from_the_one_below() {
+        assert_eq!(Role::Viewer.inherits(), None);
+        assert_eq!(Role::Editor.inherits(), Some(Role::Viewer));
+        assert_eq!(Role::Admin.inherits(), Some(Role::Editor));
+        for &(action, resource, _) in &PERMISSIONS {
+            for &role in &ROLES {
+                if let Some(lower) = role.inherits() {
+                    assert!(
+                        !lower.allows(action, resource) || role.allows(action, resource),
+                        "{} may not {:?} {:?} though {} may",
+                        role,
+                        action,
+                        resource,
+                        lower
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn what_each_role_can_do() {
+        for &(action, resource, allowed) in &PERMISSIONS {
+            for (&role, &allowed) in ROLES.iter().zip(&allowed) {
+                let active = user(Roles::from(role), true);
+                let inactive = user(Roles::from(role), false);
+                assert_eq!(
+                    active.can(action, resource),
+                    allowed,
+                    "{} {:?} {:?}",
+                    role,
+                    action,
+                    resource
+                );
+                assert!(!inactive.can(action, resource));
+            }
+            /* Several roles allow what any one of them does; none allow
+             * nothing. */
+            let all = user(ROLES.iter().copied().collect(), true);
+            assert_eq!(all.can(action, resource), allowed[2]);
+            assert!(!user(Roles::none(), true).can(action, resource));
+        }
+    }
+
+    #[test]
+    fn roles_are_written_lowest_first() {
+        let roles: Roles = " Admin;viewer;;admin".parse().unwrap();
+        assert_eq!(roles.to_string(), "viewer;admin");
+        assert_eq!(roles.to_string().parse(), Ok(roles));
+        assert_eq!("".parse(), Ok(Roles::none()));
+        assert_eq!(
+            "viewer;owner".parse::<Roles>(),
+            Err(UnknownRole(String::from("owner")))
+        );
+    }
+}
@@ -0,0 +1,488 @@
+/* Sessions for signed-in users.
+ *
+ * Signing in with a password hands back a session token: a long random
+ * string that stands in for the password on later requests. The token
+ * carries no meaning of its own; 'Sessions' looks it up to find who it
+ * belongs to. A session that goes unused for longer than the idle timeout
+ * expires. Each use also checks the user in the store, so a session stops
+ * working as soon as its user is deactivated, renamed or removed, however
+ * that was done. Sessions hold the user's 'UserId' as well as their name,
+ * so a new user who takes over a freed-up name does not inherit the old
+ * user's sessions.
+ *
+ * Time comes from a 'Clock' rather than straight from the system, so that
+ * expiry can be tried out with a 'ManualClock' instead of by waiting. */
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use rand::RngCore;
+
+use crate::store::{StoreError, UserId, UserStore};
+use crate::Username;
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const TOKEN_BYTES: usize = 32;
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/* A clock that only moves when told to. */
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Cell<SystemTime>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> ManualClock {
+        ManualClock {
+            now: Cell::new(start),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    /* No session has this token, or it was revoked. */
+    UnknownToken,
+    Expired,
+    /* Signing in failed. */
+    Store(StoreError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::UnknownToken => write!(f, "unknown session"),
+            SessionError::Expired => write!(f, "session expired"),
+            SessionError::Store(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SessionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SessionError::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for SessionError {
+    fn from(e: StoreError) -> SessionError {
+        SessionError::Store(e)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SessionToken(String);
+
+impl SessionToken {
+    fn generate() -> SessionToken {
+        let mut bytes = [0; TOKEN_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        SessionToken(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/* Shows only the start of the token, so logging a session does not leak
+ * something that can be used to take it over. */
+impl fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SessionToken({}...)", &self.0[..8])
+    }
+}
+
+impl fmt::Display for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub token: SessionToken,
+    pub user_id: UserId,
+    pub username: Username,
+    pub created: SystemTime,
+    pub last_used: SystemTime,
+}
+
+#[derive(Debug)]
+pub struct Sessions<C: Clock = SystemClock> {
+    clock: C,
+    idle_timeout: Duration,
+    sessions: HashMap<String, Session>,
+}
+
+impl Sessions<SystemClock> {
+    pub fn new() -> Sessions<SystemClock> {
+        Sessions::with_clock(SystemClock, DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+impl Default for Sessions<SystemClock> {
+    fn default() -> Sessions<SystemClock> {
+        Sessions::new()
+    }
+}
+
+impl<C: Clock> Sessions<C> {
+    pub fn with_clock(clock: C, idle_timeout: Duration) -> Sessions<C> {
+        Sessions {
+            clock,
+            idle_timeout,
+            sessions: HashMap::new(),
+        }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /* Number of sessions, including expired ones not yet cleared out. */
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /* Checks the password through the store and starts a session. */
+    pub fn sign_in(
+        &mut self,
+        store: &mut UserStore,
+        username: &str,
+        password: &str,
+    ) -> Result<SessionToken, SessionError> {
+        store.sign_in(username, password)?;
+        Ok(self.start(store, username))
+    }
+
+    /* Starts a session for a user whose password has already been
+     * checked, as 'AuditedStore::start_session' does. */
+    pub(crate) fn start(&mut self, store: &UserStore, username: &str) -> SessionToken {
+        let user_id = store.id(username).unwrap();
+        let username = store.get(username).unwrap().username.clone();
+        let now = self.clock.now();
+        let token = SessionToken::generate();
+        self.sessions.insert(
+            token.0.clone(),
+            Session {
+                token: token.clone(),
+                user_id,
+                username,
+                created: now,
+                last_used: now,
+            },
+        );
+        token
+    }
+
+    /* Looks up the session for a token and marks it as used. A session
+     * that has expired, or whose user is no longer in the store under the
+     * same name and id or is inactive, is removed. */
+    pub fn validate(&mut self, store: &UserStore, token: &str) -> Result<&Session, SessionError> {
+        let now = self.clock.now();
+        let session = self.sessions.get(token).ok_or(SessionError::UnknownToken)?;
+        let refused = if is_idle(session, now, self.idle_timeout) {
+            Some(SessionError::Expired)
+        } else {
+            let username = session.username.as_str();
+            match store.get(username) {
+                Some(_) if store.id(username) != Some(session.user_id) => {
+                    Some(StoreError::NotFound(username.to_string()).into())
+                }
+                None => Some(StoreError::NotFound(username.to_string()).into()),
+                Some(user) if !user.active => {
+                    Some(StoreError::Inactive(user.username.to_string()).into())
+                }
+                Some(_) => None,
+            }
+        };
+        if let Some(e) = refused {
+            self.sessions.remove(token);
+            return Err(e);
+        }
+        let session = self.sessions.get_mut(token).unwrap();
+        session.last_used = now;
+        Ok(session)
+    }
+
+    /* Ends one session. Returns whether there was one to end. */
+    pub fn revoke(&mut self, token: &str) -> bool {
+        self.sessions.remove(token).is_some()
+    }
+
+    /* Ends every session of the user who has this username in the store
+     * and returns how many there were. */
+    pub fn revoke_all(&mut self, store: &UserStore, username: &str) -> usize {
+        let user_id = match store.id(username) {
+            Some(id) => id,
+            None => return 0,
+        };
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, session| session.user_id != user_id);
+        before - self.sessions.len()
+    }
+
+    /* Deactivates a user in the store and ends their sessions. Sessions of
+     * an inactive user are refused by 'validate' anyway; this also clears
+     * them out. */
+    pub fn deactivate(
+        &mut self,
+        store: &mut UserStore,
+        username: &str,
+    ) -> Result<usize, SessionError> {
+        store.deactivate(username)?;
+        Ok(self.revoke_all(store, username))
+    }
+
+    /* Removes sessions that have been idle too long and returns how many
+     * were removed. */
+    pub fn expire_idle(&mut self) -> usize {
+        let now = self.clock.now();
+        let idle_timeout = self.idle_timeout;
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, session| !is_idle(session, now, idle_timeout));
+        before - self.sessions.len()
+    }
+
+    /* The live sessions of a user. */
+    pub fn for_user<'a>(
+        &'a self,
+        store: &UserStore,
+        username: &str,
+    ) -> impl Iterator<Item = &'a Session> + 'a {
+        let user_id = store.id(username);
+        let now = self.clock.now();
+        let idle_timeout = self.idle_timeout;
+        self.sessions.values().filter(move |session| {
+            Some(session.user_id) == user_id && !is_idle(session, now, idle_timeout)
+        })
+    }
+}
+
+fn is_idle(session: &Session, now: SystemTime, idle_timeout: Duration) -> bool {
+    /* A clock that went backwards counts as no time passing. */
+    now.duration_since(session.last_used)
+        .is_ok_and(|idle| idle > idle_timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_user;
+    use crate::password::{PasswordHash, MIN_ITERATIONS};
+
+    const TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+    fn setup() -> (UserStore, Sessions<ManualClock>) {
+        let mut store = UserStore::new();
+        for name in &["ann", "bob"] {
+            let email = format!("{}@example.com", name);
+            store
+                .create(build_user(email.parse().unwrap(), name.parse().unwrap()))
+                .unwrap();
+            let hash = PasswordHash::with_iterations("correct horse", MIN_ITERATIONS).unwrap();
+            store.set_password(name, hash).unwrap();
+        }
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        (store, Sessions::with_clock(clock, TIMEOUT))
+    }
+
+    #[test]
+    fn using_a_session_keeps_it_alive() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        for _ in 0..3 {
+            sessions.clock().advance(TIMEOUT);
+            let session = sessions.validate(&store, token.as_str()).unwrap();
+            assert_eq!(session.username.as_str(), "ann");
+            assert_eq!(session.created, SystemTime::UNIX_EPOCH);
+        }
+    }
+
+    #[test]
+    fn idle_session_expires() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        sessions.clock().advance(TIMEOUT + Duration::from_secs(1));
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::Expired)
+        );
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn expire_idle_removes_only_idle_sessions() {
+        let (mut store, mut sessions) = setup();
+        sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        sessions.clock().advance(TIMEOUT);
+        let token = sessions
+            .sign_in(&mut store, "bob", "correct horse")
+            .unwrap();
+        sessions.clock().advance(Duration::from_secs(1));
+        assert_eq!(sessions.for_user(&store, "ann").count(), 0);
+        assert_eq!(sessions.expire_idle(), 1);
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions.validate(&store, token.as_str()).is_ok());
+    }
+
+    #[test]
+    fn revoked_sessions_stop_working() {
+        let (mut store, mut sessions) = setup();
+        let first = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        let second = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        let other = sessions
+            .sign_in(&mut store, "bob", "correct horse")
+            .unwrap();
+        assert!(sessions.revoke(first.as_str()));
+        assert!(!sessions.revoke(first.as_str()));
+        assert_eq!(
+            sessions.validate(&store, first.as_str()),
+            Err(SessionError::UnknownToken)
+        );
+        assert!(sessions.validate(&store, second.as_str()).is_ok());
+        sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        assert_eq!(sessions.revoke_all(&store, "ANN"), 2);
+        assert_eq!(
+            sessions.validate(&store, second.as_str()),
+            Err(SessionError::UnknownToken)
+        );
+        assert!(sessions.validate(&store, other.as_str()).is_ok());
+    }
+
+    #[test]
+    fn inactive_user_session_is_refused() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        store.deactivate("ann").unwrap();
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::Store(StoreError::Inactive("ann".to_string())))
+        );
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn renamed_user_session_is_not_passed_on() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        store.rename("ann", "anne".parse().unwrap()).unwrap();
+        store
+            .create(build_user(
+                "new.ann@example.com".parse().unwrap(),
+                "ann".parse().unwrap(),
+            ))
+            .unwrap();
+        assert_eq!(sessions.for_user(&store, "ann").count(), 0);
+        assert_eq!(sessions.for_user(&store, "anne").count(), 1);
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::Store(StoreError::NotFound("ann".to_string())))
+        );
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn removed_user_session_is_not_passed_on() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        let mut ann = store.remove("ann").unwrap();
+        ann.sign_in_count = 0;
+        store.create(ann).unwrap();
+        assert_eq!(sessions.revoke_all(&store, "ann"), 0);
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::Store(StoreError::NotFound("ann".to_string())))
+        );
+    }
+
+    #[test]
+    fn removed_user_session_is_refused() {
+        let (mut store, mut sessions) = setup();
+        let token = sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        store.remove("ann").unwrap();
+        assert_eq!(
+            sessions.validate(&store, token.as_str()),
+            Err(SessionError::Store(StoreError::NotFound("ann".to_string())))
+        );
+    }
+
+    #[test]
+    fn deactivate_ends_sessions() {
+        let (mut store, mut sessions) = setup();
+        sessions
+            .sign_in(&mut store, "ann", "correct horse")
+            .unwrap();
+        sessions
+            .sign_in(&mut store, "bob", "correct horse")
+            .unwrap();
+        assert_eq!(sessions.deactivate(&mut store, "ann"), Ok(1));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(
+            sessions.sign_in(&mut store, "ann", "correct horse"),
+            Err(SessionError::Store(StoreError::Inactive("ann".to_string())))
+        );
+    }
+}
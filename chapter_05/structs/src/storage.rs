@@ -0,0 +1,557 @@
+/* Saving users so they outlive the process.
+ *
+ * A 'Storage' keeps a snapshot of every user. 'FileStorage' writes them as
+ * JSON lines: a header line giving the schema version, then one user per
+ * line.
+ *
+ *   {"schema":3}
+ *   {"username":"abc","email":"abc@xyz.com","sign_in_count":1,"active":true,
+ *    "password":"pbkdf2-sha256$...","failed_attempts":0,"roles":["viewer"]}
+ *
+ * (shown here over two lines). Version 1 had no password or failed attempts,
+ * and versions before 3 had no roles.
+ *
+ * Saving writes a temporary file next to the real one and renames it into
+ * place, so a crash part way through leaves the old file intact. The file
+ * holds password hashes, so on Unix it is created readable and writable by
+ * its owner only (mode 0600).
+ *
+ * When the fields of User change, SCHEMA_VERSION goes up and 'decode_user'
+ * learns to read the older versions, so existing files keep loading. Files
+ * written by a newer version than this one are refused rather than misread.
+ *
+ * 'MemoryStorage' keeps the snapshot in memory, for trying things out
+ * without touching the disk. */
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::json::{self, Value};
+use crate::password::PasswordError;
+use crate::store::{StoreError, UserStore};
+use crate::validation::ValidationError;
+use crate::{Credentials, Email, Roles, User, Username};
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    MissingHeader,
+    UnsupportedVersion(u64),
+    /* A line that is not valid JSON or not an object. */
+    Syntax { line: usize, message: String },
+    MissingField { line: usize, field: &'static str },
+    Invalid { line: usize, error: ValidationError },
+    /* A stored password hash that is malformed or too weak to accept. */
+    Password { line: usize, error: PasswordError },
+    /* The users read are fine on their own but clash with each other. */
+    Conflict(StoreError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io(e) => write!(f, "{}", e),
+            StorageError::MissingHeader => write!(f, "missing schema header"),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "schema version {} is newer than the supported version {}",
+                version, SCHEMA_VERSION
+            ),
+            StorageError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            StorageError::MissingField { line, field } => {
+                write!(f, "line {}: missing or invalid field {:?}", line, field)
+            }
+            StorageError::Invalid { line, error } => write!(f, "line {}: {}", line, error),
+            StorageError::Password { line, error } => write!(f, "line {}: {}", line, error),
+            StorageError::Conflict(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StorageError::Io(e) => Some(e),
+            StorageError::Invalid { error, .. } => Some(error),
+            StorageError::Password { error, .. } => Some(error),
+            StorageError::Conflict(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> StorageError {
+        StorageError::Io(e)
+    }
+}
+
+pub trait Storage {
+    /* Reads every saved user. Storage that has never been saved to holds
+     * no users. */
+    fn load(&self) -> Result<Vec<User>, StorageError>;
+
+    /* Replaces everything saved with 'users'. */
+    fn save(&mut self, users: &[User]) -> Result<(), StorageError>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    users: Vec<User>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self) -> Result<Vec<User>, StorageError> {
+        Ok(self.users.clone())
+    }
+
+    fn save(&mut self, users: &[User]) -> Result<(), StorageError> {
+        self.users = users.to_vec();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileStorage {
+        FileStorage { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /* "users.jsonl" is written as "users.jsonl.tmp" first. */
+    fn temp_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        self.path.with_file_name(name)
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self) -> Result<Vec<User>, StorageError> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => from_json_lines(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&mut self, users: &[User]) -> Result<(), StorageError> {
+        let temp = self.temp_path();
+        let result = create_private(&temp).and_then(|mut file| {
+            file.write_all(to_json_lines(users).as_bytes())?;
+            file.sync_all()
+        });
+        if let Err(e) = result.and_then(|_| fs::rename(&temp, &self.path)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/* Creates a new file that only its owner can read. A temporary file left
+ * behind by an earlier crash is replaced, since it may have been created
+ * with wider permissions. */
+fn create_private(path: &Path) -> io::Result<File> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+impl UserStore {
+    /* Builds a store from the users saved in 'storage'. */
+    pub fn load<S: Storage + ?Sized>(storage: &S) -> Result<UserStore, StorageError> {
+        let mut store = UserStore::new();
+        for user in storage.load()? {
+            store.create(user).map_err(StorageError::Conflict)?;
+        }
+        Ok(store)
+    }
+
+    pub fn save<S: Storage + ?Sized>(&self, storage: &mut S) -> Result<(), StorageError> {
+        let users: Vec<User> = self.iter().cloned().collect();
+        storage.save(&users)
+    }
+}
+
+pub fn to_json_lines(users: &[User]) -> String {
+    let header = Value::Object(vec![("schema".to_string(), Value::number(SCHEMA_VERSION))]);
+    let mut out = header.to_json();
+    out.push('\n');
+    for user in users {
+        out.push_str(&encode_user(user).to_json());
+        out.push('\n');
+    }
+    out
+}
+
+/* Reads users written by 'to_json_lines', by this or an earlier schema
+ * version. Blank lines are skipped. */
+pub fn from_json_lines(text: &str) -> Result<Vec<User>, StorageError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let version = match lines.next() {
+        Some((number, line)) => read_object(number, line)?
+            .get("schema")
+            .and_then(Value::as_u64)
+            .ok_or(StorageError::MissingHeader)?,
+        None => return Ok(Vec::new()),
+    };
+    if version > u64::from(SCHEMA_VERSION) {
+        return Err(StorageError::UnsupportedVersion(version));
+    }
+
+    lines
+        .map(|(number, line)| decode_user(number, &read_object(number, line)?, version as u32))
+        .collect()
+}
+
+fn read_object(number: usize, line: &str) -> Result<Value, StorageError> {
+    match json::parse(line) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        Ok(_) => Err(StorageError::Syntax {
+            line: number,
+            message: "expected an object".to_string(),
+        }),
+        Err(message) => Err(StorageError::Syntax {
+            line: number,
+            message,
+        }),
+    }
+}
+
+fn encode_user(user: &User) -> Value {
+    Value::Object(vec![
+        (
+            "username".to_string(),
+            Value::string(user.username.as_str()),
+        ),
+        ("email".to_string(), Value::string(user.email.as_str())),
+        (
+            "sign_in_count".to_string(),
+            Value::number(user.sign_in_count),
+        ),
+        ("active".to_string(), Value::Bool(user.active)),
+        (
+            "password".to_string(),
+            match &user.credentials.password {
+                Some(hash) => Value::string(hash.to_string()),
+                None => Value::Null,
+            },
+        ),
+        (
+            "failed_attempts".to_string(),
+            Value::number(user.credentials.failed_attempts),
+        ),
+        (
+            "roles".to_string(),
+            Value::Array(
+                user.roles
+                    .iter()
+                    .map(|role| Value::string(role.name()))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+/* Each schema version reads the fields it has; fields added in later
+ * versions get their defaults. Version 2 added the password and the count of
+ * failed attempts, and version 3 the roles. */
+fn decode_user(number: usize, record: &Value, version: u32) -> Result<User, StorageError> {
+    let missing = |field| StorageError::MissingField {
+        line: number,
+        field,
+    };
+    let invalid = |error| StorageError::Invalid {
+        line: number,
+        error,
+    };
+    let text = |field| {
+        record
+            .get(field)
+            .and_then(Value::as_str)
+            .ok_or_else(|| missing(field))
+    };
+
+    Ok(User {
+        username: Username::parse(text("username")?).map_err(invalid)?,
+        email: Email::parse(text("email")?).map_err(invalid)?,
+        sign_in_count: record
+            .get("sign_in_count")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| missing("sign_in_count"))?,
+        active: record
+            .get("active")
+            .and_then(Value::as_bool)
+            .ok_or_else(|| missing("active"))?,
+        credentials: if version >= 2 {
+            decode_credentials(number, record)?
+        } else {
+            Credentials::default()
+        },
+        roles: if version >= 3 {
+            decode_roles(record).ok_or_else(|| missing("roles"))?
+        } else {
+            Roles::default()
+        },
+    })
+}
+
+fn decode_credentials(number: usize, record: &Value) -> Result<Credentials, StorageError> {
+    let missing = |field| StorageError::MissingField {
+        line: number,
+        field,
+    };
+    let password = match record.get("password").ok_or_else(|| missing("password"))? {
+        Value::Null => None,
+        hash => {
+            let text = hash.as_str().ok_or_else(|| missing("password"))?;
+            Some(text.parse().map_err(|error| StorageError::Password {
+                line: number,
+                error,
+            })?)
+        }
+    };
+    Ok(Credentials {
+        password,
+        failed_attempts: record
+            .get("failed_attempts")
+            .and_then(Value::as_u64)
+            .and_then(|count| u32::try_from(count).ok())
+            .ok_or_else(|| missing("failed_attempts"))?,
+    })
+}
+
+fn decode_roles(record: &Value) -> Option<Roles> {
+    record
+        .get("roles")?
+        .as_array()?
+        .iter()
+        .map(|role| role.as_str()?.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_user;
+    use crate::password::{PasswordHash, MIN_ITERATIONS};
+
+    fn user_with_password() -> User {
+        let mut user = build_user("ann@example.com".parse().unwrap(), "ann".parse().unwrap());
+        user.credentials.password =
+            Some(PasswordHash::with_iterations("correct horse", MIN_ITERATIONS).unwrap());
+        user
+    }
+
+    #[test]
+    fn users_round_trip_through_json_lines() {
+        let users = vec![user_with_password()];
+        assert_eq!(from_json_lines(&to_json_lines(&users)).unwrap(), users);
+    }
+
+    #[test]
+    fn failed_attempts_out_of_range_is_refused() {
+        let text = to_json_lines(&[user_with_password()]);
+        let huge = text.replace("\"failed_attempts\":0", "\"failed_attempts\":4294967296");
+        assert_ne!(huge, text);
+        match from_json_lines(&huge) {
+            Err(StorageError::MissingField {
+                line: 2,
+                field: "failed_attempts",
+            }) => {}
+            other => panic!("expected a field error, found {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("structs-storage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("users.jsonl");
+        /* A stale temporary file readable by everyone must not be reused. */
+        fs::write(dir.join("users.jsonl.tmp"), "").unwrap();
+        fs::set_permissions(
+            dir.join("users.jsonl.tmp"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        let users = vec![user_with_password()];
+        let mut storage = FileStorage::new(&path);
+        storage.save(&users).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        let loaded = storage.load();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(loaded.unwrap(), users);
+    }
+
+    #[test]
+    fn weakened_stored_hash_is_refused() {
+        let text = to_json_lines(&[user_with_password()]);
+        let weakened = text.replacen(
+            &format!("pbkdf2-sha256${}$", MIN_ITERATIONS),
+            "pbkdf2-sha256$1$",
+            1,
+        );
+        match from_json_lines(&weakened) {
+            Err(StorageError::Password {
+                line: 2,
+                error: PasswordError::TooFewIterations(1),
+            }) => {}
+            other => panic!("expected a password error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn version_1_file_loads_with_defaults() {
+        let text = r#"{"schema":1}
+{"username":"ann","email":"ann@example.com","sign_in_count":3,"active":true}
+
+{"username":"Bob","email":"BOB@example.com","sign_in_count":0,"active":false}
+"#;
+        let users = from_json_lines(text).unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].username.as_str(), "ann");
+        assert_eq!(users[0].sign_in_count, 3);
+        assert_eq!(users[0].credentials, Credentials::default());
+        assert_eq!(users[0].roles, Roles::default());
+        assert_eq!(users[1].email.as_str(), "bob@example.com");
+        assert!(!users[1].active);
+    }
+
+    #[test]
+    fn version_2_file_loads_credentials_but_no_roles() {
+        let hash = format!(
+            "pbkdf2-sha256${}${}${}",
+            MIN_ITERATIONS,
+            "0".repeat(32),
+            "0".repeat(64)
+        );
+        let text = format!(
+            "{{\"schema\":2}}\n\
+             {{\"username\":\"ann\",\"email\":\"ann@example.com\",\"sign_in_count\":1,\
+             \"active\":true,\"password\":\"{}\",\"failed_attempts\":2}}\n\
+             {{\"username\":\"bob\",\"email\":\"bob@example.com\",\"sign_in_count\":0,\
+             \"active\":true,\"password\":null,\"failed_attempts\":0}}\n",
+            hash
+        );
+        let users = from_json_lines(&text).unwrap();
+        assert_eq!(users[0].credentials.password, Some(hash.parse().unwrap()));
+        assert_eq!(users[0].credentials.failed_attempts, 2);
+        assert_eq!(users[0].roles, Roles::default());
+        assert_eq!(users[1].credentials, Credentials::default());
+
+        /* A version 2 user must say whether it has a password. */
+        let no_password = text.replace("\"password\":null,", "");
+        match from_json_lines(&no_password) {
+            Err(StorageError::MissingField {
+                line: 3,
+                field: "password",
+            }) => {}
+            other => panic!("expected a field error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn newer_or_headerless_files_are_refused() {
+        let text = to_json_lines(&[user_with_password()]);
+        let newer = text.replacen(
+            &format!("{{\"schema\":{}}}", SCHEMA_VERSION),
+            &format!("{{\"schema\":{}}}", SCHEMA_VERSION + 1),
+            1,
+        );
+        assert_ne!(newer, text);
+        match from_json_lines(&newer) {
+            Err(StorageError::UnsupportedVersion(version)) => {
+                assert_eq!(version, u64::from(SCHEMA_VERSION) + 1)
+            }
+            other => panic!("expected a version error, found {:?}", other),
+        }
+        let headerless = text.lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert!(matches!(
+            from_json_lines(&headerless),
+            Err(StorageError::MissingHeader)
+        ));
+        assert!(matches!(
+            from_json_lines("{\"schema\":3}\n[1]\n"),
+            Err(StorageError::Syntax { line: 2, .. })
+        ));
+        assert_eq!(from_json_lines("\n\n").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn memory_storage_round_trips_a_store() {
+        let mut storage = MemoryStorage::new();
+        assert!(UserStore::load(&storage).unwrap().is_empty());
+
+        let mut store = UserStore::new();
+        store.create(user_with_password()).unwrap();
+        store
+            .create(build_user(
+                "bob@example.com".parse().unwrap(),
+                "bob".parse().unwrap(),
+            ))
+            .unwrap();
+        store.save(&mut storage).unwrap();
+        let loaded = UserStore::load(&storage).unwrap();
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            store.iter().collect::<Vec<_>>()
+        );
+
+        /* Saving replaces what was there. */
+        store.remove("bob").unwrap();
+        store.save(&mut storage).unwrap();
+        assert_eq!(
+            storage.load().unwrap(),
+            vec![store.get("ann").unwrap().clone()]
+        );
+
+        /* Users that clash are refused when loading. */
+        let ann = storage.load().unwrap().remove(0);
+        storage.save(&[ann.clone(), ann]).unwrap();
+        assert!(matches!(
+            UserStore::load(&storage),
+            Err(StorageError::Conflict(StoreError::DuplicateUsername(_)))
+        ));
+    }
+}
@@ -0,0 +1,509 @@
+/* An in-memory store of users.
+ *
+ * Users are kept by username, and no two users may share a username or an
+ * email address. Lookups by text are case-insensitive, matching the
+ * normalization done by Username and Email. Changes go through the store's
+ * methods rather than through the User fields directly, so that these rules
+ * always hold.
+ *
+ * The store also gives each user a 'UserId' when they are created. Unlike
+ * the username it never changes, and it is never handed to another user,
+ * even one created later under the same name. Ids are not saved, so a store
+ * loaded from storage numbers its users afresh. */
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use crate::{Credentials, Email, PasswordHash, Role, User, Username};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    DuplicateUsername(String),
+    DuplicateEmail(String),
+    NotFound(String),
+    /* Inactive users cannot sign in. */
+    Inactive(String),
+    /* Too many wrong passwords; see 'unlock'. */
+    Locked(String),
+    NoPassword(String),
+    WrongPassword(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::DuplicateUsername(username) => {
+                write!(f, "username {:?} is already taken", username)
+            }
+            StoreError::DuplicateEmail(email) => write!(f, "email {:?} is already in use", email),
+            StoreError::NotFound(username) => write!(f, "no user named {:?}", username),
+            StoreError::Inactive(username) => write!(f, "user {:?} is not active", username),
+            StoreError::Locked(username) => write!(
+                f,
+                "user {:?} is locked after too many failed sign-ins",
+                username
+            ),
+            StoreError::NoPassword(username) => write!(f, "user {:?} has no password", username),
+            StoreError::WrongPassword(username) => {
+                write!(f, "wrong password for user {:?}", username)
+            }
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UserId(u64);
+
+#[derive(Debug, Clone, Default)]
+pub struct UserStore {
+    users: BTreeMap<String, User>,
+    /* Maps each email to the username it belongs to. */
+    emails: HashMap<String, String>,
+    /* Maps each username to the id of the user who has it. */
+    ids: HashMap<String, UserId>,
+    next_id: u64,
+}
+
+impl UserStore {
+    pub fn new() -> UserStore {
+        UserStore::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    /* All users, ordered by username. */
+    pub fn iter(&self) -> impl Iterator<Item = &User> {
+        self.users.values()
+    }
+
+    pub fn create(&mut self, user: User) -> Result<&User, StoreError> {
+        let username = user.username.to_string();
+        let email = user.email.to_string();
+        if self.users.contains_key(&username) {
+            return Err(StoreError::DuplicateUsername(username));
+        }
+        if self.emails.contains_key(&email) {
+            return Err(StoreError::DuplicateEmail(email));
+        }
+        self.emails.insert(email, username.clone());
+        self.ids.insert(username.clone(), UserId(self.next_id));
+        self.next_id += 1;
+        Ok(self.users.entry(username).or_insert(user))
+    }
+
+    pub fn get(&self, username: &str) -> Option<&User> {
+        self.users.get(&Username::normalize(username))
+    }
+
+    /* The id of the user who has this username now. */
+    pub fn id(&self, username: &str) -> Option<UserId> {
+        self.ids.get(&Username::normalize(username)).copied()
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Option<&User> {
+        self.emails
+            .get(&Email::normalize(email))
+            .and_then(|username| self.users.get(username))
+    }
+
+    pub fn update_email(&mut self, username: &str, email: Email) -> Result<&User, StoreError> {
+        let username = Username::normalize(username);
+        self.existing(&username)?;
+        match self.emails.get(email.as_str()) {
+            Some(owner) if *owner == username => return self.existing(&username),
+            Some(_) => return Err(StoreError::DuplicateEmail(email.to_string())),
+            None => {}
+        }
+        let user = self.existing_mut(&username)?;
+        let old = std::mem::replace(&mut user.email, email);
+        let new = user.email.to_string();
+        self.emails.remove(old.as_str());
+        self.emails.insert(new, username.clone());
+        self.existing(&username)
+    }
+
+    pub fn rename(&mut self, username: &str, new_username: Username) -> Result<&User, StoreError> {
+        let username = Username::normalize(username);
+        self.existing(&username)?;
+        if username == new_username.as_str() {
+            return self.existing(&username);
+        }
+        if self.users.contains_key(new_username.as_str()) {
+            return Err(StoreError::DuplicateUsername(new_username.to_string()));
+        }
+        let mut user = self.users.remove(&username).unwrap();
+        let key = new_username.to_string();
+        user.username = new_username;
+        self.emails.insert(user.email.to_string(), key.clone());
+        let id = self.ids.remove(&username).unwrap();
+        self.ids.insert(key.clone(), id);
+        Ok(self.users.entry(key).or_insert(user))
+    }
+
+    pub fn activate(&mut self, username: &str) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.active = true;
+        Ok(user)
+    }
+
+    pub fn deactivate(&mut self, username: &str) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.active = false;
+        Ok(user)
+    }
+
+    /* Adding a role the user already has does nothing. */
+    pub fn grant(&mut self, username: &str, role: Role) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.roles.insert(role);
+        Ok(user)
+    }
+
+    pub fn revoke(&mut self, username: &str, role: Role) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.roles.remove(role);
+        Ok(user)
+    }
+
+    /* Replaces the user's password. This also unlocks the user. */
+    pub fn set_password(
+        &mut self,
+        username: &str,
+        password: PasswordHash,
+    ) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.credentials = Credentials {
+            password: Some(password),
+            failed_attempts: 0,
+        };
+        Ok(user)
+    }
+
+    /* Lets a locked user try signing in again. */
+    pub fn unlock(&mut self, username: &str) -> Result<&User, StoreError> {
+        let user = self.existing_mut(username)?;
+        user.credentials.failed_attempts = 0;
+        Ok(user)
+    }
+
+    /* Checks the password and, when it is right, records a sign-in and
+     * returns the new sign-in count. Each wrong password counts towards
+     * locking the user; a right one starts the count again. */
+    pub fn sign_in(&mut self, username: &str, password: &str) -> Result<u64, StoreError> {
+        let user = self.existing_mut(username)?;
+        if !user.active {
+            return Err(StoreError::Inactive(user.username.to_string()));
+        }
+        if user.credentials.is_locked() {
+            return Err(StoreError::Locked(user.username.to_string()));
+        }
+        let verified = match &user.credentials.password {
+            Some(hash) => hash.verify(password),
+            None => return Err(StoreError::NoPassword(user.username.to_string())),
+        };
+        if !verified {
+            user.credentials.failed_attempts += 1;
+            return Err(StoreError::WrongPassword(user.username.to_string()));
+        }
+        user.credentials.failed_attempts = 0;
+        user.sign_in_count += 1;
+        Ok(user.sign_in_count)
+    }
+
+    pub fn remove(&mut self, username: &str) -> Result<User, StoreError> {
+        let user = self
+            .users
+            .remove(&Username::normalize(username))
+            .ok_or_else(|| StoreError::NotFound(username.to_string()))?;
+        self.emails.remove(user.email.as_str());
+        self.ids.remove(user.username.as_str());
+        Ok(user)
+    }
+
+    fn existing(&self, username: &str) -> Result<&User, StoreError> {
+        self.get(username)
+            .ok_or_else(|| StoreError::NotFound(username.to_string()))
+    }
+
+    fn existing_mut(&mut self, username: &str) -> Result<&mut User, StoreError> {
+        self.users
+            .get_mut(&Username::normalize(username))
+            .ok_or_else(|| StoreError::NotFound(username.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_user;
+    use crate::password::{MAX_FAILED_ATTEMPTS, MIN_ITERATIONS};
+
+    fn store_with_password(password: &str) -> UserStore {
+        let mut store = UserStore::new();
+        store
+            .create(build_user(
+                "ann@example.com".parse().unwrap(),
+                "ann".parse().unwrap(),
+            ))
+            .unwrap();
+        let hash = PasswordHash::with_iterations(password, MIN_ITERATIONS).unwrap();
+        store.set_password("ann", hash).unwrap();
+        store
+    }
+
+    #[test]
+    fn right_password_signs_in() {
+        let mut store = store_with_password("correct horse");
+        assert_eq!(store.sign_in("ann", "correct horse"), Ok(1));
+        assert_eq!(store.sign_in("ann", "correct horse"), Ok(2));
+    }
+
+    #[test]
+    fn locked_after_too_many_wrong_passwords() {
+        let mut store = store_with_password("correct horse");
+        for attempt in 1..=MAX_FAILED_ATTEMPTS {
+            assert_eq!(
+                store.sign_in("ann", "battery staple"),
+                Err(StoreError::WrongPassword("ann".to_string()))
+            );
+            assert_eq!(
+                store.get("ann").unwrap().credentials.failed_attempts,
+                attempt
+            );
+        }
+        /* Even the right password is refused once locked. */
+        assert_eq!(
+            store.sign_in("ann", "correct horse"),
+            Err(StoreError::Locked("ann".to_string()))
+        );
+        assert_eq!(store.get("ann").unwrap().sign_in_count, 0);
+
+        store.unlock("ann").unwrap();
+        assert_eq!(store.sign_in("ann", "correct horse"), Ok(1));
+    }
+
+    #[test]
+    fn right_password_resets_failed_attempts() {
+        let mut store = store_with_password("correct horse");
+        for _ in 1..MAX_FAILED_ATTEMPTS {
+            store.sign_in("ann", "battery staple").unwrap_err();
+        }
+        store.sign_in("ann", "correct horse").unwrap();
+        assert_eq!(store.get("ann").unwrap().credentials.failed_attempts, 0);
+        store.sign_in("ann", "battery staple").unwrap_err();
+        assert!(!store.get("ann").unwrap().credentials.is_locked());
+    }
+
+    #[test]
+    fn setting_a_password_unlocks() {
+        let mut store = store_with_password("correct horse");
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            store.sign_in("ann", "battery staple").unwrap_err();
+        }
+        let hash = PasswordHash::with_iterations("new password", MIN_ITERATIONS).unwrap();
+        store.set_password("ann", hash).unwrap();
+        assert_eq!(store.sign_in("ann", "new password"), Ok(1));
+    }
+
+    #[test]
+    fn inactive_user_or_no_password_cannot_sign_in() {
+        let mut store = store_with_password("correct horse");
+        store.deactivate("ann").unwrap();
+        assert_eq!(
+            store.sign_in("ann", "correct horse"),
+            Err(StoreError::Inactive("ann".to_string()))
+        );
+
+        store
+            .create(build_user(
+                "bob@example.com".parse().unwrap(),
+                "bob".parse().unwrap(),
+            ))
+            .unwrap();
+        assert_eq!(
+            store.sign_in("bob", "anything at all"),
+            Err(StoreError::NoPassword("bob".to_string()))
+        );
+    }
+
+    fn user(username: &str, email: &str) -> User {
+        build_user(email.parse().unwrap(), username.parse().unwrap())
+    }
+
+    fn store() -> UserStore {
+        let mut store = UserStore::new();
+        store.create(user("ann", "ann@example.com")).unwrap();
+        store.create(user("bob", "bob@example.com")).unwrap();
+        store
+    }
+
+    #[test]
+    fn usernames_and_emails_are_unique_ignoring_case() {
+        let mut store = store();
+        for (username, email, error) in &[
+            (
+                "ann",
+                "new@example.com",
+                StoreError::DuplicateUsername("ann".to_string()),
+            ),
+            (
+                "ANN",
+                "new@example.com",
+                StoreError::DuplicateUsername("ann".to_string()),
+            ),
+            (
+                "cat",
+                "ann@example.com",
+                StoreError::DuplicateEmail("ann@example.com".to_string()),
+            ),
+            (
+                "cat",
+                "Ann@EXAMPLE.com",
+                StoreError::DuplicateEmail("ann@example.com".to_string()),
+            ),
+        ] {
+            assert_eq!(
+                store.create(user(username, email)).err().as_ref(),
+                Some(error)
+            );
+        }
+        assert_eq!(store.len(), 2);
+        assert!(store.create(user("cat", "cat@example.com")).is_ok());
+    }
+
+    #[test]
+    fn lookups_ignore_case() {
+        let store = store();
+        assert_eq!(store.get("ANN").unwrap().username.as_str(), "ann");
+        assert_eq!(
+            store
+                .find_by_email("Bob@Example.COM")
+                .unwrap()
+                .username
+                .as_str(),
+            "bob"
+        );
+        assert_eq!(store.find_by_email("nobody@example.com"), None);
+        assert_eq!(store.id("Ann"), store.id("ann"));
+        assert_ne!(store.id("ann"), store.id("bob"));
+    }
+
+    #[test]
+    fn update_email_moves_the_email_index() {
+        let mut store = store();
+        store
+            .update_email("ann", "ann@example.org".parse().unwrap())
+            .unwrap();
+        assert_eq!(store.find_by_email("ann@example.com"), None);
+        assert_eq!(
+            store
+                .find_by_email("ann@example.org")
+                .unwrap()
+                .username
+                .as_str(),
+            "ann"
+        );
+        /* The old email is free again; an email in use is not. */
+        store.create(user("cat", "ann@example.com")).unwrap();
+        assert_eq!(
+            store.update_email("ann", "bob@example.com".parse().unwrap()),
+            Err(StoreError::DuplicateEmail("bob@example.com".to_string()))
+        );
+        /* Setting the email a user already has changes nothing. */
+        assert!(store
+            .update_email("ANN", "ann@example.org".parse().unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn rename_re_keys_the_user() {
+        let mut store = store();
+        let id = store.id("ann");
+        store.rename("Ann", "anne".parse().unwrap()).unwrap();
+        assert_eq!(store.get("ann"), None);
+        assert_eq!(store.get("anne").unwrap().username.as_str(), "anne");
+        assert_eq!(
+            store
+                .find_by_email("ann@example.com")
+                .unwrap()
+                .username
+                .as_str(),
+            "anne"
+        );
+        assert_eq!(store.id("anne"), id);
+        assert_eq!(store.id("ann"), None);
+        assert_eq!(
+            store.rename("anne", "bob".parse().unwrap()),
+            Err(StoreError::DuplicateUsername("bob".to_string()))
+        );
+        /* The old name can be taken by someone new. */
+        store.create(user("ann", "new@example.com")).unwrap();
+        assert_ne!(store.id("ann"), id);
+        assert_eq!(
+            store
+                .iter()
+                .map(|u| u.username.as_str())
+                .collect::<Vec<_>>(),
+            ["ann", "anne", "bob"]
+        );
+    }
+
+    #[test]
+    fn missing_users_are_not_found_before_anything_else() {
+        let mut store = store();
+        let not_found = Err(StoreError::NotFound("cat".to_string()));
+        /* Even when the new email or name is taken, a missing user is the
+         * error that matters. */
+        assert_eq!(
+            store.update_email("cat", "ann@example.com".parse().unwrap()),
+            not_found
+        );
+        assert_eq!(store.rename("cat", "ann".parse().unwrap()), not_found);
+        assert_eq!(store.rename("cat", "cat".parse().unwrap()), not_found);
+        assert_eq!(store.activate("cat"), not_found);
+        assert_eq!(store.grant("cat", Role::Admin), not_found);
+        assert_eq!(store.unlock("cat"), not_found);
+        assert_eq!(
+            store.sign_in("cat", "correct horse"),
+            Err(StoreError::NotFound("cat".to_string()))
+        );
+        assert_eq!(
+            store.remove("cat"),
+            Err(StoreError::NotFound("cat".to_string()))
+        );
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn removing_frees_the_name_and_email() {
+        let mut store = store();
+        assert_eq!(store.remove("ANN").unwrap().username.as_str(), "ann");
+        assert_eq!(store.get("ann"), None);
+        assert_eq!(store.find_by_email("ann@example.com"), None);
+        assert_eq!(store.id("ann"), None);
+        assert!(store.create(user("ann", "ann@example.com")).is_ok());
+    }
+
+    #[test]
+    fn inactive_users_are_refused_until_activated() {
+        let mut store = store_with_password("correct horse");
+        store.deactivate("ann").unwrap();
+        assert_eq!(
+            store.sign_in("ann", "correct horse"),
+            Err(StoreError::Inactive("ann".to_string()))
+        );
+        /* Refusing an inactive user is not a failed attempt. */
+        assert_eq!(store.get("ann").unwrap().credentials.failed_attempts, 0);
+        store.activate("ann").unwrap();
+        assert_eq!(store.sign_in("ann", "correct horse"), Ok(1));
+    }
+}
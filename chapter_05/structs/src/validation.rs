@@ -0,0 +1,354 @@
+/* Validated usernames and email addresses.
+ *
+ * With plain Strings nothing stops a User from getting an email of "user3"
+ * and a username of "user3@newuser.com", as happens to user3 in main.rs when
+ * the two are swapped. 'Username' and 'Email' can only be created by parsing,
+ * which checks the text and normalizes it, so a User holding them is always
+ * well formed.
+ *
+ * Usernames are 3 to 32 characters: ASCII letters, digits, '_', '-' and '.',
+ * starting with a letter or digit. Emails follow the common subset of RFC
+ * 5321: a local part, '@', and a domain of at least two labels ending in an
+ * alphabetic top-level domain. Both are folded to lower case, so "Abc" and
+ * "abc" are the same user. */
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+const USERNAME_MIN: usize = 3;
+const USERNAME_MAX: usize = 32;
+const EMAIL_MAX: usize = 254;
+const LOCAL_MAX: usize = 64;
+const LABEL_MAX: usize = 63;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    UsernameLength(usize),
+    UsernameStart(char),
+    UsernameCharacter(char),
+    /* A username containing '@', most likely an email passed by mistake. */
+    UsernameLooksLikeEmail(String),
+    EmailTooLong(usize),
+    EmailMissingAt,
+    EmailMultipleAt,
+    EmailLocalPart(String),
+    EmailDomain(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::UsernameLength(found) => write!(
+                f,
+                "username must be {} to {} characters long, found {}",
+                USERNAME_MIN, USERNAME_MAX, found
+            ),
+            ValidationError::UsernameStart(c) => {
+                write!(
+                    f,
+                    "username must start with a letter or digit, found {:?}",
+                    c
+                )
+            }
+            ValidationError::UsernameCharacter(c) => write!(
+                f,
+                "username may only contain letters, digits, '_', '-' and '.', found {:?}",
+                c
+            ),
+            ValidationError::UsernameLooksLikeEmail(text) => write!(
+                f,
+                "username {:?} looks like an email address; were email and username swapped?",
+                text
+            ),
+            ValidationError::EmailTooLong(found) => write!(
+                f,
+                "email must be at most {} characters long, found {}",
+                EMAIL_MAX, found
+            ),
+            ValidationError::EmailMissingAt => write!(f, "email must contain '@'"),
+            ValidationError::EmailMultipleAt => write!(f, "email must contain only one '@'"),
+            ValidationError::EmailLocalPart(local) => {
+                write!(f, "invalid part before '@' in email: {:?}", local)
+            }
+            ValidationError::EmailDomain(domain) => {
+                write!(f, "invalid domain in email: {:?}", domain)
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Username(String);
+
+impl Username {
+    pub fn parse(text: &str) -> Result<Username, ValidationError> {
+        let name = text.trim();
+        if name.contains('@') {
+            return Err(ValidationError::UsernameLooksLikeEmail(name.to_string()));
+        }
+        let length = name.chars().count();
+        if !(USERNAME_MIN..=USERNAME_MAX).contains(&length) {
+            return Err(ValidationError::UsernameLength(length));
+        }
+        if let Some(c) = name.chars().find(|&c| !is_username_char(c)) {
+            return Err(ValidationError::UsernameCharacter(c));
+        }
+        let first = name.chars().next().unwrap();
+        if !first.is_ascii_alphanumeric() {
+            return Err(ValidationError::UsernameStart(first));
+        }
+        Ok(Username(name.to_ascii_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /* The form a username takes once parsed, for looking users up by text
+     * that has not been validated. */
+    pub fn normalize(text: &str) -> String {
+        text.trim().to_ascii_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Email(String);
+
+impl Email {
+    pub fn parse(text: &str) -> Result<Email, ValidationError> {
+        let email = text.trim();
+        let length = email.chars().count();
+        if length > EMAIL_MAX {
+            return Err(ValidationError::EmailTooLong(length));
+        }
+        let mut parts = email.split('@');
+        let (local, domain) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(local), Some(domain), None) => (local, domain),
+            (_, None, _) => return Err(ValidationError::EmailMissingAt),
+            _ => return Err(ValidationError::EmailMultipleAt),
+        };
+        if !is_valid_local(local) {
+            return Err(ValidationError::EmailLocalPart(local.to_string()));
+        }
+        if !is_valid_domain(domain) {
+            return Err(ValidationError::EmailDomain(domain.to_string()));
+        }
+        Ok(Email(email.to_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn domain(&self) -> &str {
+        self.0.rsplit('@').next().unwrap_or("")
+    }
+
+    /* The form an email takes once parsed, for looking users up by text
+     * that has not been validated. */
+    pub fn normalize(text: &str) -> String {
+        text.trim().to_lowercase()
+    }
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+fn is_valid_local(local: &str) -> bool {
+    let special = "!#$%&'*+/=?^_`{|}~.-";
+    !local.is_empty()
+        && local.len() <= LOCAL_MAX
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || special.contains(c))
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let tld = labels[labels.len() - 1];
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= LABEL_MAX
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+impl FromStr for Username {
+    type Err = ValidationError;
+
+    fn from_str(text: &str) -> Result<Username, ValidationError> {
+        Username::parse(text)
+    }
+}
+
+impl FromStr for Email {
+    type Err = ValidationError;
+
+    fn from_str(text: &str) -> Result<Email, ValidationError> {
+        Email::parse(text)
+    }
+}
+
+impl fmt::Display for Username {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for Email {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Email {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<Username> for String {
+    fn from(username: Username) -> String {
+        username.0
+    }
+}
+
+impl From<Email> for String {
+    fn from(email: Email) -> String {
+        email.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames() {
+        let longest = "a".repeat(USERNAME_MAX);
+        let too_long = "a".repeat(USERNAME_MAX + 1);
+        let accepted: &[(&str, &str)] = &[
+            ("abc", "abc"),
+            ("Abc", "abc"),
+            ("  ABC_def  ", "abc_def"),
+            ("user.name-1", "user.name-1"),
+            ("9lives", "9lives"),
+            (&longest, &longest),
+        ];
+        for &(text, parsed) in accepted {
+            assert_eq!(
+                Username::parse(text).map(|u| u.0),
+                Ok(parsed.to_string()),
+                "{:?}",
+                text
+            );
+            assert_eq!(Username::normalize(text), parsed);
+        }
+
+        let rejected: &[(&str, ValidationError)] = &[
+            ("", ValidationError::UsernameLength(0)),
+            ("ab", ValidationError::UsernameLength(2)),
+            ("   ab   ", ValidationError::UsernameLength(2)),
+            (&too_long, ValidationError::UsernameLength(USERNAME_MAX + 1)),
+            ("_abc", ValidationError::UsernameStart('_')),
+            (".abc", ValidationError::UsernameStart('.')),
+            ("-abc", ValidationError::UsernameStart('-')),
+            ("a b", ValidationError::UsernameCharacter(' ')),
+            ("abc!", ValidationError::UsernameCharacter('!')),
+            ("zoë", ValidationError::UsernameCharacter('ë')),
+            (
+                "ann@example.com",
+                ValidationError::UsernameLooksLikeEmail("ann@example.com".to_string()),
+            ),
+        ];
+        for (text, error) in rejected {
+            assert_eq!(Username::parse(text).as_ref(), Err(error), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn emails() {
+        let longest_local = format!("{}@example.com", "a".repeat(LOCAL_MAX));
+        let long_label = format!("ann@{}.com", "a".repeat(LABEL_MAX));
+        let accepted: &[(&str, &str)] = &[
+            ("ann@example.com", "ann@example.com"),
+            ("  Ann@Example.COM ", "ann@example.com"),
+            (
+                "first.last+tag@mail.example.co",
+                "first.last+tag@mail.example.co",
+            ),
+            ("o'brien@example.ie", "o'brien@example.ie"),
+            ("a@b-c.io", "a@b-c.io"),
+            ("x@123.example.org", "x@123.example.org"),
+            (&longest_local, &longest_local),
+            (&long_label, &long_label),
+        ];
+        for &(text, parsed) in accepted {
+            assert_eq!(
+                Email::parse(text).map(|e| e.0),
+                Ok(parsed.to_string()),
+                "{:?}",
+                text
+            );
+            assert_eq!(Email::normalize(text), parsed);
+        }
+        assert_eq!(
+            Email::parse("ann@Mail.Example.com").unwrap().domain(),
+            "mail.example.com"
+        );
+
+        let too_long = format!("ann@{}.com", vec!["a".repeat(60); 5].join("."));
+        let too_long_local = format!("{}@example.com", "a".repeat(LOCAL_MAX + 1));
+        let too_long_label = format!("ann@{}.com", "a".repeat(LABEL_MAX + 1));
+        let local = |text: &str| ValidationError::EmailLocalPart(text.to_string());
+        let domain = |text: &str| ValidationError::EmailDomain(text.to_string());
+        let rejected: Vec<(&str, ValidationError)> = vec![
+            ("", ValidationError::EmailMissingAt),
+            ("ann.example.com", ValidationError::EmailMissingAt),
+            ("ann@@example.com", ValidationError::EmailMultipleAt),
+            ("ann@home@example.com", ValidationError::EmailMultipleAt),
+            (&too_long, ValidationError::EmailTooLong(too_long.len())),
+            ("@example.com", local("")),
+            (".ann@example.com", local(".ann")),
+            ("ann.@example.com", local("ann.")),
+            ("a..nn@example.com", local("a..nn")),
+            ("a nn@example.com", local("a nn")),
+            ("\"ann\"@example.com", local("\"ann\"")),
+            ("ann,bob@example.com", local("ann,bob")),
+            (&too_long_local, local(&too_long_local[..LOCAL_MAX + 1])),
+            ("ann@", domain("")),
+            ("ann@localhost", domain("localhost")),
+            ("ann@example.", domain("example.")),
+            ("ann@.example.com", domain(".example.com")),
+            ("ann@example..com", domain("example..com")),
+            ("ann@-example.com", domain("-example.com")),
+            ("ann@example-.com", domain("example-.com")),
+            ("ann@exa_mple.com", domain("exa_mple.com")),
+            ("ann@example.c", domain("example.c")),
+            ("ann@example.c0m", domain("example.c0m")),
+            ("ann@192.168.0.1", domain("192.168.0.1")),
+            (&too_long_label, domain(&too_long_label[4..])),
+        ];
+        for (text, error) in &rejected {
+            assert_eq!(Email::parse(text).as_ref(), Err(error), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,522 @@
+/* Networks written in CIDR notation.
+ *
+ * "10.0.0.0/8" is every address whose first 8 bits match 10.0.0.0, from
+ * 10.0.0.0 up to 10.255.255.255. The number after the '/' is the prefix
+ * length: how many leading bits name the network. The remaining bits pick a
+ * host within it.
+ *
+ * The address of a network has all of its host bits zero. "192.168.1.77/24"
+ * is refused rather than quietly read as 192.168.1.0/24, since it more
+ * likely names one host than the whole network; 'Cidr::containing' gives
+ * the network an address belongs to.
+ *
+ * In IPv4 the last address of a network is its broadcast address, and
+ * neither it nor the network address is given to a host, except in /31
+ * point-to-point links and /32 single hosts, where every address is a
+ * host. IPv6 has no broadcast, so every address in the network can be a
+ * host. */
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{AddrParseError, IpAddrVariant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CidrError {
+    Addr(AddrParseError),
+    MissingPrefix,
+    InvalidPrefix(String),
+    PrefixTooLong { prefix: u8, max: u8 },
+    /* An address with bits set after the prefix, as in 10.0.0.1/8. */
+    HostBitsSet { addr: IpAddrVariant, prefix: u8 },
+    /* Splitting into subnets larger than the network itself. */
+    PrefixTooShort { prefix: u8, min: u8 },
+}
+
+impl fmt::Display for CidrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CidrError::Addr(e) => write!(f, "invalid network address: {}", e),
+            CidrError::MissingPrefix => {
+                write!(f, "a network needs a prefix length, as in 10.0.0.0/8")
+            }
+            CidrError::InvalidPrefix(text) => {
+                write!(f, "{:?} is not a prefix length", text)
+            }
+            CidrError::PrefixTooLong { prefix, max } => write!(
+                f,
+                "prefix length {} is longer than the address, at most {}",
+                prefix, max
+            ),
+            CidrError::HostBitsSet { addr, prefix } => {
+                write!(f, "{}/{} has host bits set", addr, prefix)?;
+                match Cidr::containing(*addr, *prefix) {
+                    Ok(network) => write!(f, ", the network is {}", network),
+                    Err(_) => Ok(()),
+                }
+            }
+            CidrError::PrefixTooShort { prefix, min } => write!(
+                f,
+                "prefix length {} is shorter than the network's, at least {}",
+                prefix, min
+            ),
+        }
+    }
+}
+
+impl Error for CidrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CidrError::Addr(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AddrParseError> for CidrError {
+    fn from(e: AddrParseError) -> CidrError {
+        CidrError::Addr(e)
+    }
+}
+
+/* Ordered by network address, V4 before V6, then shorter prefixes first. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cidr {
+    network: IpAddrVariant,
+    prefix: u8,
+}
+
+impl Cidr {
+    /* The network with this address and prefix length. The address must
+     * be the first one of the network, with no host bits set. */
+    pub fn new(network: IpAddrVariant, prefix: u8) -> Result<Cidr, CidrError> {
+        let cidr = Cidr::containing(network, prefix)?;
+        if cidr.network != network {
+            return Err(CidrError::HostBitsSet {
+                addr: network,
+                prefix,
+            });
+        }
+        Ok(cidr)
+    }
+
+    /* The network of the given prefix length that 'addr' belongs to. */
+    pub fn containing(addr: IpAddrVariant, prefix: u8) -> Result<Cidr, CidrError> {
+        let max = addr.bit_width();
+        if prefix > max {
+            return Err(CidrError::PrefixTooLong { prefix, max });
+        }
+        Ok(Cidr {
+            network: addr.with_bits(addr.to_bits() & network_mask(prefix, max)),
+            prefix,
+        })
+    }
+
+    pub fn network(&self) -> IpAddrVariant {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /* The prefix as an address: 255.255.255.0 for a /24. */
+    pub fn netmask(&self) -> IpAddrVariant {
+        self.network
+            .with_bits(network_mask(self.prefix, self.network.bit_width()))
+    }
+
+    /* The last address in the network. */
+    pub fn last(&self) -> IpAddrVariant {
+        self.network
+            .with_bits(self.network.to_bits() | self.host_mask())
+    }
+
+    /* The last address of an IPv4 network. IPv6 has no broadcast. */
+    pub fn broadcast(&self) -> Option<IpAddrVariant> {
+        if self.network.is_v4() {
+            Some(self.last())
+        } else {
+            None
+        }
+    }
+
+    /* The first and last addresses that can be given to hosts. */
+    pub fn hosts(&self) -> (IpAddrVariant, IpAddrVariant) {
+        if self.network.is_v4() && self.prefix < 31 {
+            let bits = self.network.to_bits();
+            (
+                self.network.with_bits(bits + 1),
+                self.network.with_bits((bits | self.host_mask()) - 1),
+            )
+        } else {
+            (self.network, self.last())
+        }
+    }
+
+    /* How many addresses the network has, or None for ::/0, whose 2^128
+     * addresses don't fit in a u128. */
+    pub fn size(&self) -> Option<u128> {
+        self.host_mask().checked_add(1)
+    }
+
+    pub fn contains(&self, addr: IpAddrVariant) -> bool {
+        addr.bit_width() == self.network.bit_width()
+            && addr.to_bits() & !self.host_mask() == self.network.to_bits()
+    }
+
+    /* Whether every address of 'other' is in this network. */
+    pub fn contains_cidr(&self, other: &Cidr) -> bool {
+        self.prefix <= other.prefix && self.contains(other.network)
+    }
+
+    /* The network one bit shorter that contains this one and its sibling,
+     * or None for a /0. */
+    pub fn supernet(&self) -> Option<Cidr> {
+        let prefix = self.prefix.checked_sub(1)?;
+        Cidr::containing(self.network, prefix).ok()
+    }
+
+    /* The networks of the longer prefix length that this one splits into,
+     * lowest first. */
+    pub fn subnets(&self, prefix: u8) -> Result<Subnets, CidrError> {
+        let max = self.network.bit_width();
+        if prefix > max {
+            return Err(CidrError::PrefixTooLong { prefix, max });
+        }
+        if prefix < self.prefix {
+            return Err(CidrError::PrefixTooShort {
+                prefix,
+                min: self.prefix,
+            });
+        }
+        Ok(Subnets {
+            next: Some(self.network.to_bits()),
+            last: self.last().to_bits(),
+            step: host_mask(prefix, max).checked_add(1),
+            template: self.network,
+            prefix,
+        })
+    }
+
+    fn host_mask(&self) -> u128 {
+        host_mask(self.prefix, self.network.bit_width())
+    }
+}
+
+/* The low 'width - prefix' bits of a 'width'-bit address. */
+fn host_mask(prefix: u8, width: u8) -> u128 {
+    let all = if width == 128 {
+        u128::MAX
+    } else {
+        (1 << width) - 1
+    };
+    all.checked_shr(u32::from(prefix)).unwrap_or(0)
+}
+
+fn network_mask(prefix: u8, width: u8) -> u128 {
+    !host_mask(prefix, width) & host_mask(0, width)
+}
+
+/* Returned by 'Cidr::subnets'. */
+#[derive(Debug, Clone)]
+pub struct Subnets {
+    next: Option<u128>,
+    last: u128,
+    /* None when a single subnet covers all 2^128 addresses. */
+    step: Option<u128>,
+    template: IpAddrVariant,
+    prefix: u8,
+}
+
+impl Iterator for Subnets {
+    type Item = Cidr;
+
+    fn next(&mut self) -> Option<Cidr> {
+        let bits = self.next?;
+        self.next = self
+            .step
+            .and_then(|step| bits.checked_add(step))
+            .filter(|&next| next <= self.last);
+        Some(Cidr {
+            network: self.template.with_bits(bits),
+            prefix: self.prefix,
+        })
+    }
+}
+
+/* The smallest list of networks covering exactly the same addresses as
+ * 'networks': networks inside others are dropped, and sibling pairs such as
+ * 10.0.0.0/25 and 10.0.0.128/25 are merged into 10.0.0.0/24, repeatedly.
+ * The result is sorted. */
+pub fn aggregate(networks: &[Cidr]) -> Vec<Cidr> {
+    let mut sorted = networks.to_vec();
+    sorted.sort();
+    let mut merged: Vec<Cidr> = Vec::with_capacity(sorted.len());
+    for network in sorted {
+        if merged.last().is_some_and(|top| top.contains_cidr(&network)) {
+            continue;
+        }
+        merged.push(network);
+        while merged.len() >= 2 {
+            let (a, b) = (merged[merged.len() - 2], merged[merged.len() - 1]);
+            match a.supernet() {
+                Some(parent) if a.prefix == b.prefix && parent.contains_cidr(&b) => {
+                    merged.truncate(merged.len() - 2);
+                    merged.push(parent);
+                }
+                _ => break,
+            }
+        }
+    }
+    merged
+}
+
+impl FromStr for Cidr {
+    type Err = CidrError;
+
+    fn from_str(text: &str) -> Result<Cidr, CidrError> {
+        let text = text.trim();
+        let slash = text.find('/').ok_or(CidrError::MissingPrefix)?;
+        let addr: IpAddrVariant = text[..slash].parse()?;
+        let digits = &text[slash + 1..];
+        let prefix = if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        };
+        let prefix = prefix.ok_or_else(|| CidrError::InvalidPrefix(digits.to_string()))?;
+        Cidr::new(addr, prefix)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&format!("{}/{}", self.network, self.prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(text: &str) -> Cidr {
+        text.parse().unwrap()
+    }
+
+    fn cidrs(texts: &[&str]) -> Vec<Cidr> {
+        texts.iter().map(|text| cidr(text)).collect()
+    }
+
+    fn addr(text: &str) -> IpAddrVariant {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn constructor_rejects_host_bits() {
+        for &(text, prefix) in &[
+            ("10.0.0.1", 8),
+            ("192.168.1.77", 24),
+            ("10.0.0.1", 31),
+            ("255.255.255.255", 0),
+            ("2001:db8::1", 64),
+            ("::1", 127),
+        ] {
+            assert_eq!(
+                Cidr::new(addr(text), prefix),
+                Err(CidrError::HostBitsSet {
+                    addr: addr(text),
+                    prefix
+                }),
+                "{}/{}",
+                text,
+                prefix
+            );
+        }
+        assert_eq!(
+            "192.168.1.77/24".parse::<Cidr>().unwrap_err().to_string(),
+            "192.168.1.77/24 has host bits set, the network is 192.168.1.0/24"
+        );
+        assert!(Cidr::new(addr("10.0.0.1"), 32).is_ok());
+        assert!(Cidr::new(addr("10.0.0.0"), 8).is_ok());
+    }
+
+    #[test]
+    fn containing_masks_host_bits() {
+        assert_eq!(
+            Cidr::containing(addr("192.168.1.77"), 24),
+            Ok(cidr("192.168.1.0/24"))
+        );
+        assert_eq!(
+            Cidr::containing(addr("2001:db8:1:2::3"), 32),
+            Ok(cidr("2001:db8::/32"))
+        );
+        assert_eq!(
+            Cidr::containing(addr("10.0.0.1"), 33),
+            Err(CidrError::PrefixTooLong {
+                prefix: 33,
+                max: 32
+            })
+        );
+    }
+
+    #[test]
+    fn whole_address_space() {
+        let v4 = cidr("0.0.0.0/0");
+        assert_eq!(v4.size(), Some(1 << 32));
+        assert_eq!(v4.netmask(), addr("0.0.0.0"));
+        assert_eq!(v4.last(), addr("255.255.255.255"));
+        assert_eq!(v4.hosts(), (addr("0.0.0.1"), addr("255.255.255.254")));
+        assert!(v4.contains(addr("203.0.113.1")));
+        assert!(!v4.contains(addr("::1")));
+        assert_eq!(v4.supernet(), None);
+
+        let v6 = cidr("::/0");
+        assert_eq!(v6.size(), None);
+        assert_eq!(v6.last(), addr("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"));
+        assert_eq!(v6.broadcast(), None);
+        assert!(v6.contains(addr("2001:db8::1")));
+        assert!(!v6.contains(addr("0.0.0.0")));
+        assert_eq!(
+            v6.subnets(1).unwrap().collect::<Vec<_>>(),
+            cidrs(&["::/1", "8000::/1"])
+        );
+        assert_eq!(v6.subnets(0).unwrap().collect::<Vec<_>>(), [v6]);
+    }
+
+    #[test]
+    fn single_addresses() {
+        let host = cidr("192.0.2.7/32");
+        assert_eq!(host.size(), Some(1));
+        assert_eq!(host.netmask(), addr("255.255.255.255"));
+        assert_eq!(host.hosts(), (addr("192.0.2.7"), addr("192.0.2.7")));
+        assert_eq!(host.broadcast(), Some(addr("192.0.2.7")));
+        assert!(host.contains(addr("192.0.2.7")));
+        assert!(!host.contains(addr("192.0.2.6")));
+        assert_eq!(host.supernet(), Some(cidr("192.0.2.6/31")));
+        assert_eq!(host.subnets(32).unwrap().collect::<Vec<_>>(), [host]);
+        assert_eq!(
+            host.subnets(33).unwrap_err(),
+            CidrError::PrefixTooLong {
+                prefix: 33,
+                max: 32
+            }
+        );
+
+        let host = cidr("2001:db8::1/128");
+        assert_eq!(host.size(), Some(1));
+        assert_eq!(host.last(), addr("2001:db8::1"));
+        assert_eq!(host.hosts(), (addr("2001:db8::1"), addr("2001:db8::1")));
+        assert_eq!(host.supernet(), Some(cidr("2001:db8::/127")));
+        assert_eq!(
+            "::/129".parse::<Cidr>(),
+            Err(CidrError::PrefixTooLong {
+                prefix: 129,
+                max: 128
+            })
+        );
+    }
+
+    #[test]
+    fn point_to_point_links_have_no_broadcast_hosts() {
+        let link = cidr("203.0.113.8/31");
+        assert_eq!(link.hosts(), (addr("203.0.113.8"), addr("203.0.113.9")));
+        let lan = cidr("192.168.1.0/24");
+        assert_eq!(lan.hosts(), (addr("192.168.1.1"), addr("192.168.1.254")));
+        assert_eq!(lan.broadcast(), Some(addr("192.168.1.255")));
+    }
+
+    #[test]
+    fn aggregate_merges_adjacent_blocks() {
+        assert_eq!(
+            aggregate(&cidrs(&["10.0.0.0/25", "10.0.0.128/25"])),
+            cidrs(&["10.0.0.0/24"])
+        );
+        assert_eq!(
+            aggregate(&cidrs(&[
+                "10.0.3.0/24",
+                "10.0.1.0/24",
+                "10.0.0.0/24",
+                "10.0.2.0/24"
+            ])),
+            cidrs(&["10.0.0.0/22"])
+        );
+        assert_eq!(
+            aggregate(&cidrs(&["0.0.0.0/1", "128.0.0.0/1", "::/1", "8000::/1"])),
+            cidrs(&["0.0.0.0/0", "::/0"])
+        );
+    }
+
+    #[test]
+    fn aggregate_keeps_blocks_that_are_not_siblings() {
+        /* Adjacent, but 10.0.1.0/24 and 10.0.2.0/24 are in different /23s. */
+        assert_eq!(
+            aggregate(&cidrs(&["10.0.1.0/24", "10.0.2.0/24"])),
+            cidrs(&["10.0.1.0/24", "10.0.2.0/24"])
+        );
+        assert_eq!(
+            aggregate(&cidrs(&["10.0.0.0/24", "10.0.1.0/25"])),
+            cidrs(&["10.0.0.0/24", "10.0.1.0/25"])
+        );
+    }
+
+    #[test]
+    fn aggregate_removes_nested_blocks() {
+        assert_eq!(
+            aggregate(&cidrs(&[
+                "10.0.0.0/26",
+                "10.0.0.0/8",
+                "10.200.0.0/16",
+                "10.0.0.5/32",
+                "10.0.0.0/8",
+            ])),
+            cidrs(&["10.0.0.0/8"])
+        );
+        assert_eq!(
+            aggregate(&cidrs(&["0.0.0.0/0", "192.0.2.0/24", "2001:db8::/32"])),
+            cidrs(&["0.0.0.0/0", "2001:db8::/32"])
+        );
+        assert_eq!(aggregate(&[]), []);
+    }
+
+    #[test]
+    fn subnets_aggregate_back() {
+        let lan = cidr("192.168.1.0/24");
+        let quarters: Vec<Cidr> = lan.subnets(26).unwrap().collect();
+        assert_eq!(
+            quarters,
+            cidrs(&[
+                "192.168.1.0/26",
+                "192.168.1.64/26",
+                "192.168.1.128/26",
+                "192.168.1.192/26",
+            ])
+        );
+        assert_eq!(aggregate(&quarters), [lan]);
+        assert_eq!(
+            lan.subnets(16).unwrap_err(),
+            CidrError::PrefixTooShort {
+                prefix: 16,
+                min: 24
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("10.0.0.0".parse::<Cidr>(), Err(CidrError::MissingPrefix));
+        for prefix in &["", "x", "+8", "-1", "256"] {
+            assert_eq!(
+                format!("10.0.0.0/{}", prefix).parse::<Cidr>(),
+                Err(CidrError::InvalidPrefix(prefix.to_string())),
+                "{:?}",
+                prefix
+            );
+        }
+        assert!(matches!(
+            "10.0.0/8".parse::<Cidr>(),
+            Err(CidrError::Addr(_))
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+/* What kind of address an address is.
+ *
+ *                   IPv4                          IPv6
+ *   loopback        127.0.0.0/8                   ::1
+ *   private         10.0.0.0/8, 172.16.0.0/12,    fc00::/7 (unique local)
+ *                   192.168.0.0/16
+ *   link-local      169.254.0.0/16                fe80::/10
+ *   multicast       224.0.0.0/4                   ff00::/8
+ *   documentation   192.0.2.0/24, 198.51.100.0/24, 2001:db8::/32, 3fff::/20
+ *                   203.0.113.0/24
+ *   unspecified     0.0.0.0                       ::
+ *   IPv4-mapped                                   ::ffff:0:0/96
+ *
+ * An IPv4-mapped address is only that: ::ffff:127.0.0.1 is not classed as
+ * loopback. Use 'to_ipv4_mapped' to classify the IPv4 address inside it. */
+
+use crate::IpAddrVariant;
+
+impl IpAddrVariant {
+    pub fn is_loopback(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, ..) => a == 127,
+            IpAddrVariant::V6(groups) => groups == [0, 0, 0, 0, 0, 0, 0, 1],
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, b, ..) => {
+                a == 10 || (a == 172 && b & 0xf0 == 16) || (a == 192 && b == 168)
+            }
+            IpAddrVariant::V6(groups) => groups[0] & 0xfe00 == 0xfc00,
+        }
+    }
+
+    pub fn is_link_local(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, b, ..) => a == 169 && b == 254,
+            IpAddrVariant::V6(groups) => groups[0] & 0xffc0 == 0xfe80,
+        }
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, ..) => a & 0xf0 == 224,
+            IpAddrVariant::V6(groups) => groups[0] & 0xff00 == 0xff00,
+        }
+    }
+
+    pub fn is_documentation(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, b, c, _) => {
+                matches!((a, b, c), (192, 0, 2) | (198, 51, 100) | (203, 0, 113))
+            }
+            IpAddrVariant::V6(groups) => {
+                (groups[0] == 0x2001 && groups[1] == 0xdb8)
+                    || (groups[0] == 0x3fff && groups[1] & 0xf000 == 0)
+            }
+        }
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        match *self {
+            IpAddrVariant::V4(a, b, c, d) => [a, b, c, d] == [0; 4],
+            IpAddrVariant::V6(groups) => groups == [0; 8],
+        }
+    }
+
+    pub fn is_ipv4_mapped(&self) -> bool {
+        self.to_ipv4_mapped().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::IpAddrVariant;
+
+    type Predicate = fn(&IpAddrVariant) -> bool;
+
+    const CLASSES: [(&str, Predicate); 7] = [
+        ("loopback", IpAddrVariant::is_loopback),
+        ("private", IpAddrVariant::is_private),
+        ("link-local", IpAddrVariant::is_link_local),
+        ("multicast", IpAddrVariant::is_multicast),
+        ("documentation", IpAddrVariant::is_documentation),
+        ("unspecified", IpAddrVariant::is_unspecified),
+        ("ipv4-mapped", IpAddrVariant::is_ipv4_mapped),
+    ];
+
+    /* Each address with every class it should be in. */
+    const EXPECTED: &[(&str, &[&str])] = &[
+        ("127.0.0.1", &["loopback"]),
+        ("127.255.0.9", &["loopback"]),
+        ("::1", &["loopback"]),
+        ("::2", &[]),
+        ("10.1.2.3", &["private"]),
+        ("172.16.0.1", &["private"]),
+        ("172.31.255.255", &["private"]),
+        ("172.15.255.255", &[]),
+        ("172.32.0.1", &[]),
+        ("192.168.0.1", &["private"]),
+        ("192.169.0.1", &[]),
+        ("fc00::1", &["private"]),
+        ("fd12:3456::1", &["private"]),
+        ("fe00::1", &[]),
+        ("169.254.10.1", &["link-local"]),
+        ("169.253.10.1", &[]),
+        ("fe80::1", &["link-local"]),
+        ("febf::1", &["link-local"]),
+        ("fec0::1", &[]),
+        ("224.0.0.251", &["multicast"]),
+        ("239.255.255.250", &["multicast"]),
+        ("223.255.255.255", &[]),
+        ("240.0.0.1", &[]),
+        ("ff02::fb", &["multicast"]),
+        ("192.0.2.1", &["documentation"]),
+        ("198.51.100.7", &["documentation"]),
+        ("203.0.113.200", &["documentation"]),
+        ("192.0.3.1", &[]),
+        ("2001:db8::1", &["documentation"]),
+        ("2001:db9::1", &[]),
+        ("3fff:fff::1", &["documentation"]),
+        ("3fff:1000::1", &[]),
+        ("3ff0::1", &[]),
+        ("0.0.0.0", &["unspecified"]),
+        ("::", &["unspecified"]),
+        ("::ffff:192.168.0.1", &["ipv4-mapped"]),
+        ("::ffff:127.0.0.1", &["ipv4-mapped"]),
+        ("8.8.8.8", &[]),
+        ("2606:4700::1111", &[]),
+    ];
+
+    #[test]
+    fn classifies_every_address_in_the_table() {
+        for &(text, expected) in EXPECTED {
+            let addr: IpAddrVariant = text.parse().unwrap();
+            let found: Vec<&str> = CLASSES
+                .iter()
+                .filter(|(_, is)| is(&addr))
+                .map(|&(name, _)| name)
+                .collect();
+            assert_eq!(found, expected, "classes of {}", text);
+        }
+    }
+
+    #[test]
+    fn mapped_address_is_classified_through_to_ipv4_mapped() {
+        let addr: IpAddrVariant = "::ffff:127.0.0.1".parse().unwrap();
+        assert!(!addr.is_loopback());
+        assert!(addr.to_ipv4_mapped().unwrap().is_loopback());
+    }
+}
@@ -0,0 +1,168 @@
+/* The IpAddrVariant enum from the 'Enums' section lives in the library so
+ * that the address code built on top of it can be shared. See src/main.rs
+ * for the walkthrough.
+ *
+ * The book stores a V6 address as a String, which holds any text at all.
+ * Here it holds the eight 16-bit groups an IPv6 address is made of, just as
+ * V4 holds its four 8-bit numbers. Addresses are read from text with
+ * 'parse' (see src/parse.rs) and written back in their canonical form:
+ *
+ *   IPv4   four decimal numbers separated by '.', as in 192.168.0.1
+ *   IPv6   groups in lowercase hex without leading zeros, the longest run of
+ *          two or more zero groups written as '::' (the first one if there
+ *          is a tie), as in 2001:db8::1. Addresses of the form
+ *          ::ffff:a.b.c.d, which carry an IPv4 address, keep the dotted
+ *          quad at the end. */
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub mod cidr;
+mod classify;
+pub mod parse;
+pub mod route;
+
+pub use cidr::Cidr;
+pub use parse::AddrParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IpAddrVariant {
+    V4(u8, u8, u8, u8),
+    V6([u16; 8]),
+}
+
+impl IpAddrVariant {
+    pub fn is_v4(&self) -> bool {
+        matches!(self, IpAddrVariant::V4(..))
+    }
+
+    pub fn is_v6(&self) -> bool {
+        matches!(self, IpAddrVariant::V6(_))
+    }
+
+    /* The address as bytes, most significant first: 4 of them for V4 and
+     * 16 for V6. */
+    pub fn octets(&self) -> Vec<u8> {
+        match *self {
+            IpAddrVariant::V4(a, b, c, d) => vec![a, b, c, d],
+            IpAddrVariant::V6(groups) => groups.iter().flat_map(|g| g.to_be_bytes()).collect(),
+        }
+    }
+
+    /* How many bits the address has: 32 or 128. */
+    pub fn bit_width(&self) -> u8 {
+        match self {
+            IpAddrVariant::V4(..) => 32,
+            IpAddrVariant::V6(_) => 128,
+        }
+    }
+
+    /* The address as one number, as used for prefix arithmetic. */
+    pub fn to_bits(&self) -> u128 {
+        match *self {
+            IpAddrVariant::V4(a, b, c, d) => u128::from(u32::from_be_bytes([a, b, c, d])),
+            IpAddrVariant::V6(groups) => groups
+                .iter()
+                .fold(0, |bits, &group| bits << 16 | u128::from(group)),
+        }
+    }
+
+    /* An address of the same kind as this one with the given bits. Bits
+     * beyond the width of a V4 address are dropped. */
+    pub(crate) fn with_bits(&self, bits: u128) -> IpAddrVariant {
+        let bytes = bits.to_be_bytes();
+        match self {
+            IpAddrVariant::V4(..) => IpAddrVariant::V4(bytes[12], bytes[13], bytes[14], bytes[15]),
+            IpAddrVariant::V6(_) => {
+                let mut groups = [0; 8];
+                for (group, pair) in groups.iter_mut().zip(bytes.chunks(2)) {
+                    *group = u16::from_be_bytes([pair[0], pair[1]]);
+                }
+                IpAddrVariant::V6(groups)
+            }
+        }
+    }
+
+    /* The IPv4 address carried in an IPv4-mapped IPv6 address
+     * (::ffff:a.b.c.d), if this is one. */
+    pub fn to_ipv4_mapped(&self) -> Option<IpAddrVariant> {
+        match *self {
+            IpAddrVariant::V6([0, 0, 0, 0, 0, 0xffff, high, low]) => {
+                let [a, b] = high.to_be_bytes();
+                let [c, d] = low.to_be_bytes();
+                Some(IpAddrVariant::V4(a, b, c, d))
+            }
+            _ => None,
+        }
+    }
+}
+
+/* Written with 'pad' so that widths such as "{:<15}" line addresses up. */
+impl fmt::Display for IpAddrVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match *self {
+            IpAddrVariant::V4(a, b, c, d) => format!("{}.{}.{}.{}", a, b, c, d),
+            IpAddrVariant::V6(groups) => match self.to_ipv4_mapped() {
+                Some(v4) => format!("::ffff:{}", v4),
+                None => {
+                    let hex = |groups: &[u16]| {
+                        let groups: Vec<String> =
+                            groups.iter().map(|g| format!("{:x}", g)).collect();
+                        groups.join(":")
+                    };
+                    match longest_zero_run(&groups) {
+                        Some((start, end)) => {
+                            format!("{}::{}", hex(&groups[..start]), hex(&groups[end..]))
+                        }
+                        None => hex(&groups),
+                    }
+                }
+            },
+        };
+        f.pad(&text)
+    }
+}
+
+/* The start and end of the first longest run of at least two zero groups. */
+fn longest_zero_run(groups: &[u16; 8]) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = 0;
+    while start < groups.len() {
+        if groups[start] != 0 {
+            start += 1;
+            continue;
+        }
+        let end = (start..groups.len())
+            .find(|&i| groups[i] != 0)
+            .unwrap_or(groups.len());
+        let longer = best.is_none_or(|(s, e)| end - start > e - s);
+        if end - start >= 2 && longer {
+            best = Some((start, end));
+        }
+        start = end;
+    }
+    best
+}
+
+impl From<IpAddr> for IpAddrVariant {
+    fn from(addr: IpAddr) -> IpAddrVariant {
+        match addr {
+            IpAddr::V4(v4) => {
+                let [a, b, c, d] = v4.octets();
+                IpAddrVariant::V4(a, b, c, d)
+            }
+            IpAddr::V6(v6) => IpAddrVariant::V6(v6.segments()),
+        }
+    }
+}
+
+impl From<IpAddrVariant> for IpAddr {
+    fn from(addr: IpAddrVariant) -> IpAddr {
+        match addr {
+            IpAddrVariant::V4(a, b, c, d) => IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+            IpAddrVariant::V6(g) => IpAddr::V6(Ipv6Addr::new(
+                g[0], g[1], g[2], g[3], g[4], g[5], g[6], g[7],
+            )),
+        }
+    }
+}
@@ -6,6 +6,13 @@
  *
  * */
 
+use enums::cidr::aggregate;
+use enums::route::RoutingTable;
+use enums::{Cidr, IpAddrVariant};
+
+/* A test such as 'IpAddrVariant::is_loopback'. */
+type Predicate = fn(&IpAddrVariant) -> bool;
+
 enum IpAddrKind {
     V4,
     V6,
@@ -24,12 +31,15 @@ fn main() {
 
     /* Variants of the enum are namespaced under its indentifier. We use
      * a double colon to separate the two. We can write a function that
-     * takes any IpAddrKind. Check 'route' function below. */
+     * takes any IpAddrKind. Check 'default_route' function below. */
 
-    /* We can call 'route' function with either variant. */
+    /* We can call 'default_route' function with either variant. It gives
+     * the network that every address of that kind is in, which a routing
+     * table uses for addresses no other route matches. See 'Routing' at the
+     * end for the table itself. */
 
-    route(IpAddrKind::V4);
-    route(IpAddrKind::V6);
+    let default_v4 = default_route(four);
+    let default_v6 = default_route(six);
 
     /* We can give meaning to a IpAddrKind enum by using a struct 'IpAddr'
      * like below
@@ -51,6 +61,14 @@ fn main() {
         address: String::from("::1"),
     };
 
+    for ip in &[home, loopback] {
+        let version = match ip.kind {
+            IpAddrKind::V4 => 4,
+            IpAddrKind::V6 => 6,
+        };
+        println!("{} is an IPv{} address", ip.address, version);
+    }
+
     /* We can represent the same concept in a more concise way just using an
      * enum, rather than an enum inside a struct, by putting data directly
      * into each variant. See the definition of IpAddrEnum above. */
@@ -58,6 +76,13 @@ fn main() {
     let home = IpAddrEnum::V4(String::from("127.0.0.1"));
     let loopback = IpAddrEnum::V6(String::from("::1"));
 
+    for ip in &[home, loopback] {
+        match ip {
+            IpAddrEnum::V4(address) => println!("{} is an IPv4 address", address),
+            IpAddrEnum::V6(address) => println!("{} is an IPv6 address", address),
+        }
+    }
+
     /* Like depicted above, we can attach data to each variant of the enum
      * directly, so there is no need for an extra struct.
      *
@@ -70,13 +95,18 @@ fn main() {
      * wouldn't be able to with a struct.
      * */
 
-    enum IpAddrVariant {
-        V4(u8, u8, u8, u8),
-        V6(String),
-    }
+    /* IpAddrVariant is defined in src/lib.rs. In the book V6 holds a String;
+     * there it holds the eight 16-bit groups of the address, and both kinds
+     * can be parsed from text:
+     *
+     * enum IpAddrVariant {
+     *     V4(u8, u8, u8, u8),
+     *     V6([u16; 8]),
+     * }
+     * */
 
     let home = IpAddrVariant::V4(127, 0, 0, 1);
-    let loopback = IpAddrVariant::V6(String::from("::1"));
+    let loopback: IpAddrVariant = "::1".parse().unwrap();
 
     /* The standard library has a definition for IP address we can use. It
      * uses 2 structs inside an enum like below.
@@ -115,6 +145,264 @@ fn main() {
     }
     struct WriteMessage(String); // tuple struct
     struct ChangeColorMessage(i32, i32, i32); // tuple struct
+
+    /* One function can take any of the Message variants and match on them
+     * to get at their data. */
+
+    for message in &[
+        Message::Quit,
+        Message::Move { x: 10, y: 20 },
+        Message::Write(String::from("hello")),
+        Message::ChangeColor(0, 128, 255),
+    ] {
+        match message {
+            Message::Quit => println!("quit"),
+            Message::Move { x, y } => println!("move to ({}, {})", x, y),
+            Message::Write(text) => println!("write {:?}", text),
+            Message::ChangeColor(r, g, b) => println!("change color to ({}, {}, {})", r, g, b),
+        }
+    }
+
+    /* With the structs, each kind of message is a different type, so no one
+     * function could take all four. The unit struct holds no data at all. */
+
+    let quit = QuitMessage;
+    let move_to = MoveMessage { x: 10, y: 20 };
+    let write = WriteMessage(String::from("hello"));
+    let change_color = ChangeColorMessage(0, 128, 255);
+    println!(
+        "QuitMessage takes {} bytes; move to ({}, {}); write {:?}; change color to ({}, {}, {})",
+        std::mem::size_of_val(&quit),
+        move_to.x,
+        move_to.y,
+        write.0,
+        change_color.0,
+        change_color.1,
+        change_color.2
+    );
+
+    /* Parsing addresses */
+
+    /* IpAddrEnum::V4(String) will hold any text, "hello" included. Parsing
+     * into IpAddrVariant checks the address and says what is wrong with it.
+     * Printing gives the canonical form, the same one the standard library
+     * prints, and converting to and from std::net::IpAddr is lossless. */
+
+    for text in &[
+        "192.168.0.1",
+        "2001:0DB8:0000:0000:0000:0000:0000:0001",
+        "fe80::1:0:0:0:1",
+        "::ffff:192.0.2.128",
+        "64:ff9b::192.0.2.33",
+        "::",
+        "256.1.1.1",
+        "10.0.01.1",
+        "1.2.3",
+        "1::2::3",
+        "1:2:3:4:5:6:7:8:9",
+        "2001:db8::g",
+        "1.2.3.4::",
+        "hello",
+    ] {
+        match text.parse::<IpAddrVariant>() {
+            Ok(addr) => {
+                let std_addr = std::net::IpAddr::from(addr);
+                println!(
+                    "{:<42} {:<24} std: {:<24} back: {}",
+                    text,
+                    addr,
+                    std_addr,
+                    IpAddrVariant::from(std_addr) == addr
+                );
+            }
+            Err(e) => println!("{:<42} error: {}", text, e),
+        }
+    }
+    println!("{} and {}", home, loopback);
+
+    /* Networks */
+
+    /* A network is an address and a prefix length, such as 10.0.0.0/8: all
+     * the addresses whose first 8 bits are those of 10.0.0.0. The same
+     * arithmetic works for IPv4 and IPv6. */
+
+    for text in &[
+        "192.168.1.0/24",
+        "10.0.0.0/8",
+        "203.0.113.8/31",
+        "fe80::/10",
+    ] {
+        let network: Cidr = text.parse().unwrap();
+        let (first, last) = network.hosts();
+        let broadcast = match network.broadcast() {
+            Some(broadcast) => broadcast.to_string(),
+            None => String::from("none"),
+        };
+        println!(
+            "{:<16} network {:<15} netmask {:<15} broadcast {:<15} hosts {} - {}",
+            text,
+            network,
+            network.netmask(),
+            broadcast,
+            first,
+            last
+        );
+    }
+    let host: IpAddrVariant = "192.168.1.77".parse().unwrap();
+    let lan = Cidr::containing(host, 24).unwrap();
+    println!("{} is in {}", host, lan);
+    for text in &["192.168.1.200", "192.168.2.1", "::ffff:192.168.1.200"] {
+        let addr: IpAddrVariant = text.parse().unwrap();
+        println!("{} contains {}: {}", lan, addr, lan.contains(addr));
+    }
+
+    /* Splitting a network and aggregating the pieces gives it back. */
+
+    let quarters: Vec<Cidr> = lan.subnets(26).unwrap().collect();
+    for quarter in &quarters {
+        println!("{} has {} addresses", quarter, quarter.size().unwrap());
+    }
+    let names =
+        |networks: Vec<Cidr>| -> Vec<String> { networks.iter().map(Cidr::to_string).collect() };
+    println!("aggregated back: {:?}", names(aggregate(&quarters)));
+    let scattered: Vec<Cidr> = [
+        "10.0.2.0/24",
+        "10.0.0.0/24",
+        "10.0.1.0/24",
+        "10.0.3.0/24",
+        "10.0.3.128/25",
+        "10.0.5.0/24",
+        "2001:db8::/33",
+        "2001:db8:8000::/33",
+    ]
+    .iter()
+    .map(|text| text.parse().unwrap())
+    .collect();
+    println!("aggregated: {:?}", names(aggregate(&scattered)));
+    for text in &[
+        "10.0.0.0",
+        "10.0.0.0/33",
+        "10.0.0.0/x",
+        "10.0.0/8",
+        "192.168.1.77/24",
+    ] {
+        if let Err(e) = text.parse::<Cidr>() {
+            println!("{}: {}", text, e);
+        }
+    }
+    if let Err(e) = lan.subnets(16) {
+        println!("{} into /16s: {}", lan, e);
+    }
+
+    /* Routing */
+
+    /* A routing table sends each address to the next hop of the most
+     * specific network containing it. The default routes from
+     * 'default_route' catch everything no other route does. */
+
+    let mut table = RoutingTable::new();
+    table.insert(default_v4, "isp");
+    table.insert(default_v6, "isp");
+    for &(network, next_hop) in &[
+        ("10.0.0.0/8", "vpn"),
+        ("10.1.0.0/16", "office"),
+        ("10.1.2.0/24", "lab"),
+        ("192.168.1.0/24", "lan"),
+        ("2001:db8::/32", "docs"),
+        ("fe80::/10", "link"),
+    ] {
+        table.insert(network.parse().unwrap(), next_hop);
+    }
+    let addrs: Vec<IpAddrVariant> = [
+        "10.1.2.3",
+        "10.1.9.9",
+        "10.200.0.1",
+        "192.168.1.20",
+        "8.8.8.8",
+        "2001:db8::1",
+        "fe80::1",
+        "2606:4700::1111",
+    ]
+    .iter()
+    .map(|text| text.parse().unwrap())
+    .collect();
+    let show = |table: &RoutingTable<&str>| {
+        for &addr in &addrs {
+            match table.route(addr) {
+                Some(route) => {
+                    println!("{:<16} via {:<7} ({})", addr, route.next_hop, route.network)
+                }
+                None => println!("{:<16} unreachable", addr),
+            }
+        }
+    };
+    show(&table);
+    let office: Cidr = "10.1.0.0/16".parse().unwrap();
+    println!(
+        "removed {}: {:?}, {} routes left",
+        office,
+        table.remove(&office),
+        table.len()
+    );
+    table.remove(&default_v4);
+    show(&table);
+    for route in table.iter() {
+        println!("{:<16} {}", route.network, route.next_hop);
+    }
+
+    /* Classifying addresses */
+
+    /* Each class of address has a predicate; an address can be in none of
+     * them, as a public address is. Both addresses from the start of the
+     * walkthrough are loopback. The full table of expected classes is
+     * checked by the tests in src/classify.rs. */
+
+    let classes: [(&str, Predicate); 7] = [
+        ("loopback", IpAddrVariant::is_loopback),
+        ("private", IpAddrVariant::is_private),
+        ("link-local", IpAddrVariant::is_link_local),
+        ("multicast", IpAddrVariant::is_multicast),
+        ("documentation", IpAddrVariant::is_documentation),
+        ("unspecified", IpAddrVariant::is_unspecified),
+        ("ipv4-mapped", IpAddrVariant::is_ipv4_mapped),
+    ];
+    for text in &[
+        "127.0.0.1",
+        "192.168.0.1",
+        "fe80::1",
+        "ff02::fb",
+        "2001:db8::1",
+        "::",
+        "::ffff:127.0.0.1",
+        "8.8.8.8",
+    ] {
+        let addr: IpAddrVariant = text.parse().unwrap();
+        let found: Vec<&str> = classes
+            .iter()
+            .filter(|(_, is)| is(&addr))
+            .map(|&(name, _)| name)
+            .collect();
+        if found.is_empty() {
+            println!("{:<18} public", addr);
+        } else {
+            println!("{:<18} {}", addr, found.join(", "));
+        }
+    }
+    println!(
+        "{} and {} are loopback: {} and {}",
+        home,
+        loopback,
+        home.is_loopback(),
+        loopback.is_loopback()
+    );
 }
 
-fn route(ip_kind: IpAddrKind) {}
+/* The book's 'route' took an IpAddrKind and did nothing with it. Routing
+ * an address is done by RoutingTable::route in src/route.rs. */
+fn default_route(ip_kind: IpAddrKind) -> Cidr {
+    let addr = match ip_kind {
+        IpAddrKind::V4 => IpAddrVariant::V4(0, 0, 0, 0),
+        IpAddrKind::V6 => IpAddrVariant::V6([0; 8]),
+    };
+    Cidr::new(addr, 0).unwrap()
+}
@@ -0,0 +1,329 @@
+/* Reading addresses from text.
+ *
+ * Text with a ':' in it is read as IPv6, anything else as IPv4.
+ *
+ * IPv4 is four decimal numbers from 0 to 255 separated by '.'. Leading
+ * zeros are refused, since some programs read "010" as octal 8 and others
+ * as decimal 10.
+ *
+ * IPv6 is eight groups of one to four hex digits separated by ':'. One run
+ * of zero groups may be left out and replaced by '::', as in "fe80::1", and
+ * the last two groups may be written as an IPv4 address, as in
+ * "::ffff:192.0.2.1". Zone ids ("fe80::1%eth0") are not supported. */
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::IpAddrVariant;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddrParseError {
+    Empty,
+    /* An IPv4 address with other than four numbers. */
+    OctetCount(usize),
+    InvalidOctet(String),
+    LeadingZero(String),
+    /* An IPv6 address with the wrong number of groups for whether it has
+     * '::' in it. */
+    GroupCount(usize),
+    InvalidGroup(String),
+    DoubleColonTwice,
+    /* An embedded IPv4 address anywhere but at the end. */
+    MisplacedV4(String),
+}
+
+impl fmt::Display for AddrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddrParseError::Empty => write!(f, "an address cannot be empty"),
+            AddrParseError::OctetCount(count) => write!(
+                f,
+                "an IPv4 address has 4 numbers separated by '.', found {}",
+                count
+            ),
+            AddrParseError::InvalidOctet(octet) => {
+                write!(f, "{:?} is not a number from 0 to 255", octet)
+            }
+            AddrParseError::LeadingZero(octet) => {
+                write!(f, "{:?} has a leading zero", octet)
+            }
+            AddrParseError::GroupCount(count) => write!(
+                f,
+                "an IPv6 address has 8 groups, or fewer with '::', found {}",
+                count
+            ),
+            AddrParseError::InvalidGroup(group) => {
+                write!(f, "{:?} is not a group of 1 to 4 hex digits", group)
+            }
+            AddrParseError::DoubleColonTwice => {
+                write!(f, "'::' can appear only once in an IPv6 address")
+            }
+            AddrParseError::MisplacedV4(v4) => write!(
+                f,
+                "an IPv4 address can only end an IPv6 address, found {:?}",
+                v4
+            ),
+        }
+    }
+}
+
+impl Error for AddrParseError {}
+
+impl FromStr for IpAddrVariant {
+    type Err = AddrParseError;
+
+    fn from_str(text: &str) -> Result<IpAddrVariant, AddrParseError> {
+        let text = text.trim();
+        if text.is_empty() {
+            Err(AddrParseError::Empty)
+        } else if text.contains(':') {
+            parse_v6(text).map(IpAddrVariant::V6)
+        } else {
+            let [a, b, c, d] = parse_v4(text)?;
+            Ok(IpAddrVariant::V4(a, b, c, d))
+        }
+    }
+}
+
+fn parse_v4(text: &str) -> Result<[u8; 4], AddrParseError> {
+    let parts: Vec<&str> = text.split('.').collect();
+    if parts.len() != 4 {
+        return Err(AddrParseError::OctetCount(parts.len()));
+    }
+    let mut octets = [0; 4];
+    for (octet, part) in octets.iter_mut().zip(parts) {
+        /* u8's own parser accepts a leading '+', which an address can't
+         * have. */
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AddrParseError::InvalidOctet(part.to_string()));
+        }
+        if part.len() > 1 && part.starts_with('0') {
+            return Err(AddrParseError::LeadingZero(part.to_string()));
+        }
+        *octet = part
+            .parse()
+            .map_err(|_| AddrParseError::InvalidOctet(part.to_string()))?;
+    }
+    Ok(octets)
+}
+
+fn parse_v6(text: &str) -> Result<[u16; 8], AddrParseError> {
+    let halves: Vec<&str> = text.split("::").collect();
+    if halves.len() > 2 {
+        return Err(AddrParseError::DoubleColonTwice);
+    }
+    let head = parse_groups(halves[0], halves.len() == 1)?;
+    let tail = match halves.get(1) {
+        Some(half) => parse_groups(half, true)?,
+        None => Vec::new(),
+    };
+    let count = head.len() + tail.len();
+    let compressed = halves.len() == 2;
+    if (compressed && count > 7) || (!compressed && count != 8) {
+        return Err(AddrParseError::GroupCount(count));
+    }
+    let mut groups = [0; 8];
+    groups[..head.len()].copy_from_slice(&head);
+    groups[8 - tail.len()..].copy_from_slice(&tail);
+    Ok(groups)
+}
+
+/* The groups on one side of a '::'. Only the side that ends the address may
+ * end in an IPv4 address, which counts as two groups. */
+fn parse_groups(text: &str, ends_address: bool) -> Result<Vec<u16>, AddrParseError> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    let parts: Vec<&str> = text.split(':').collect();
+    let mut groups = Vec::with_capacity(parts.len() + 1);
+    for (i, part) in parts.iter().enumerate() {
+        if part.contains('.') {
+            if !ends_address || i != parts.len() - 1 {
+                return Err(AddrParseError::MisplacedV4(part.to_string()));
+            }
+            let [a, b, c, d] = parse_v4(part)?;
+            groups.push(u16::from_be_bytes([a, b]));
+            groups.push(u16::from_be_bytes([c, d]));
+        } else if (1..=4).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_hexdigit()) {
+            groups.push(u16::from_str_radix(part, 16).unwrap());
+        } else {
+            return Err(AddrParseError::InvalidGroup(part.to_string()));
+        }
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    fn parse(text: &str) -> Result<IpAddrVariant, AddrParseError> {
+        text.parse()
+    }
+
+    #[test]
+    fn reads_ipv4() {
+        assert_eq!(parse("192.0.2.1"), Ok(IpAddrVariant::V4(192, 0, 2, 1)));
+        assert_eq!(parse("0.0.0.0"), Ok(IpAddrVariant::V4(0, 0, 0, 0)));
+        assert_eq!(
+            parse(" 255.255.255.255 "),
+            Ok(IpAddrVariant::V4(255, 255, 255, 255))
+        );
+        assert_eq!(parse(""), Err(AddrParseError::Empty));
+        assert_eq!(parse("   "), Err(AddrParseError::Empty));
+    }
+
+    #[test]
+    fn refuses_leading_zeros() {
+        for &(text, octet) in &[
+            ("010.0.0.1", "010"),
+            ("10.0.0.01", "01"),
+            ("1.00.2.3", "00"),
+        ] {
+            assert_eq!(
+                parse(text),
+                Err(AddrParseError::LeadingZero(octet.to_string())),
+                "{}",
+                text
+            );
+        }
+        /* A group of IPv6 may have leading zeros; an embedded IPv4 may not. */
+        assert_eq!(parse("2001:0db8::0001"), parse("2001:db8::1"));
+        assert_eq!(
+            parse("::ffff:192.0.2.01"),
+            Err(AddrParseError::LeadingZero("01".to_string()))
+        );
+    }
+
+    #[test]
+    fn refuses_bad_octets() {
+        for &(text, octet) in &[
+            ("256.0.0.1", "256"),
+            ("1.2.3.999", "999"),
+            ("1.2.3.99999999999", "99999999999"),
+            ("1.2.3.-1", "-1"),
+            ("1.2.3.+1", "+1"),
+            ("1..3.4", ""),
+            ("1.2.3.a", "a"),
+        ] {
+            assert_eq!(
+                parse(text),
+                Err(AddrParseError::InvalidOctet(octet.to_string())),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse("1.2.3"), Err(AddrParseError::OctetCount(3)));
+        assert_eq!(parse("1.2.3.4.5"), Err(AddrParseError::OctetCount(5)));
+        assert_eq!(parse("1.2.3.4."), Err(AddrParseError::OctetCount(5)));
+    }
+
+    #[test]
+    fn refuses_more_than_one_double_colon() {
+        for text in &["1::2::3", "::1::", "::::", "1:2::3:4::"] {
+            assert_eq!(
+                parse(text),
+                Err(AddrParseError::DoubleColonTwice),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_bad_groups() {
+        assert_eq!(parse("1:2:3:4:5:6:7"), Err(AddrParseError::GroupCount(7)));
+        assert_eq!(
+            parse("1:2:3:4:5:6:7:8:9"),
+            Err(AddrParseError::GroupCount(9))
+        );
+        assert_eq!(
+            parse("1:2:3:4::5:6:7:8"),
+            Err(AddrParseError::GroupCount(8))
+        );
+        assert_eq!(
+            parse("12345::"),
+            Err(AddrParseError::InvalidGroup("12345".to_string()))
+        );
+        assert_eq!(
+            parse("g::"),
+            Err(AddrParseError::InvalidGroup("g".to_string()))
+        );
+        assert_eq!(
+            parse(":::"),
+            Err(AddrParseError::InvalidGroup(String::new()))
+        );
+        assert_eq!(
+            parse("1:2:3:4:5:6:7:"),
+            Err(AddrParseError::InvalidGroup(String::new()))
+        );
+        assert_eq!(
+            parse(":1::"),
+            Err(AddrParseError::InvalidGroup(String::new()))
+        );
+        assert_eq!(
+            parse("fe80::1%eth0"),
+            Err(AddrParseError::InvalidGroup("1%eth0".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_embedded_ipv4() {
+        assert_eq!(
+            parse("::ffff:192.0.2.1"),
+            Ok(IpAddrVariant::V6([0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0201]))
+        );
+        assert_eq!(
+            parse("64:ff9b::203.0.113.5"),
+            Ok(IpAddrVariant::V6([
+                0x64, 0xff9b, 0, 0, 0, 0, 0xcb00, 0x7105
+            ]))
+        );
+        assert_eq!(parse("1:2:3:4:5:6:1.2.3.4"), parse("1:2:3:4:5:6:102:304"));
+        assert_eq!(parse("::1.2.3.4"), parse("::102:304"));
+        assert_eq!(
+            parse("1:2:3:4:5:6:7:1.2.3.4"),
+            Err(AddrParseError::GroupCount(9))
+        );
+        for text in &["1.2.3.4::", "::1.2.3.4:1", "1:1.2.3.4::5", "1.2.3.4::1"] {
+            assert!(
+                matches!(parse(text), Err(AddrParseError::MisplacedV4(_))),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse("::ffff:1.2.3"), Err(AddrParseError::OctetCount(3)));
+        assert_eq!(
+            parse("::ffff:1.2.3.256"),
+            Err(AddrParseError::InvalidOctet("256".to_string()))
+        );
+    }
+
+    #[test]
+    fn round_trips_through_canonical_form() {
+        for &(text, canonical) in &[
+            ("192.0.2.1", "192.0.2.1"),
+            ("::", "::"),
+            ("::1", "::1"),
+            ("1::", "1::"),
+            ("2001:DB8:0:0:0:0:0:1", "2001:db8::1"),
+            ("2001:db8:0:0:1:0:0:1", "2001:db8::1:0:0:1"),
+            ("2001:db8:0:1:1:1:1:1", "2001:db8:0:1:1:1:1:1"),
+            ("2001:0:0:1:0:0:0:1", "2001:0:0:1::1"),
+            ("fe80:0000::0001", "fe80::1"),
+            ("0:0:0:0:0:ffff:c000:201", "::ffff:192.0.2.1"),
+            ("::ffff:0:0", "::ffff:0.0.0.0"),
+            ("1:2:3:4:5:6:7:8", "1:2:3:4:5:6:7:8"),
+        ] {
+            let addr = parse(text).unwrap();
+            assert_eq!(addr.to_string(), canonical, "{}", text);
+            assert_eq!(parse(canonical), Ok(addr));
+            /* The standard library agrees on the canonical form. */
+            let std: IpAddr = text.parse().unwrap();
+            assert_eq!(std.to_string(), canonical, "{}", text);
+            assert_eq!(IpAddrVariant::from(std), addr);
+        }
+    }
+}
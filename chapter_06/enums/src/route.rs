@@ -0,0 +1,309 @@
+/* A routing table.
+ *
+ * Each route sends the addresses of one network to a next hop, which can be
+ * any data: a gateway address, an interface name, and so on. An address
+ * can be in several of the networks, as 10.1.2.3 is in 10.0.0.0/8,
+ * 10.1.0.0/16 and the default route 0.0.0.0/0. The most specific network,
+ * the one with the longest prefix, wins.
+ *
+ * Routes are kept in a binary trie, one for IPv4 and one for IPv6. Each
+ * level of the trie is one bit of the address, and a route hangs off the
+ * node its prefix leads to. Finding the route for an address walks down
+ * the trie along the address's bits, remembering the last route passed.
+ * That is at most 32 steps for IPv4 and 128 for IPv6, however many routes
+ * the table holds. */
+
+use crate::{Cidr, IpAddrVariant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route<T> {
+    pub network: Cidr,
+    pub next_hop: T,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    route: Option<Route<T>>,
+    children: [Option<Box<Node<T>>>; 2],
+}
+
+impl<T> Node<T> {
+    fn new() -> Node<T> {
+        Node {
+            route: None,
+            children: [None, None],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.route.is_none() && self.children.iter().all(Option::is_none)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutingTable<T> {
+    v4: Node<T>,
+    v6: Node<T>,
+    len: usize,
+}
+
+impl<T> Default for RoutingTable<T> {
+    fn default() -> RoutingTable<T> {
+        RoutingTable::new()
+    }
+}
+
+impl<T> RoutingTable<T> {
+    pub fn new() -> RoutingTable<T> {
+        RoutingTable {
+            v4: Node::new(),
+            v6: Node::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /* Adds a route, returning the next hop it replaced if the network was
+     * already in the table. */
+    pub fn insert(&mut self, network: Cidr, next_hop: T) -> Option<T> {
+        let path = Path::of(network.network());
+        let mut node = self.root_mut(network.network());
+        for depth in 0..network.prefix() {
+            let child = &mut node.children[path.bit(depth)];
+            node = child.get_or_insert_with(|| Box::new(Node::new()));
+        }
+        let old = node.route.replace(Route { network, next_hop });
+        if old.is_none() {
+            self.len += 1;
+        }
+        old.map(|route| route.next_hop)
+    }
+
+    /* Removes the route for exactly this network, returning its next hop. */
+    pub fn remove(&mut self, network: &Cidr) -> Option<T> {
+        let path = Path::of(network.network());
+        let removed = remove(self.root_mut(network.network()), &path, network.prefix(), 0);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /* The route for exactly this network, if there is one. */
+    pub fn get(&self, network: &Cidr) -> Option<&Route<T>> {
+        let path = Path::of(network.network());
+        let mut node = self.root(network.network());
+        for depth in 0..network.prefix() {
+            node = node.children[path.bit(depth)].as_deref()?;
+        }
+        node.route.as_ref()
+    }
+
+    /* The route with the longest prefix whose network contains 'addr', or
+     * None if no route matches. */
+    pub fn route(&self, addr: IpAddrVariant) -> Option<&Route<T>> {
+        let path = Path::of(addr);
+        let mut node = self.root(addr);
+        let mut best = node.route.as_ref();
+        for depth in 0..path.width {
+            match node.children[path.bit(depth)].as_deref() {
+                Some(child) => node = child,
+                None => break,
+            }
+            best = node.route.as_ref().or(best);
+        }
+        best
+    }
+
+    /* Every route, IPv4 first, ordered by network and then by prefix
+     * length. */
+    pub fn iter(&self) -> impl Iterator<Item = &Route<T>> {
+        let mut routes = Vec::with_capacity(self.len);
+        collect(&self.v4, &mut routes);
+        collect(&self.v6, &mut routes);
+        routes.into_iter()
+    }
+
+    fn root(&self, addr: IpAddrVariant) -> &Node<T> {
+        match addr {
+            IpAddrVariant::V4(..) => &self.v4,
+            IpAddrVariant::V6(_) => &self.v6,
+        }
+    }
+
+    fn root_mut(&mut self, addr: IpAddrVariant) -> &mut Node<T> {
+        match addr {
+            IpAddrVariant::V4(..) => &mut self.v4,
+            IpAddrVariant::V6(_) => &mut self.v6,
+        }
+    }
+}
+
+/* The bits of an address, worked out once before walking down the trie
+ * rather than at every level. */
+struct Path {
+    bits: u128,
+    width: u8,
+}
+
+impl Path {
+    fn of(addr: IpAddrVariant) -> Path {
+        Path {
+            bits: addr.to_bits(),
+            width: addr.bit_width(),
+        }
+    }
+
+    /* Bit 'depth' of the address, counting from the most significant. */
+    fn bit(&self, depth: u8) -> usize {
+        (self.bits >> (self.width - 1 - depth) & 1) as usize
+    }
+}
+
+/* Removes the route below 'node', pruning the nodes left with nothing in
+ * them on the way back up. */
+fn remove<T>(node: &mut Node<T>, path: &Path, prefix: u8, depth: u8) -> Option<T> {
+    if depth == prefix {
+        return node.route.take().map(|route| route.next_hop);
+    }
+    let slot = &mut node.children[path.bit(depth)];
+    let child = slot.as_deref_mut()?;
+    let removed = remove(child, path, prefix, depth + 1);
+    if child.is_empty() {
+        *slot = None;
+    }
+    removed
+}
+
+fn collect<'a, T>(node: &'a Node<T>, routes: &mut Vec<&'a Route<T>>) {
+    routes.extend(node.route.as_ref());
+    for child in node.children.iter().flatten() {
+        collect(child, routes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(routes: &[(&str, &'static str)]) -> RoutingTable<&'static str> {
+        let mut table = RoutingTable::new();
+        for &(network, next_hop) in routes {
+            assert_eq!(table.insert(network.parse().unwrap(), next_hop), None);
+        }
+        table
+    }
+
+    fn next_hop(table: &RoutingTable<&'static str>, addr: &str) -> Option<&'static str> {
+        table
+            .route(addr.parse().unwrap())
+            .map(|route| route.next_hop)
+    }
+
+    #[test]
+    fn default_route_matches_everything_of_its_kind() {
+        let table = table(&[("0.0.0.0/0", "v4 default")]);
+        assert_eq!(next_hop(&table, "0.0.0.0"), Some("v4 default"));
+        assert_eq!(next_hop(&table, "203.0.113.9"), Some("v4 default"));
+        assert_eq!(next_hop(&table, "255.255.255.255"), Some("v4 default"));
+        assert_eq!(next_hop(&table, "2001:db8::1"), None);
+    }
+
+    #[test]
+    fn longest_overlapping_prefix_wins() {
+        let table = table(&[
+            ("0.0.0.0/0", "default"),
+            ("10.0.0.0/8", "/8"),
+            ("10.1.0.0/16", "/16"),
+            ("10.1.2.0/24", "/24"),
+            ("10.1.2.3/32", "/32"),
+        ]);
+        assert_eq!(next_hop(&table, "10.1.2.3"), Some("/32"));
+        assert_eq!(next_hop(&table, "10.1.2.4"), Some("/24"));
+        assert_eq!(next_hop(&table, "10.1.3.1"), Some("/16"));
+        assert_eq!(next_hop(&table, "10.2.0.1"), Some("/8"));
+        assert_eq!(next_hop(&table, "11.0.0.1"), Some("default"));
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_are_kept_apart() {
+        let table = table(&[
+            ("0.0.0.0/1", "low v4"),
+            ("::/0", "v6 default"),
+            ("2001:db8::/32", "documentation"),
+            ("::ffff:0:0/96", "mapped"),
+        ]);
+        assert_eq!(next_hop(&table, "10.0.0.1"), Some("low v4"));
+        assert_eq!(next_hop(&table, "192.0.2.1"), None);
+        assert_eq!(next_hop(&table, "2001:db8:1::1"), Some("documentation"));
+        assert_eq!(next_hop(&table, "2001:db9::1"), Some("v6 default"));
+        assert_eq!(next_hop(&table, "::ffff:192.0.2.1"), Some("mapped"));
+        assert_eq!(table.len(), 4);
+    }
+
+    #[test]
+    fn matches_a_brute_force_search() {
+        let networks = [
+            "0.0.0.0/0",
+            "128.0.0.0/1",
+            "192.168.0.0/16",
+            "192.168.1.0/24",
+            "192.168.1.128/25",
+            "fe80::/10",
+            "2000::/3",
+            "2001:db8::/32",
+            "2001:db8::/48",
+        ];
+        let mut table = RoutingTable::new();
+        for network in &networks {
+            table.insert(network.parse::<Cidr>().unwrap(), *network);
+        }
+        for addr in &[
+            "1.2.3.4",
+            "192.168.1.1",
+            "192.168.1.200",
+            "192.168.2.1",
+            "200.0.0.1",
+            "fe80::1",
+            "2001:db8::1",
+            "2001:db8:1::1",
+            "3fff::1",
+            "::1",
+        ] {
+            let addr: IpAddrVariant = addr.parse().unwrap();
+            let expected = networks
+                .iter()
+                .map(|network| network.parse::<Cidr>().unwrap())
+                .filter(|network| network.contains(addr))
+                .max_by_key(Cidr::prefix);
+            assert_eq!(
+                table.route(addr).map(|route| route.network),
+                expected,
+                "route for {}",
+                addr
+            );
+        }
+    }
+
+    #[test]
+    fn insert_replace_and_remove() {
+        let mut table = table(&[("10.0.0.0/8", "a"), ("10.1.0.0/16", "b")]);
+        let network: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert_eq!(table.insert(network, "c"), Some("b"));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(&network).map(|route| route.next_hop), Some("c"));
+        assert_eq!(table.remove(&network), Some("c"));
+        assert_eq!(table.remove(&network), None);
+        assert_eq!(next_hop(&table, "10.1.0.1"), Some("a"));
+        assert_eq!(table.remove(&"10.0.0.0/8".parse().unwrap()), Some("a"));
+        assert!(table.is_empty());
+        assert!(table.v4.is_empty());
+    }
+}